@@ -0,0 +1,131 @@
+use crate::packing::MAX_BYTES_IN_FIELD;
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use light_poseidon::{Poseidon, PoseidonError, PoseidonHasher};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The maximal `max_len` of a committed part, whose packed bytes and salt are hashed by a single Poseidon
+/// instance of at most 12 inputs.
+pub const MAX_COMMITTED_BYTES: usize = 11 * MAX_BYTES_IN_FIELD;
+
+/// Error definitions of the commitments to substrings.
+#[derive(Error, Debug)]
+pub enum CommitmentError {
+    #[error("The substring has {0} bytes but max_len is {1}")]
+    SubstringTooLong(usize, usize),
+    #[error("The max_len {0} must be between 1 and {}", MAX_COMMITTED_BYTES)]
+    InvalidMaxLen(usize),
+    #[error(
+        "Salt {0} is not a decimal number smaller than the field modulus without leading zeros"
+    )]
+    InvalidSalt(String),
+    #[error(transparent)]
+    PoseidonError(#[from] PoseidonError),
+}
+
+/// Computes the commitment of a committed part natively, i.e., the `reveal{idx}_commitment` output of the Circom
+/// template: the circom-compatible Poseidon hash over BN254 of the substring padded with zeros to `max_len`
+/// bytes and packed into field elements of 31 little-endian bytes each, followed by the salt.
+///
+/// # Arguments
+///
+/// * `substr` - The bytes of the committed substring.
+/// * `max_len` - The `max_len` of the part.
+/// * `salt` - The decimal string of the `reveal{idx}_salt` input.
+///
+/// # Returns
+///
+/// A Result containing the decimal string of the commitment, or a CommitmentError.
+pub fn commit_bytes(substr: &[u8], max_len: usize, salt: &str) -> Result<String, CommitmentError> {
+    if max_len == 0 || max_len > MAX_COMMITTED_BYTES {
+        return Err(CommitmentError::InvalidMaxLen(max_len));
+    }
+    if substr.len() > max_len {
+        return Err(CommitmentError::SubstringTooLong(substr.len(), max_len));
+    }
+    let mut padded = substr.to_vec();
+    padded.resize(max_len, 0);
+
+    let mut inputs = padded
+        .chunks(MAX_BYTES_IN_FIELD)
+        .map(Fr::from_le_bytes_mod_order)
+        .collect::<Vec<_>>();
+    // `Fr::from_str` reduces the numbers that are not smaller than the modulus.
+    let salt_field = Fr::from_str(salt)
+        .ok()
+        .filter(|field| field.into_bigint().to_string() == salt)
+        .ok_or_else(|| CommitmentError::InvalidSalt(salt.to_string()))?;
+    inputs.push(salt_field);
+
+    let mut poseidon = Poseidon::<Fr>::new_circom(inputs.len())?;
+    Ok(poseidon.hash(&inputs)?.into_bigint().to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_commit_bytes() {
+        // The Poseidon hash of [1, 2] in circomlibjs.
+        let mut poseidon = Poseidon::<Fr>::new_circom(2).unwrap();
+        assert_eq!(
+            poseidon
+                .hash(&[Fr::from(1u64), Fr::from(2u64)])
+                .unwrap()
+                .into_bigint()
+                .to_string(),
+            "7853200120776062878684798364095072458815029376092732009249414926327459813530"
+        );
+
+        let commitment = commit_bytes(b"alice@zkemail.com", 40, "12345").unwrap();
+        assert_eq!(
+            commit_bytes(b"alice@zkemail.com", 40, "12345").unwrap(),
+            commitment
+        );
+        // The padding is zero, so a substring with trailing zeros has the same commitment.
+        assert_eq!(
+            commit_bytes(b"alice@zkemail.com\0", 40, "12345").unwrap(),
+            commitment
+        );
+        assert_ne!(
+            commit_bytes(b"alice@zkemail.com", 40, "12346").unwrap(),
+            commitment
+        );
+        assert_eq!(
+            commit_bytes(b"alice@zkemail.com", 41, "12345").unwrap(),
+            commitment
+        );
+        assert_ne!(
+            commit_bytes(b"alice@zkemail.com", 70, "12345").unwrap(),
+            commitment
+        );
+        // The commitment of the padding packed by `pack_bytes`.
+        let fields = crate::packing::pack_bytes(b"ab\0\0");
+        let mut poseidon = Poseidon::<Fr>::new_circom(2).unwrap();
+        assert_eq!(
+            commit_bytes(b"ab", 4, "0").unwrap(),
+            poseidon
+                .hash(&[Fr::from_str(&fields[0]).unwrap(), Fr::from(0u64)])
+                .unwrap()
+                .into_bigint()
+                .to_string()
+        );
+
+        assert!(matches!(
+            commit_bytes(b"alice", 4, "1"),
+            Err(CommitmentError::SubstringTooLong(5, 4))
+        ));
+        assert!(matches!(
+            commit_bytes(b"alice", MAX_COMMITTED_BYTES + 1, "1"),
+            Err(CommitmentError::InvalidMaxLen(_))
+        ));
+        for salt in ["", "-1", "0x1", "1 ", "01", &"9".repeat(80)] {
+            assert!(matches!(
+                commit_bytes(b"alice", 8, salt),
+                Err(CommitmentError::InvalidSalt(_))
+            ));
+        }
+    }
+}
@@ -1,15 +1,153 @@
+use crate::leftmost::LeftmostDfa;
 use fancy_regex::Regex;
+use regex::bytes::{Regex as BytesRegex, RegexBuilder as BytesRegexBuilder};
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 use thiserror::Error;
 
 /// A configuration of decomposed regexes.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// The options of the JSON format besides the parts, i.e., the match policy, the constraints and the `max_len` of the
+/// parts, are read by [`DecomposedRegexOptions`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DecomposedRegexConfig {
     pub parts: Vec<RegexPartConfig>,
 }
 
+/// The options of a decomposed regex besides its parts, read from the same JSON as its [`DecomposedRegexConfig`].
+///
+/// The default options are those of [`extract_substr_idxes`], which extracts the leftmost-first captures of the
+/// entire regex without constraints.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecomposedRegexOptions {
+    /// The match revealed by the circuit and extracted by [`extract_substr_idxes_with_options`].
+    #[serde(
+        default,
+        alias = "matchPolicy",
+        skip_serializing_if = "MatchPolicy::is_greedy"
+    )]
+    pub match_policy: MatchPolicy,
+    /// The constraints between the substrings of the parts, which the extraction checks.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<PartConstraint>,
+    /// The `max_len` of each part, i.e., the maximum length of its revealed substring, which is required for a part
+    /// compared by a constraint. It is written in the `parts` of the JSON.
+    #[serde(
+        default,
+        rename = "parts",
+        skip_serializing,
+        deserialize_with = "deserialize_max_lens"
+    )]
+    pub max_lens: Vec<Option<usize>>,
+}
+
+impl DecomposedRegexOptions {
+    /// Returns the `max_len` of the part at `part_idx`, if any.
+    pub fn max_len(&self, part_idx: usize) -> Option<usize> {
+        self.max_lens.get(part_idx).copied().flatten()
+    }
+
+    /// Adds the options to the JSON object of a [`DecomposedRegexConfig`], so that both are read back from it.
+    pub fn write_to_json(&self, config_json: &mut serde_json::Map<String, serde_json::Value>) {
+        if let serde_json::Value::Object(options) =
+            serde_json::to_value(self).expect("the options are serializable")
+        {
+            config_json.extend(options);
+        }
+        if let Some(serde_json::Value::Array(parts)) = config_json.get_mut("parts") {
+            for (part, max_len) in parts.iter_mut().zip(&self.max_lens) {
+                if let (serde_json::Value::Object(part), Some(max_len)) = (part, max_len) {
+                    part.insert("max_len".to_string(), (*max_len).into());
+                }
+            }
+        }
+    }
+}
+
+/// Reads the `max_len` of each part from the `parts` of a decomposed regex JSON.
+fn deserialize_max_lens<'de, D>(deserializer: D) -> Result<Vec<Option<usize>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct PartMaxLen {
+        #[serde(default, alias = "maxLen")]
+        max_len: Option<usize>,
+    }
+
+    Ok(Vec::<PartMaxLen>::deserialize(deserializer)?
+        .into_iter()
+        .map(|part| part.max_len)
+        .collect())
+}
+
+/// Parses a decomposed regex JSON into its [`DecomposedRegexConfig`] and [`DecomposedRegexOptions`].
+pub fn parse_decomposed_regex(
+    json: &serde_json::Value,
+) -> Result<(DecomposedRegexConfig, DecomposedRegexOptions), serde_json::Error> {
+    Ok((
+        DecomposedRegexConfig::deserialize(json)?,
+        DecomposedRegexOptions::deserialize(json)?,
+    ))
+}
+
+/// A reference to a part of a decomposed regex in a [`PartConstraint`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PartRef {
+    /// The index of a part in the `parts` of the same regex.
+    Index(usize),
+    /// The index of a part in the `parts` of the regex with the given name, when several regexes are compiled or
+    /// checked together.
+    Named { regex: String, part: usize },
+}
+
+impl std::fmt::Display for PartRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PartRef::Index(part) => write!(f, "{}", part),
+            PartRef::Named { regex, part } => write!(f, "{} of {}", part, regex),
+        }
+    }
+}
+
+/// A constraint between the substrings of two parts, written as `{"equal": [a, b]}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartConstraint {
+    /// The substrings of the parts are identical.
+    Equal([PartRef; 2]),
+}
+
+/// Which match of a regex is revealed when the input contains several matches or matches of different lengths.
+///
+/// With the leftmost policies, the circuit reveals only the first run of its DFA that reaches the accept state,
+/// i.e., the match starting at the leftmost position the circuit restarts from, so that the prover cannot choose
+/// between matches, e.g., where a `.+` part ends. The parts of the match are split as in the compiled DFA, where a
+/// part ends as soon as the next part can start, which the extraction follows on the same DFA.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchPolicy {
+    /// The original behaviour: the circuit reveals every run of its DFA that reaches the accept state up to its
+    /// last accept state, and the extraction uses the leftmost-first captures of the entire regex.
+    #[default]
+    Greedy,
+    /// The leftmost match, extended to its longest end.
+    LeftmostLongest,
+    /// The leftmost match, ended at its shortest end.
+    LeftmostShortest,
+}
+
+impl MatchPolicy {
+    /// Returns whether this is [`MatchPolicy::Greedy`].
+    pub fn is_greedy(&self) -> bool {
+        *self == MatchPolicy::Greedy
+    }
+}
+
 /// Decomposed regex part.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "RegexPartConfigJson")]
 pub struct RegexPartConfig {
     /// A flag indicating whether the substring matching with `regex_def` should be exposed.
     #[serde(alias = "isPublic")]
@@ -19,69 +157,481 @@ pub struct RegexPartConfig {
     pub regex_def: String,
 }
 
+/// The JSON of a [`RegexPartConfig`], which may carry the `and`, `difference` and `not` operators of the compiler.
+#[derive(Deserialize)]
+struct RegexPartConfigJson {
+    #[serde(alias = "isPublic")]
+    is_public: bool,
+    #[serde(alias = "regexDef")]
+    regex_def: String,
+    #[serde(default)]
+    and: Vec<String>,
+    #[serde(default)]
+    difference: Vec<String>,
+    #[serde(default)]
+    not: bool,
+}
+
+impl TryFrom<RegexPartConfigJson> for RegexPartConfig {
+    type Error = String;
+
+    /// Rejects a part with operators, which the regexes of the extraction cannot express.
+    fn try_from(part: RegexPartConfigJson) -> Result<Self, Self::Error> {
+        if !part.and.is_empty() || !part.difference.is_empty() || part.not {
+            return Err(format!(
+                "The part {} uses the and, difference or not operators, which the substring extraction does not support",
+                part.regex_def
+            ));
+        }
+        Ok(Self {
+            is_public: part.is_public,
+            regex_def: part.regex_def,
+        })
+    }
+}
+
+/// Offsets of an extracted substring in the index spaces of the input string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubstrOffsets {
+    /// UTF-8 byte offsets, i.e., the positions in the circuit input.
+    pub byte_start: usize,
+    pub byte_end: usize,
+    /// UTF-16 code unit offsets, i.e., the positions in a JavaScript string.
+    pub utf16_start: usize,
+    pub utf16_end: usize,
+    /// Unicode scalar value offsets, i.e., the positions in `str::chars()`.
+    pub char_start: usize,
+    pub char_end: usize,
+}
+
+/// Diagnostics of an input that does not match the entire regex of a [`DecomposedRegexConfig`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchFailure {
+    /// The number of leading parts that matched, i.e., `parts[..matched_parts]` matched but no match of them
+    /// is followed by `parts[matched_parts]`.
+    pub matched_parts: usize,
+    /// The byte offset where matching stopped, i.e., the end of the leftmost match of the matched parts.
+    pub stopped_at: usize,
+    /// The index of the part that was expected at `stopped_at`.
+    pub expected_part: usize,
+    /// The regex of the expected part.
+    pub expected_regex_def: String,
+    /// The byte offset of `snippet` in the input.
+    pub snippet_start: usize,
+    /// The input around `stopped_at`. Invalid UTF-8 bytes are replaced with U+FFFD.
+    pub snippet: String,
+}
+
+impl std::fmt::Display for MatchFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.matched_parts == 0 {
+            write!(f, "no part matched")?;
+        } else {
+            write!(
+                f,
+                "parts 0..{} matched up to byte {}",
+                self.matched_parts, self.stopped_at
+            )?;
+        }
+        write!(
+            f,
+            ", part {} '{}' was expected next near \"{}\"",
+            self.expected_part,
+            self.expected_regex_def.escape_debug(),
+            self.snippet.escape_debug()
+        )
+    }
+}
+
+/// The number of bytes shown before and after the stop offset in [`MatchFailure::snippet`].
+const SNIPPET_RADIUS: usize = 20;
+
 /// Error definitions of the compiler.
 #[derive(Error, Debug)]
 pub enum ExtractSubstrssError {
     // #[error("The max length is {} but the input length is {}",.0,.1)]
     // InvalidInputLen(usize, usize),
-    #[error("Substring of the entire regex {} is not found given input_str",.0)]
-    SubstringOfEntireNotFound(Regex),
+    #[error("Substring of the entire regex {} is not found given input_str; {}",.0,.1)]
+    SubstringOfEntireNotFound(Box<Regex>, Box<MatchFailure>),
     #[error("Substring of {} is not found in given input_str",.0)]
-    SubstringNotFound(Regex, String),
+    SubstringNotFound(Box<Regex>, String),
     #[error(transparent)]
-    RegexError(#[from] fancy_regex::Error),
+    RegexError(Box<fancy_regex::Error>),
+    #[error("Substring of the entire regex {} is not found given input bytes; {}",.0,.1)]
+    SubstringOfEntireNotFoundInBytes(Box<BytesRegex>, Box<MatchFailure>),
+    #[error("The entire regex cannot be used on byte inputs: {0}")]
+    BytesRegexError(#[from] regex::Error),
+    #[error("Pattern {0} is not found in the registry")]
+    PatternNotFound(String),
+    #[error("Byte index {0} is out of bounds or not on a character boundary of input_str")]
+    InvalidByteIndex(usize),
+    #[error("The substrings of the parts {0} and {1} are not equal")]
+    PartsNotEqual(String, String),
+    #[error("The constraint refers to the unknown part {0}")]
+    UnknownPart(String),
+    #[error("The constrained part {0} must be public with a max_len")]
+    UnrevealedPart(String),
+    #[error("The substring of the constrained part {0} has {1} bytes, more than its max_len {2}")]
+    PartTooLong(String, usize, usize),
     #[error("Invalid regex in parts, index {part_index}: '{regex_def}' - {error}")]
     InvalidRegexPart {
         part_index: usize,
         regex_def: String,
-        error: fancy_regex::Error,
+        error: Box<fancy_regex::Error>,
+    },
+    #[error("Invalid DFA of the regex part, index {part_index}: '{regex_def}' - {error}")]
+    InvalidDfaPart {
+        part_index: usize,
+        regex_def: String,
+        error: Box<regex_automata::dfa::dense::BuildError>,
     },
 }
 
-pub fn extract_substr_idxes(
-    input_str: &str,
-    regex_config: &DecomposedRegexConfig,
-    reveal_private: bool,
-) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
-    // Validate each regex part individually, to throw better errors
+impl From<fancy_regex::Error> for ExtractSubstrssError {
+    fn from(error: fancy_regex::Error) -> Self {
+        Self::RegexError(Box::new(error))
+    }
+}
+
+/// Validates each regex part individually, to throw better errors.
+fn validate_regex_parts(regex_config: &DecomposedRegexConfig) -> Result<(), ExtractSubstrssError> {
     for (i, part) in regex_config.parts.iter().enumerate() {
         Regex::new(&part.regex_def).map_err(|e| ExtractSubstrssError::InvalidRegexPart {
             part_index: i,
             regex_def: part.regex_def.clone(),
-            error: e,
+            error: Box::new(e),
         })?;
     }
+    Ok(())
+}
 
-    // Construct the full regex pattern with groups for each part
+/// Constructs the full regex pattern with one capture group for each part.
+fn build_entire_regex_str(parts: &[RegexPartConfig]) -> String {
     let mut entire_regex_str = String::new();
-    for (_, part) in regex_config.parts.iter().enumerate() {
+    for part in parts.iter() {
         let adjusted_regex_def = part.regex_def.replace("(", "(?:");
         entire_regex_str += &format!("({})", adjusted_regex_def);
     }
+    entire_regex_str
+}
+
+/// The byte ranges of the parts of a match, or `None` for a part that did not participate in it.
+pub(crate) type PartSpans = Vec<Option<(usize, usize)>>;
+
+/// Returns the byte ranges of the parts of the match selected by a leftmost match policy, split on the joined DFA
+/// of the parts as in the circuit, or `None` if the DFA does not match although the entire regex does.
+fn leftmost_part_spans(
+    input: &[u8],
+    regex_config: &DecomposedRegexConfig,
+    policy: MatchPolicy,
+) -> Result<Option<PartSpans>, ExtractSubstrssError> {
+    Ok(LeftmostDfa::new(&regex_config.parts)?.part_spans(input, policy, regex_config.parts.len()))
+}
+
+/// Returns the ranges of the public parts, or of every part if `reveal_private` is set.
+fn collect_public_idxes(
+    parts: &[RegexPartConfig],
+    spans: &[Option<(usize, usize)>],
+    reveal_private: bool,
+) -> Vec<(usize, usize)> {
+    parts
+        .iter()
+        .zip(spans)
+        .filter(|(part, _)| part.is_public || reveal_private)
+        .filter_map(|(_, span)| *span)
+        .collect()
+}
+
+/// The name, the input, the config, the options and the part spans of a regex whose constraints are checked.
+type ConstrainedMatch<'a> = (
+    Option<&'a str>,
+    &'a [u8],
+    &'a DecomposedRegexConfig,
+    &'a DecomposedRegexOptions,
+    &'a PartSpans,
+);
+
+/// Returns the match and the index of a part referenced from the constraints of the match `regex_idx`.
+fn resolve_part<'a>(
+    matches: &[ConstrainedMatch<'a>],
+    regex_idx: usize,
+    part: &PartRef,
+) -> Result<(ConstrainedMatch<'a>, usize), ExtractSubstrssError> {
+    let (regex_idx, part_idx) = match part {
+        PartRef::Index(part_idx) => (Some(regex_idx), *part_idx),
+        PartRef::Named { regex, part } => (
+            matches
+                .iter()
+                .position(|(name, ..)| *name == Some(regex.as_str())),
+            *part,
+        ),
+    };
+    let constrained_match = regex_idx
+        .map(|regex_idx| matches[regex_idx])
+        .filter(|(_, _, config, ..)| part_idx < config.parts.len())
+        .ok_or_else(|| ExtractSubstrssError::UnknownPart(part.to_string()))?;
+    Ok((constrained_match, part_idx))
+}
+
+/// Returns the substring of a part referenced from the constraints of the match `regex_idx`, which is empty if the
+/// part did not participate in its match.
+fn part_substr<'a>(
+    matches: &[ConstrainedMatch<'a>],
+    regex_idx: usize,
+    part: &PartRef,
+) -> Result<&'a [u8], ExtractSubstrssError> {
+    let ((_, input, _, _, spans), part_idx) = resolve_part(matches, regex_idx, part)?;
+    Ok(spans[part_idx].map_or(&[][..], |(start, end)| &input[start..end]))
+}
+
+/// Checks that a part referenced from the constraints of the match `regex_idx` is revealed as the circuit compares
+/// it, i.e., that it is public with a `max_len` its substring does not exceed.
+fn check_revealed_part(
+    matches: &[ConstrainedMatch],
+    regex_idx: usize,
+    part: &PartRef,
+) -> Result<(), ExtractSubstrssError> {
+    let ((_, _, config, options, spans), part_idx) = resolve_part(matches, regex_idx, part)?;
+    let max_len = options
+        .max_len(part_idx)
+        .filter(|_| config.parts[part_idx].is_public)
+        .ok_or_else(|| ExtractSubstrssError::UnrevealedPart(part.to_string()))?;
+    let len = spans[part_idx].map_or(0, |(start, end)| end - start);
+    if len > max_len {
+        return Err(ExtractSubstrssError::PartTooLong(
+            part.to_string(),
+            len,
+            max_len,
+        ));
+    }
+    Ok(())
+}
+
+/// Checks the constraints of the given matches. The constraints of an unnamed match, i.e., of a single regex, with
+/// a [`PartRef::Named`] reference are skipped, since they are checked by [`check_part_constraints`].
+fn check_constraints(matches: &[ConstrainedMatch]) -> Result<(), ExtractSubstrssError> {
+    let named = |part: &PartRef| matches!(part, PartRef::Named { .. });
+    for (regex_idx, (name, _, _, options, _)) in matches.iter().enumerate() {
+        for PartConstraint::Equal([a, b]) in &options.constraints {
+            if name.is_none() && (named(a) || named(b)) {
+                continue;
+            }
+            if part_substr(matches, regex_idx, a)? != part_substr(matches, regex_idx, b)? {
+                return Err(ExtractSubstrssError::PartsNotEqual(
+                    a.to_string(),
+                    b.to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the constraints of several named regexes matched on the same input, including the constraints between
+/// parts of different regexes, as a combined template does.
+///
+/// As in the circuit, the constrained parts must be public with a `max_len`, and the substrings compared are those
+/// of the first run each regex reveals, i.e., of its leftmost-longest match with the default match policy.
+///
+/// # Arguments
+///
+/// * `input` - The input bytes.
+/// * `regexes` - The names, configs and options of the regexes.
+///
+/// # Returns
+///
+/// A Result indicating whether the constraints hold, or an ExtractSubstrssError if a regex does not match, a
+/// constrained part is not revealed or is longer than its `max_len`, or a constraint does not hold.
+pub fn check_part_constraints(
+    input: &[u8],
+    regexes: &[(String, DecomposedRegexConfig, DecomposedRegexOptions)],
+) -> Result<(), ExtractSubstrssError> {
+    let spans = regexes
+        .iter()
+        .map(|(_, config, options)| first_revealed_part_spans(input, config, options))
+        .collect::<Result<Vec<_>, ExtractSubstrssError>>()?;
+    let matches = regexes
+        .iter()
+        .zip(&spans)
+        .map(|((name, config, options), spans)| {
+            (Some(name.as_str()), input, config, options, spans)
+        })
+        .collect::<Vec<_>>();
+    for (regex_idx, (_, _, _, options, _)) in matches.iter().enumerate() {
+        for PartConstraint::Equal(parts) in &options.constraints {
+            for part in parts {
+                check_revealed_part(&matches, regex_idx, part)?;
+            }
+        }
+    }
+    check_constraints(&matches)
+}
+
+/// Returns the byte ranges of the parts in the first run the circuit reveals, which ends at the last accept state of
+/// the run with the default match policy.
+fn first_revealed_part_spans(
+    input: &[u8],
+    regex_config: &DecomposedRegexConfig,
+    options: &DecomposedRegexOptions,
+) -> Result<PartSpans, ExtractSubstrssError> {
+    let spans = bytes_part_spans(input, regex_config, options)?;
+    if !options.match_policy.is_greedy() {
+        return Ok(spans);
+    }
+    Ok(leftmost_part_spans(input, regex_config, MatchPolicy::LeftmostLongest)?.unwrap_or(spans))
+}
+
+/// Finds the longest prefix of `parts` matching the input, given a function returning the end of the leftmost match
+/// of a regex string, and builds the [`MatchFailure`] of the next part.
+///
+/// Since a match of a prefix contains a match of any shorter prefix, the prefixes are tried from the longest.
+fn find_match_failure<E>(
+    input: &[u8],
+    parts: &[RegexPartConfig],
+    find_end: impl Fn(&str) -> Result<Option<usize>, E>,
+) -> Result<MatchFailure, E> {
+    let mut matched_parts = 0;
+    let mut stopped_at = 0;
+    for num_parts in (1..parts.len()).rev() {
+        if let Some(end) = find_end(&build_entire_regex_str(&parts[..num_parts]))? {
+            matched_parts = num_parts;
+            stopped_at = end;
+            break;
+        }
+    }
+
+    let snippet_start = stopped_at.saturating_sub(SNIPPET_RADIUS);
+    let snippet_end = input.len().min(stopped_at + SNIPPET_RADIUS);
+    Ok(MatchFailure {
+        matched_parts,
+        stopped_at,
+        expected_part: matched_parts,
+        expected_regex_def: parts[matched_parts].regex_def.clone(),
+        snippet_start,
+        snippet: String::from_utf8_lossy(&input[snippet_start..snippet_end]).to_string(),
+    })
+}
+
+/// Explains why `input_str` does not match the entire regex of `regex_config`.
+///
+/// Returns `None` if it matches.
+pub fn diagnose_match(
+    input_str: &str,
+    regex_config: &DecomposedRegexConfig,
+) -> Result<Option<MatchFailure>, ExtractSubstrssError> {
+    validate_regex_parts(regex_config)?;
+
+    let entire_regex = Regex::new(&build_entire_regex_str(&regex_config.parts)).unwrap();
+    if entire_regex.is_match(input_str)? {
+        return Ok(None);
+    }
+
+    Ok(Some(str_match_failure(input_str, regex_config)?))
+}
+
+fn str_match_failure(
+    input_str: &str,
+    regex_config: &DecomposedRegexConfig,
+) -> Result<MatchFailure, ExtractSubstrssError> {
+    find_match_failure(input_str.as_bytes(), &regex_config.parts, |regex_str| {
+        let regex = Regex::new(regex_str).unwrap();
+        Ok(regex.find(input_str)?.map(|found| found.end()))
+    })
+}
+
+/// Builds a regex on raw bytes with Unicode disabled, so that a negated class such as `[^\r\n]` matches any byte
+/// outside it, including the bytes of a Latin-1 encoded header that are not valid UTF-8.
+fn new_bytes_regex(regex_str: &str) -> Result<BytesRegex, regex::Error> {
+    BytesRegexBuilder::new(regex_str).unicode(false).build()
+}
+
+/// Same as [`diagnose_match`], but for raw bytes.
+pub fn diagnose_match_in_bytes(
+    input: &[u8],
+    regex_config: &DecomposedRegexConfig,
+) -> Result<Option<MatchFailure>, ExtractSubstrssError> {
+    validate_regex_parts(regex_config)?;
+
+    let entire_regex = new_bytes_regex(&build_entire_regex_str(&regex_config.parts))?;
+    if entire_regex.is_match(input) {
+        return Ok(None);
+    }
+
+    Ok(Some(bytes_match_failure(input, regex_config)?))
+}
+
+fn bytes_match_failure(
+    input: &[u8],
+    regex_config: &DecomposedRegexConfig,
+) -> Result<MatchFailure, ExtractSubstrssError> {
+    find_match_failure(input, &regex_config.parts, |regex_str| {
+        let regex = new_bytes_regex(regex_str)?;
+        Ok(regex.find(input).map(|found| found.end()))
+    })
+}
+
+pub fn extract_substr_idxes(
+    input_str: &str,
+    regex_config: &DecomposedRegexConfig,
+    reveal_private: bool,
+) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
+    extract_substr_idxes_with_options(
+        input_str,
+        regex_config,
+        &DecomposedRegexOptions::default(),
+        reveal_private,
+    )
+}
+
+/// Same as [`extract_substr_idxes`], but extracts the match selected by the match policy of `options` and checks its
+/// constraints.
+pub fn extract_substr_idxes_with_options(
+    input_str: &str,
+    regex_config: &DecomposedRegexConfig,
+    options: &DecomposedRegexOptions,
+    reveal_private: bool,
+) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
+    validate_regex_parts(regex_config)?;
+
+    let entire_regex_str = build_entire_regex_str(&regex_config.parts);
 
     // Compile the entire regex
     // This should be impossible to fail, since we tested the seperate regex parts before.
     let entire_regex = Regex::new(&entire_regex_str).unwrap();
 
     // Find the match for the entire regex
-    let entire_captures = entire_regex
-        .captures(input_str)
-        .map_err(|_| ExtractSubstrssError::SubstringOfEntireNotFound(entire_regex.clone()))?
-        .ok_or_else(|| ExtractSubstrssError::SubstringOfEntireNotFound(entire_regex.clone()))?;
+    let entire_captures = match entire_regex.captures(input_str)? {
+        Some(captures) => captures,
+        None => {
+            let failure = str_match_failure(input_str, regex_config)?;
+            return Err(ExtractSubstrssError::SubstringOfEntireNotFound(
+                Box::new(entire_regex),
+                Box::new(failure),
+            ));
+        }
+    };
 
-    let mut public_idxes = vec![];
+    // Capture group indices are 1-based
+    let mut spans = (1..=regex_config.parts.len())
+        .map(|i| entire_captures.get(i).map(|m| (m.start(), m.end())))
+        .collect::<Vec<_>>();
 
-    // Iterate over each part to extract the relevant indices
-    for (i, part) in regex_config.parts.iter().enumerate() {
-        if part.is_public || reveal_private {
-            if let Some(matched) = entire_captures.get(i + 1) {
-                // Capture group indices are 1-based
-                public_idxes.push((matched.start(), matched.end()));
-            }
+    if !options.match_policy.is_greedy() {
+        if let Some(leftmost_spans) =
+            leftmost_part_spans(input_str.as_bytes(), regex_config, options.match_policy)?
+        {
+            spans = leftmost_spans;
         }
     }
 
-    Ok(public_idxes)
+    check_constraints(&[(None, input_str.as_bytes(), regex_config, options, &spans)])?;
+    Ok(collect_public_idxes(
+        &regex_config.parts,
+        &spans,
+        reveal_private,
+    ))
 }
 
 pub fn extract_substr(
@@ -99,117 +649,177 @@ pub fn extract_substr(
     Ok(result)
 }
 
-pub fn extract_email_addr_idxes(
+/// Converts UTF-8 byte indexes of `input_str` into [`SubstrOffsets`].
+///
+/// Returns an error if an index does not fall on a character boundary.
+pub fn byte_idxes_to_offsets(
     input_str: &str,
-) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
-    let regex_config = include_str!("./decomposed_defs/email_addr.json");
-    extract_substr_idxes(
-        input_str,
-        &serde_json::from_str(regex_config).unwrap(),
-        false,
-    )
+    idxes: &[(usize, usize)],
+) -> Result<Vec<SubstrOffsets>, ExtractSubstrssError> {
+    let to_other_idxes = |byte_idx: usize| {
+        if !input_str.is_char_boundary(byte_idx) {
+            return Err(ExtractSubstrssError::InvalidByteIndex(byte_idx));
+        }
+        let prefix = &input_str[..byte_idx];
+        Ok((prefix.encode_utf16().count(), prefix.chars().count()))
+    };
+
+    idxes
+        .iter()
+        .map(|&(start, end)| {
+            let (utf16_start, char_start) = to_other_idxes(start)?;
+            let (utf16_end, char_end) = to_other_idxes(end)?;
+            Ok(SubstrOffsets {
+                byte_start: start,
+                byte_end: end,
+                utf16_start,
+                utf16_end,
+                char_start,
+                char_end,
+            })
+        })
+        .collect()
 }
 
-pub fn extract_email_domain_idxes(
+/// Same as [`extract_substr_idxes`], but returns the byte, UTF-16 and char offsets of each substring.
+pub fn extract_substr_offsets(
     input_str: &str,
-) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
-    let regex_config = include_str!("./decomposed_defs/email_domain.json");
-    extract_substr_idxes(
-        input_str,
-        &serde_json::from_str(regex_config).unwrap(),
-        false,
-    )
+    regex_config: &DecomposedRegexConfig,
+    reveal_private: bool,
+) -> Result<Vec<SubstrOffsets>, ExtractSubstrssError> {
+    let substr_idxes = extract_substr_idxes(input_str, regex_config, reveal_private)?;
+    byte_idxes_to_offsets(input_str, &substr_idxes)
 }
 
-// pub fn extract_email_addr_with_name_idxes(
-//     input_str: &str,
-// ) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
-//     let regex_config = include_str!("./decomposed_defs/email_addr_with_name.json");
-//     extract_substr_idxes(input_str, &serde_json::from_str(regex_config).unwrap())
-// }
-
-pub fn extract_from_all_idxes(
-    input_str: &str,
+/// Extracts the byte indexes of the public parts from raw bytes.
+///
+/// Unlike [`extract_substr_idxes`], the input does not have to be valid UTF-8, e.g. a header with a Latin-1 encoded subject,
+/// and the returned indexes are byte offsets into `input`, i.e., the positions revealed by the regex circuit.
+/// Negated character classes such as `[^\r\n]` match any byte outside the class, including bytes that are not valid
+/// UTF-8, whereas the compiled DFA only accepts UTF-8 sequences in them.
+/// Patterns are evaluated with the `regex` crate, so look-arounds and backreferences are not supported here.
+pub fn extract_substr_idxes_from_bytes(
+    input: &[u8],
+    regex_config: &DecomposedRegexConfig,
+    reveal_private: bool,
 ) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
-    let regex_config = include_str!("./decomposed_defs/from_all.json");
-    extract_substr_idxes(
-        input_str,
-        &serde_json::from_str(regex_config).unwrap(),
-        false,
+    extract_substr_idxes_from_bytes_with_options(
+        input,
+        regex_config,
+        &DecomposedRegexOptions::default(),
+        reveal_private,
     )
 }
 
-pub fn extract_from_addr_idxes(
-    input_str: &str,
+/// Same as [`extract_substr_idxes_from_bytes`], but extracts the match selected by the match policy of `options`
+/// and checks its constraints.
+pub fn extract_substr_idxes_from_bytes_with_options(
+    input: &[u8],
+    regex_config: &DecomposedRegexConfig,
+    options: &DecomposedRegexOptions,
+    reveal_private: bool,
 ) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
-    let regex_config = include_str!("./decomposed_defs/from_addr.json");
-    extract_substr_idxes(
-        input_str,
-        &serde_json::from_str(regex_config).unwrap(),
-        false,
-    )
+    let spans = bytes_part_spans(input, regex_config, options)?;
+    check_constraints(&[(None, input, regex_config, options, &spans)])?;
+    Ok(collect_public_idxes(
+        &regex_config.parts,
+        &spans,
+        reveal_private,
+    ))
 }
 
-pub fn extract_to_all_idxes(input_str: &str) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
-    let regex_config = include_str!("./decomposed_defs/to_all.json");
-    extract_substr_idxes(
-        input_str,
-        &serde_json::from_str(regex_config).unwrap(),
-        false,
-    )
-}
+/// Returns the byte ranges of the parts of the match of the entire regex in raw bytes.
+fn bytes_part_spans(
+    input: &[u8],
+    regex_config: &DecomposedRegexConfig,
+    options: &DecomposedRegexOptions,
+) -> Result<PartSpans, ExtractSubstrssError> {
+    validate_regex_parts(regex_config)?;
 
-pub fn extract_to_addr_idxes(input_str: &str) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
-    let regex_config = include_str!("./decomposed_defs/to_addr.json");
-    extract_substr_idxes(
-        input_str,
-        &serde_json::from_str(regex_config).unwrap(),
-        false,
-    )
-}
+    let entire_regex = new_bytes_regex(&build_entire_regex_str(&regex_config.parts))?;
 
-pub fn extract_subject_all_idxes(
-    input_str: &str,
-) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
-    let regex_config = include_str!("./decomposed_defs/subject_all.json");
-    extract_substr_idxes(
-        input_str,
-        &serde_json::from_str(regex_config).unwrap(),
-        false,
-    )
+    let entire_captures = match entire_regex.captures(input) {
+        Some(captures) => captures,
+        None => {
+            let failure = bytes_match_failure(input, regex_config)?;
+            return Err(ExtractSubstrssError::SubstringOfEntireNotFoundInBytes(
+                Box::new(entire_regex),
+                Box::new(failure),
+            ));
+        }
+    };
+
+    let mut spans = (1..=regex_config.parts.len())
+        .map(|i| entire_captures.get(i).map(|m| (m.start(), m.end())))
+        .collect::<Vec<_>>();
+
+    if !options.match_policy.is_greedy() {
+        if let Some(leftmost_spans) =
+            leftmost_part_spans(input, regex_config, options.match_policy)?
+        {
+            spans = leftmost_spans;
+        }
+    }
+
+    Ok(spans)
 }
 
-pub fn extract_body_hash_idxes(
-    input_str: &str,
-) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
-    let regex_config = include_str!("./decomposed_defs/body_hash.json");
-    extract_substr_idxes(
-        input_str,
-        &serde_json::from_str(regex_config).unwrap(),
-        false,
-    )
+/// Extracts the bytes of the public parts from raw bytes.
+pub fn extract_substr_from_bytes(
+    input: &[u8],
+    regex_config: &DecomposedRegexConfig,
+    reveal_private: bool,
+) -> Result<Vec<Vec<u8>>, ExtractSubstrssError> {
+    let substr_idxes = extract_substr_idxes_from_bytes(input, regex_config, reveal_private)?;
+
+    let result: Vec<Vec<u8>> = substr_idxes
+        .iter()
+        .map(|&(start, end)| input[start..end].to_vec())
+        .collect();
+
+    Ok(result)
 }
 
-pub fn extract_timestamp_idxes(
-    input_str: &str,
-) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
-    let regex_config = include_str!("./decomposed_defs/timestamp.json");
-    extract_substr_idxes(
-        input_str,
-        &serde_json::from_str(regex_config).unwrap(),
-        false,
-    )
+/// Defines the extractors of the built-in patterns from a `&str` and from raw bytes, which look the pattern up in
+/// [`crate::registry`].
+macro_rules! builtin_extractors {
+    ($($name:literal => $extract:ident, $extract_from_bytes:ident;)*) => {
+        $(
+            #[doc = concat!("Extracts the indexes of the public parts of the built-in `", $name, "` pattern.")]
+            pub fn $extract(
+                input_str: &str,
+            ) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
+                crate::registry::extract_by_name($name, input_str)
+            }
+
+            #[doc = concat!("Same as [`", stringify!($extract), "`], but for raw bytes.")]
+            pub fn $extract_from_bytes(
+                input: &[u8],
+            ) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
+                crate::registry::extract_by_name_from_bytes($name, input)
+            }
+        )*
+    };
 }
 
-pub fn extract_message_id_idxes(
-    input_str: &str,
-) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
-    let regex_config = include_str!("./decomposed_defs/message_id.json");
-    extract_substr_idxes(
-        input_str,
-        &serde_json::from_str(regex_config).unwrap(),
-        false,
-    )
+// pub fn extract_email_addr_with_name_idxes(
+//     input_str: &str,
+// ) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
+//     let regex_config = include_str!("./decomposed_defs/email_addr_with_name.json");
+//     extract_substr_idxes(input_str, &serde_json::from_str(regex_config).unwrap())
+// }
+
+builtin_extractors! {
+    "email_addr" => extract_email_addr_idxes, extract_email_addr_idxes_from_bytes;
+    "email_domain" => extract_email_domain_idxes, extract_email_domain_idxes_from_bytes;
+    "from_all" => extract_from_all_idxes, extract_from_all_idxes_from_bytes;
+    "from_addr" => extract_from_addr_idxes, extract_from_addr_idxes_from_bytes;
+    "to_all" => extract_to_all_idxes, extract_to_all_idxes_from_bytes;
+    "to_addr" => extract_to_addr_idxes, extract_to_addr_idxes_from_bytes;
+    "subject_all" => extract_subject_all_idxes, extract_subject_all_idxes_from_bytes;
+    "body_hash" => extract_body_hash_idxes, extract_body_hash_idxes_from_bytes;
+    "timestamp" => extract_timestamp_idxes, extract_timestamp_idxes_from_bytes;
+    "message_id" => extract_message_id_idxes, extract_message_id_idxes_from_bytes;
 }
 
 #[cfg(test)]
@@ -413,6 +1023,101 @@ mod test {
         assert_eq!(strs, vec!["Hello ".to_string(), "guys!".to_string()]);
     }
 
+    #[test]
+    fn test_subject_offsets_with_emoji_and_cjk() {
+        // "🎉" takes 4 bytes and 2 UTF-16 code units, "日本" takes 6 bytes and 2 UTF-16 code units.
+        let input_str = "subject:🎉 日本 party\r\n";
+        let regex_config =
+            serde_json::from_str(include_str!("./decomposed_defs/subject_all.json")).unwrap();
+        let offsets = extract_substr_offsets(input_str, &regex_config, false).unwrap();
+        assert_eq!(
+            offsets,
+            vec![SubstrOffsets {
+                byte_start: 8,
+                byte_end: 25,
+                utf16_start: 8,
+                utf16_end: 19,
+                char_start: 8,
+                char_end: 18,
+            }]
+        );
+        assert!(byte_idxes_to_offsets(input_str, &[(9, 25)]).is_err());
+    }
+
+    #[test]
+    fn test_timestamp_from_bytes_with_latin1_header() {
+        let mut input =
+            b"subject:caf\xe9\r\ndkim-signature:v=1; a=rsa-sha256; t=1694989812; x=1695594612; bh="
+                .to_vec();
+        input.extend_from_slice(b"BWETwQ9JDReS4GyR2v2TTR8Bpzj9ayumsWQJ3q7vehs=; b=");
+        assert!(std::str::from_utf8(&input).is_err());
+        let idxes = extract_timestamp_idxes_from_bytes(&input).unwrap();
+        assert_eq!(idxes, vec![(50, 60)]);
+        assert_eq!(&input[50..60], b"1694989812");
+    }
+
+    #[test]
+    fn test_subject_all_from_bytes_with_latin1_subject() {
+        let input = b"from:alice@example.com\r\nsubject:caf\xe9 cr\xe8me\r\n";
+        let idxes = extract_subject_all_idxes_from_bytes(input).unwrap();
+        assert_eq!(idxes, vec![(32, 42)]);
+        assert_eq!(&input[32..42], b"caf\xe9 cr\xe8me");
+    }
+
+    #[test]
+    fn test_from_addr_match_failure() {
+        let regex_config: DecomposedRegexConfig =
+            serde_json::from_str(include_str!("./decomposed_defs/from_addr.json")).unwrap();
+        let input_str = "to:bob@b.com\r\nfrom:alice@a.com";
+        let failure = diagnose_match(input_str, &regex_config).unwrap().unwrap();
+        assert_eq!(failure.matched_parts, 3);
+        assert_eq!(failure.stopped_at, input_str.len());
+        assert_eq!(failure.expected_part, 3);
+        assert_eq!(failure.expected_regex_def, ">?\r\n");
+        assert_eq!(failure.snippet_start, input_str.len() - 20);
+        assert_eq!(failure.snippet, "om\r\nfrom:alice@a.com");
+
+        let err = extract_from_addr_idxes(input_str).unwrap_err();
+        assert!(err.to_string().ends_with(
+            "parts 0..3 matched up to byte 30, part 3 '>?\\r\\n' was expected next near \"om\\r\\nfrom:alice@a.com\""
+        ));
+        assert!(matches!(
+            err,
+            ExtractSubstrssError::SubstringOfEntireNotFound(_, failure) if failure.matched_parts == 3
+        ));
+
+        let failure = diagnose_match_in_bytes(b"subject:hi\r\n", &regex_config)
+            .unwrap()
+            .unwrap();
+        assert_eq!(failure.matched_parts, 0);
+        assert_eq!(failure.stopped_at, 0);
+        assert_eq!(failure.expected_part, 0);
+        assert!(diagnose_match("from:alice@a.com\r\n", &regex_config)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn extract_bytes_show_private() {
+        let code_regex = DecomposedRegexConfig {
+            parts: vec![
+                RegexPartConfig {
+                    is_public: true,
+                    regex_def: "Hello ".to_string(),
+                },
+                RegexPartConfig {
+                    is_public: false,
+                    regex_def: "[a-z]+!".to_string(),
+                },
+            ],
+        };
+        let input = b"\xff\xfe Hello guys! Best, ZK Email";
+        let idxes = extract_substr_idxes_from_bytes(input, &code_regex, true).unwrap();
+        assert_eq!(idxes, vec![(3, 9), (9, 14)]);
+        let substrs = extract_substr_from_bytes(input, &code_regex, false).unwrap();
+        assert_eq!(substrs, vec![b"Hello ".to_vec()]);
+    }
+
     #[test]
     fn extract_str_empty_vec_all_private() {
         let code_regex = DecomposedRegexConfig {
@@ -432,4 +1137,129 @@ mod test {
         let empty_vec: Vec<String> = Vec::new();
         assert_eq!(strs, empty_vec);
     }
+
+    #[test]
+    fn reject_part_operators() {
+        let config = serde_json::from_str::<DecomposedRegexConfig>(
+            r#"{"parts": [{"is_public": true, "regex_def": "[a-z]+", "difference": ["unsubscribe"]}]}"#,
+        );
+        assert!(config
+            .unwrap_err()
+            .to_string()
+            .starts_with("The part [a-z]+ uses the and, difference or not operators"));
+        let config = serde_json::from_str::<DecomposedRegexConfig>(
+            r#"{"parts": [{"isPublic": true, "regexDef": "[a-z]+", "and": []}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.parts[0].regex_def, "[a-z]+");
+    }
+
+    #[test]
+    fn extract_with_equal_parts() {
+        let (regex_config, options) = parse_decomposed_regex_str(
+            r#"{
+                "parts": [
+                    {"is_public": false, "regex_def": "from:[a-z]+@"},
+                    {"is_public": true, "regex_def": "[a-z]+"},
+                    {"is_public": false, "regex_def": "\\.com;id:[a-z]+@"},
+                    {"is_public": true, "regex_def": "[a-z]+"},
+                    {"is_public": false, "regex_def": "\\.com"}
+                ],
+                "constraints": [{"equal": [1, 3]}]
+            }"#,
+        );
+        assert_eq!(
+            options.constraints,
+            vec![PartConstraint::Equal([
+                PartRef::Index(1),
+                PartRef::Index(3)
+            ])]
+        );
+        let input = "from:alice@gmail.com;id:xyz@gmail.com";
+        assert_eq!(
+            extract_substr_idxes_with_options(input, &regex_config, &options, false).unwrap(),
+            vec![(11, 16), (28, 33)]
+        );
+        let input = "from:alice@gmail.com;id:xyz@evil.com";
+        assert!(matches!(
+            extract_substr_idxes_with_options(input, &regex_config, &options, false),
+            Err(ExtractSubstrssError::PartsNotEqual(..))
+        ));
+        assert!(matches!(
+            extract_substr_idxes_from_bytes_with_options(
+                input.as_bytes(),
+                &regex_config,
+                &options,
+                false
+            ),
+            Err(ExtractSubstrssError::PartsNotEqual(..))
+        ));
+    }
+
+    #[test]
+    fn check_equal_parts_across_regexes() {
+        let (from, from_options) = parse_decomposed_regex_str(
+            r#"{
+                "parts": [
+                    {"is_public": false, "regex_def": "from:[a-z]+@"},
+                    {"is_public": true, "regex_def": "[a-z]+", "max_len": 8},
+                    {"is_public": false, "regex_def": "\\.com"}
+                ],
+                "constraints": [{"equal": [1, {"regex": "message_id", "part": 1}]}]
+            }"#,
+        );
+        let (message_id, message_id_options) = parse_decomposed_regex_str(
+            r#"{
+                "parts": [
+                    {"is_public": false, "regex_def": "id:<[a-z0-9]+@"},
+                    {"is_public": true, "regex_def": "[a-z]+", "max_len": 8},
+                    {"is_public": false, "regex_def": "\\.com>"}
+                ]
+            }"#,
+        );
+        let regexes = [
+            ("from".to_string(), from.clone(), from_options.clone()),
+            ("message_id".to_string(), message_id, message_id_options),
+        ];
+        // The extraction of a single regex skips the references to other regexes.
+        let input = b"from:alice@gmail.com\r\nid:<abc1@evil.com>";
+        assert!(
+            extract_substr_idxes_from_bytes_with_options(input, &from, &from_options, false)
+                .is_ok()
+        );
+        assert!(matches!(
+            check_part_constraints(input, &regexes),
+            Err(ExtractSubstrssError::PartsNotEqual(..))
+        ));
+        check_part_constraints(b"from:alice@gmail.com\r\nid:<abc1@gmail.com>", &regexes).unwrap();
+        assert!(matches!(
+            check_part_constraints(input, &regexes[..1]),
+            Err(ExtractSubstrssError::UnknownPart(..))
+        ));
+        // The circuit compares the first revealed run, which must fit in the max_len.
+        check_part_constraints(
+            b"from:alice@gmail.com\r\nid:<abc1@gmail.com>\r\nid:<abc2@evil.com>",
+            &regexes,
+        )
+        .unwrap();
+        assert!(matches!(
+            check_part_constraints(
+                b"from:alice@gmailgmail.com\r\nid:<abc1@gmailgmail.com>",
+                &regexes
+            ),
+            Err(ExtractSubstrssError::PartTooLong(_, 10, 8))
+        ));
+
+        // The constrained parts must be revealed, i.e., public with a max_len.
+        let mut unrevealed = regexes.clone();
+        unrevealed[1].2.max_lens[1] = None;
+        assert!(matches!(
+            check_part_constraints(b"from:alice@gmail.com\r\nid:<abc1@gmail.com>", &unrevealed),
+            Err(ExtractSubstrssError::UnrevealedPart(..))
+        ));
+    }
+
+    fn parse_decomposed_regex_str(json: &str) -> (DecomposedRegexConfig, DecomposedRegexOptions) {
+        parse_decomposed_regex(&serde_json::from_str(json).unwrap()).unwrap()
+    }
 }
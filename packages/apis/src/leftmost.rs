@@ -0,0 +1,262 @@
+//! The part split of the leftmost match policies, computed on the DFA of the regex circuit.
+//!
+//! The compiler builds an anchored DFA for each part and joins them: an accept state of a part also takes the
+//! transitions of the start state of the next part, which win over its own transitions on the same byte, and it
+//! remains an accept state only if the next part matches the empty string. The circuit runs the joined DFA from
+//! state `0` over the input prefixed with the byte `255`, and restarts from state `0` whenever no transition fires.
+//! [`LeftmostDfa`] follows the same run, so that the parts are split exactly where the circuit reveals them.
+
+use crate::extract_substrs::{ExtractSubstrssError, MatchPolicy, PartSpans, RegexPartConfig};
+use regex_automata::{
+    dfa::{
+        dense::{Config, DFA},
+        Automaton, StartKind,
+    },
+    util::primitives::StateID,
+    Anchored, Input,
+};
+
+/// Finds the index of the first caret (^) in a regex string that is not inside parentheses.
+///
+/// # Arguments
+///
+/// * `regex` - A string slice containing the regex pattern.
+///
+/// # Returns
+///
+/// An `Option<usize>` containing the index of the caret if found, or `None` if not found.
+pub fn find_caret_index(regex: &str) -> Option<usize> {
+    let regex_bytes = regex.as_bytes();
+    let mut is_in_parenthesis = false;
+    let mut caret_found = false;
+    let mut idx = 0;
+
+    while idx < regex_bytes.len() {
+        match regex_bytes[idx] {
+            b'\\' => {
+                idx += 2;
+            }
+            b'(' => {
+                is_in_parenthesis = true;
+                idx += 1;
+            }
+            b'[' => {
+                idx += 2;
+            }
+            b')' => {
+                debug_assert!(is_in_parenthesis, "Unmatched parenthesis");
+                is_in_parenthesis = false;
+                idx += 1;
+                if caret_found {
+                    break;
+                }
+            }
+            b'^' => {
+                caret_found = true;
+                idx += 1;
+                if !is_in_parenthesis {
+                    break;
+                }
+            }
+            _ => {
+                idx += 1;
+            }
+        }
+    }
+
+    if caret_found {
+        Some(idx)
+    } else {
+        None
+    }
+}
+
+/// A state of a part of the joined DFA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PartState {
+    /// A state of the DFA of the part.
+    Dfa(StateID),
+    /// The accept state the caret prefix reaches on the byte `255`, which has no transitions of its own.
+    Caret,
+}
+
+/// The anchored DFA of a part of the joined DFA.
+struct PartDfa {
+    dfa: DFA<Vec<u32>>,
+    start: StateID,
+    /// The caret prefix split from the first part: its start state does not accept, and moves to
+    /// [`PartState::Caret`] on the byte `255` that the circuit prepends to the input.
+    is_caret: bool,
+    /// The index of the regex part, or `None` for the caret prefix.
+    part_idx: Option<usize>,
+}
+
+impl PartDfa {
+    fn new(regex_def: &str, part_idx: usize, is_caret: bool) -> Result<Self, ExtractSubstrssError> {
+        let config = Config::new().minimize(true).start_kind(StartKind::Anchored);
+        let dfa = DFA::builder()
+            .configure(config)
+            .build(&format!(r"^({})$", regex_def))
+            .map_err(|error| ExtractSubstrssError::InvalidDfaPart {
+                part_index: part_idx,
+                regex_def: regex_def.to_string(),
+                error: Box::new(error),
+            })?;
+        let start = dfa
+            .start_state_forward(&Input::new("").anchored(Anchored::Yes))
+            .expect("an anchored DFA without look-behind has a start state");
+        Ok(Self {
+            dfa,
+            start,
+            is_caret,
+            part_idx: (!is_caret).then_some(part_idx),
+        })
+    }
+
+    /// Returns whether `state` is an accept state of the part.
+    fn accepts(&self, state: PartState) -> bool {
+        match state {
+            PartState::Caret => true,
+            PartState::Dfa(id) if self.is_caret && id == self.start => false,
+            PartState::Dfa(id) => self.dfa.is_match_state(self.dfa.next_eoi_state(id)),
+        }
+    }
+
+    /// Returns the state the part moves to from `state` on `byte`, or `None` if it has no such transition.
+    fn next(&self, state: PartState, byte: u8) -> Option<PartState> {
+        match state {
+            PartState::Caret => None,
+            PartState::Dfa(id) if self.is_caret && id == self.start && byte == 255 => {
+                Some(PartState::Caret)
+            }
+            PartState::Dfa(id) => {
+                let next = self.dfa.next_state(id, byte);
+                (!self.dfa.is_dead_state(next)).then_some(PartState::Dfa(next))
+            }
+        }
+    }
+}
+
+/// The joined DFA of the parts of a decomposed regex, as compiled into the regex circuit.
+pub(crate) struct LeftmostDfa {
+    parts: Vec<PartDfa>,
+}
+
+impl LeftmostDfa {
+    /// Builds the DFA of each part, splitting the caret prefix from the first part as the compiler does.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - The regex parts.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `LeftmostDfa`, or an `ExtractSubstrssError` if a part cannot be compiled into a
+    /// DFA, e.g. if it contains a look-around.
+    pub(crate) fn new(parts: &[RegexPartConfig]) -> Result<Self, ExtractSubstrssError> {
+        let mut part_dfas = Vec::with_capacity(parts.len() + 1);
+        for (part_idx, part) in parts.iter().enumerate() {
+            match find_caret_index(&part.regex_def).filter(|_| part_idx == 0) {
+                Some(caret_idx) => {
+                    let (caret, rest) = part.regex_def.split_at(caret_idx);
+                    part_dfas.push(PartDfa::new(caret, part_idx, true)?);
+                    part_dfas.push(PartDfa::new(rest, part_idx, false)?);
+                }
+                None => part_dfas.push(PartDfa::new(&part.regex_def, part_idx, false)?),
+            }
+        }
+        Ok(Self { parts: part_dfas })
+    }
+
+    fn start_state(&self, part: usize) -> PartState {
+        PartState::Dfa(self.parts[part].start)
+    }
+
+    /// Returns whether the state of the joined DFA accepts, i.e., the part accepts and every later part matches
+    /// the empty string.
+    fn accepts(&self, (part, state): (usize, PartState)) -> bool {
+        self.parts[part].accepts(state)
+            && (part + 1..self.parts.len())
+                .all(|next| self.parts[next].accepts(self.start_state(next)))
+    }
+
+    /// Returns the state the joined DFA moves to on `byte`. The start transitions of a later part that the state
+    /// takes over win over its own transitions, and those of the last such part win.
+    fn next(&self, (part, state): (usize, PartState), byte: u8) -> Option<(usize, PartState)> {
+        let mut next = self.parts[part]
+            .next(state, byte)
+            .map(|next_state| (part, next_state));
+        if self.parts[part].accepts(state) {
+            for next_part in part + 1..self.parts.len() {
+                let start = self.start_state(next_part);
+                if let Some(next_state) = self.parts[next_part].next(start, byte) {
+                    next = Some((next_part, next_state));
+                }
+                if !self.parts[next_part].accepts(start) {
+                    break;
+                }
+            }
+        }
+        next
+    }
+
+    /// Returns the byte ranges of the parts of the match selected by a leftmost match policy, as revealed by the
+    /// circuit: the first run of the joined DFA reaching an accept state, up to its last accept state for
+    /// [`MatchPolicy::LeftmostLongest`] or its first one for [`MatchPolicy::LeftmostShortest`].
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input bytes, without the byte `255` the circuit prepends.
+    /// * `policy` - The leftmost match policy.
+    /// * `num_parts` - The number of regex parts.
+    ///
+    /// # Returns
+    ///
+    /// The byte range of each part, or `None` if no run reaches an accept state.
+    pub(crate) fn part_spans(
+        &self,
+        input: &[u8],
+        policy: MatchPolicy,
+        num_parts: usize,
+    ) -> Option<PartSpans> {
+        let initial = (0, self.start_state(0));
+        let mut state: Option<(usize, PartState)> = None;
+        let mut run_start = 0;
+        // The part of the joined DFA each byte of the current run moves into.
+        let mut run_parts: Vec<usize> = vec![];
+        let mut match_end = None;
+        for (pos, &byte) in std::iter::once(&255).chain(input).enumerate() {
+            let next = match state.and_then(|state| self.next(state, byte)) {
+                Some(next) => Some(next),
+                None if match_end.is_some() => break,
+                None => {
+                    run_start = pos.saturating_sub(1);
+                    run_parts.clear();
+                    self.next(initial, byte)
+                }
+            };
+            if let (Some((part, _)), true) = (next, pos > 0) {
+                run_parts.push(part);
+            }
+            state = next;
+            if state.is_some_and(|state| self.accepts(state)) {
+                match_end = Some(pos);
+                if policy == MatchPolicy::LeftmostShortest {
+                    break;
+                }
+            }
+        }
+
+        let match_end = match_end?;
+        run_parts.truncate(match_end - run_start);
+        let mut spans = vec![None; num_parts];
+        for (part, part_dfa) in self.parts.iter().enumerate() {
+            if let Some(part_idx) = part_dfa.part_idx {
+                let before = run_parts.iter().filter(|&&p| p < part).count();
+                let through = run_parts.iter().filter(|&&p| p <= part).count();
+                spans[part_idx] = Some((run_start + before, run_start + through));
+            }
+        }
+        Some(spans)
+    }
+}
@@ -1,6 +1,11 @@
+pub mod commitment;
 pub mod extract_substrs;
+pub mod leftmost;
+pub mod numeric;
+pub mod packing;
 pub mod padding;
-#[cfg(target_arch = "wasm32")]
+pub mod registry;
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
 mod wasm;
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
 pub use crate::wasm::*;
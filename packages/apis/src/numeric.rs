@@ -0,0 +1,102 @@
+use thiserror::Error;
+
+/// The maximal number of digits of a numeric value including its decimals, so that it is smaller than the scalar
+/// field modulus of BN254.
+pub const MAX_UINT_DIGITS: usize = 76;
+
+/// Error definitions of the numeric values of substrings.
+#[derive(Error, Debug)]
+pub enum NumericError {
+    #[error("Byte {0} at index {1} is neither a digit nor a permitted decimal point")]
+    InvalidByte(u8, usize),
+    #[error("The number has {0} decimals but at most {1} are permitted")]
+    TooManyDecimals(usize, usize),
+    #[error(
+        "The value has {0} digits but at most {} are permitted",
+        MAX_UINT_DIGITS
+    )]
+    TooManyDigits(usize),
+}
+
+/// Converts a substring of ASCII digits into its numeric value scaled by `10 ** decimals`, as the
+/// `reveal{idx}_value` output of a part with the `uint` output, e.g., `12.5` with 2 decimals into `1250`.
+///
+/// If `decimals` is positive, the substring may contain one decimal point followed by at most `decimals` digits.
+/// As in the circuit, a substring without digits has the value `0`.
+///
+/// # Arguments
+///
+/// * `substr` - The bytes of the substring.
+/// * `decimals` - The number of decimals of the part.
+///
+/// # Returns
+///
+/// A Result containing the decimal string of the value, or a NumericError.
+pub fn parse_uint(substr: &[u8], decimals: usize) -> Result<String, NumericError> {
+    let mut integer = vec![];
+    let mut fraction: Option<Vec<u8>> = None;
+    for (idx, &byte) in substr.iter().enumerate() {
+        match (byte, &mut fraction) {
+            (b'0'..=b'9', None) => integer.push(byte),
+            (b'0'..=b'9', Some(fraction)) => fraction.push(byte),
+            (b'.', None) if decimals > 0 => fraction = Some(vec![]),
+            _ => return Err(NumericError::InvalidByte(byte, idx)),
+        }
+    }
+    let mut fraction = fraction.unwrap_or_default();
+    if fraction.len() > decimals {
+        return Err(NumericError::TooManyDecimals(fraction.len(), decimals));
+    }
+    fraction.resize(decimals, b'0');
+
+    let digits = [integer, fraction].concat();
+    let value = match digits.iter().position(|&digit| digit != b'0') {
+        Some(first) => String::from_utf8(digits[first..].to_vec()).unwrap(),
+        None => "0".to_string(),
+    };
+    if value.len() > MAX_UINT_DIGITS {
+        return Err(NumericError::TooManyDigits(value.len()));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_uint() {
+        assert_eq!(parse_uint(b"1694989812", 0).unwrap(), "1694989812");
+        assert_eq!(parse_uint(b"007", 0).unwrap(), "7");
+        assert_eq!(parse_uint(b"", 0).unwrap(), "0");
+        assert_eq!(parse_uint(b"12.5", 2).unwrap(), "1250");
+        assert_eq!(parse_uint(b"12", 2).unwrap(), "1200");
+        assert_eq!(parse_uint(b".05", 2).unwrap(), "5");
+        assert_eq!(parse_uint(b"12.", 2).unwrap(), "1200");
+        assert_eq!(
+            parse_uint(&[b'9'; MAX_UINT_DIGITS], 0).unwrap(),
+            "9".repeat(MAX_UINT_DIGITS)
+        );
+
+        assert!(matches!(
+            parse_uint(b"12.5", 0),
+            Err(NumericError::InvalidByte(b'.', 2))
+        ));
+        assert!(matches!(
+            parse_uint(b"1.2.3", 2),
+            Err(NumericError::InvalidByte(b'.', 3))
+        ));
+        assert!(matches!(
+            parse_uint(b"1,000", 0),
+            Err(NumericError::InvalidByte(b',', 1))
+        ));
+        assert!(matches!(
+            parse_uint(b"1.234", 2),
+            Err(NumericError::TooManyDecimals(3, 2))
+        ));
+        assert!(matches!(
+            parse_uint(&[b'9'; MAX_UINT_DIGITS], 1),
+            Err(NumericError::TooManyDigits(77))
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+use thiserror::Error;
+
+/// The number of bytes packed into a field element, which fits in the scalar field of BN254.
+pub const MAX_BYTES_IN_FIELD: usize = 31;
+
+/// Error definitions of the packing of bytes into field elements.
+#[derive(Error, Debug)]
+pub enum PackingError {
+    #[error("Field element {0} is not a decimal number")]
+    InvalidFieldElement(String),
+    #[error("Field element {0} does not fit in {} bytes", MAX_BYTES_IN_FIELD)]
+    FieldElementTooLarge(String),
+    #[error("The field elements hold {0} bytes but only {1} bytes are expected")]
+    TooManyBytes(usize, usize),
+}
+
+/// Packs bytes into field elements of 31 little-endian bytes each, as the `msg_packed` input and the
+/// `reveal{idx}_fields` outputs of the generated Circom templates.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes to be packed.
+///
+/// # Returns
+///
+/// The decimal strings of the `ceil(bytes.len() / 31)` field elements.
+pub fn pack_bytes(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(MAX_BYTES_IN_FIELD)
+        .map(le_bytes_to_decimal)
+        .collect()
+}
+
+/// Unpacks field elements of 31 little-endian bytes each into `num_bytes` bytes, the inverse of [`pack_bytes`].
+///
+/// # Arguments
+///
+/// * `fields` - The decimal strings of the field elements.
+/// * `num_bytes` - The number of packed bytes.
+///
+/// # Returns
+///
+/// A Result containing the bytes, or a PackingError if a field element is invalid or a byte after the first
+/// `num_bytes` bytes is not zero.
+pub fn unpack_bytes<S: AsRef<str>>(
+    fields: &[S],
+    num_bytes: usize,
+) -> Result<Vec<u8>, PackingError> {
+    let mut bytes = Vec::with_capacity(fields.len() * MAX_BYTES_IN_FIELD);
+    for field in fields {
+        bytes.extend(decimal_to_le_bytes(field.as_ref())?);
+    }
+    if let Some(last) = bytes.iter().rposition(|&byte| byte != 0) {
+        if last >= num_bytes {
+            return Err(PackingError::TooManyBytes(last + 1, num_bytes));
+        }
+    }
+    bytes.resize(num_bytes, 0);
+    Ok(bytes)
+}
+
+/// Returns the decimal string of the little-endian bytes.
+fn le_bytes_to_decimal(bytes: &[u8]) -> String {
+    let mut limbs = bytes.iter().rev().copied().collect::<Vec<_>>();
+    let mut digits = vec![];
+    while limbs.iter().any(|&limb| limb != 0) {
+        let mut remainder = 0u32;
+        for limb in limbs.iter_mut() {
+            let value = (remainder << 8) | *limb as u32;
+            *limb = (value / 10) as u8;
+            remainder = value % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+    }
+    if digits.is_empty() {
+        return "0".to_string();
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+/// Returns the 31 little-endian bytes of the decimal string.
+fn decimal_to_le_bytes(field: &str) -> Result<[u8; MAX_BYTES_IN_FIELD], PackingError> {
+    if field.is_empty() || !field.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(PackingError::InvalidFieldElement(field.to_string()));
+    }
+    let mut bytes = [0u8; MAX_BYTES_IN_FIELD];
+    for digit in field.bytes() {
+        let mut carry = (digit - b'0') as u32;
+        for byte in bytes.iter_mut() {
+            let value = *byte as u32 * 10 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        if carry != 0 {
+            return Err(PackingError::FieldElementTooLarge(field.to_string()));
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pack_bytes_round_trip() {
+        assert_eq!(pack_bytes(b"ab"), vec!["25185"]);
+        assert_eq!(pack_bytes(&[]), Vec::<String>::new());
+        assert_eq!(pack_bytes(&[0, 0]), vec!["0"]);
+
+        let bytes = (0..70).map(|i| (i * 37 % 256) as u8).collect::<Vec<_>>();
+        let fields = pack_bytes(&bytes);
+        assert_eq!(fields.len(), 3);
+        assert_eq!(unpack_bytes(&fields, bytes.len()).unwrap(), bytes);
+        // The padding of a fixed-size output is unpacked as zeros.
+        assert_eq!(
+            unpack_bytes(&pack_bytes(b"ab"), 4).unwrap(),
+            vec![b'a', b'b', 0, 0]
+        );
+
+        assert!(matches!(
+            unpack_bytes(&["25185"], 1),
+            Err(PackingError::TooManyBytes(2, 1))
+        ));
+        assert!(matches!(
+            unpack_bytes(&["0x12"], 1),
+            Err(PackingError::InvalidFieldElement(_))
+        ));
+        let too_large = le_bytes_to_decimal(&[255; 32]);
+        assert!(matches!(
+            unpack_bytes(&[too_large], 31),
+            Err(PackingError::FieldElementTooLarge(_))
+        ));
+    }
+}
@@ -0,0 +1,371 @@
+use crate::extract_substrs::{
+    extract_substr_idxes_from_bytes_with_options, extract_substr_idxes_with_options,
+    parse_decomposed_regex, DecomposedRegexConfig, DecomposedRegexOptions, ExtractSubstrssError,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// The built-in patterns as (name, json definition) pairs.
+///
+/// These definitions are shared by the extractors in this crate and the `zk-regex registry` command of the compiler,
+/// so that a circuit and its extractor are always generated from the same regex.
+const BUILTIN_PATTERNS: [(&str, &str); 10] = [
+    (
+        "email_addr",
+        include_str!("./decomposed_defs/email_addr.json"),
+    ),
+    (
+        "email_domain",
+        include_str!("./decomposed_defs/email_domain.json"),
+    ),
+    ("from_all", include_str!("./decomposed_defs/from_all.json")),
+    (
+        "from_addr",
+        include_str!("./decomposed_defs/from_addr.json"),
+    ),
+    ("to_all", include_str!("./decomposed_defs/to_all.json")),
+    ("to_addr", include_str!("./decomposed_defs/to_addr.json")),
+    (
+        "subject_all",
+        include_str!("./decomposed_defs/subject_all.json"),
+    ),
+    (
+        "body_hash",
+        include_str!("./decomposed_defs/body_hash.json"),
+    ),
+    (
+        "timestamp",
+        include_str!("./decomposed_defs/timestamp.json"),
+    ),
+    (
+        "message_id",
+        include_str!("./decomposed_defs/message_id.json"),
+    ),
+];
+
+/// Expected size of the circuit generated from a pattern.
+///
+/// The generated circuit has O(msg_bytes * dfa_states) signals, and one transition check per DFA transition and input byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitSize {
+    /// The number of states of the compiled DFA.
+    #[serde(alias = "dfaStates")]
+    pub dfa_states: usize,
+    /// The number of (from, to) state transitions of the compiled DFA.
+    #[serde(alias = "dfaTransitions")]
+    pub dfa_transitions: usize,
+}
+
+/// A pattern definition, i.e., the json file format of a pattern.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "PatternDefJson", into = "PatternDefJson")]
+pub struct PatternDef {
+    /// A human-readable description of what the pattern matches.
+    pub description: String,
+    /// The names of the public parts, in the order of the revealed substrings.
+    pub public_parts: Vec<String>,
+    /// The expected size of the generated circuit, if known.
+    pub expected_circuit_size: Option<CircuitSize>,
+    /// The decomposed regex of the pattern.
+    pub config: DecomposedRegexConfig,
+    /// The options of the decomposed regex, read from the same json.
+    pub options: DecomposedRegexOptions,
+}
+
+/// The json of a [`PatternDef`], whose decomposed regex is read as both its config and its options.
+#[derive(Serialize, Deserialize)]
+struct PatternDefJson {
+    #[serde(default)]
+    description: String,
+    #[serde(default, alias = "publicParts")]
+    public_parts: Vec<String>,
+    #[serde(default, alias = "expectedCircuitSize")]
+    expected_circuit_size: Option<CircuitSize>,
+    #[serde(flatten)]
+    decomposed_regex: serde_json::Map<String, serde_json::Value>,
+}
+
+impl TryFrom<PatternDefJson> for PatternDef {
+    type Error = serde_json::Error;
+
+    fn try_from(def: PatternDefJson) -> Result<Self, Self::Error> {
+        let (config, options) =
+            parse_decomposed_regex(&serde_json::Value::Object(def.decomposed_regex))?;
+        Ok(Self {
+            description: def.description,
+            public_parts: def.public_parts,
+            expected_circuit_size: def.expected_circuit_size,
+            config,
+            options,
+        })
+    }
+}
+
+impl From<PatternDef> for PatternDefJson {
+    fn from(def: PatternDef) -> Self {
+        let mut decomposed_regex = match serde_json::to_value(&def.config) {
+            Ok(serde_json::Value::Object(decomposed_regex)) => decomposed_regex,
+            _ => unreachable!("a decomposed regex is serialized as an object"),
+        };
+        def.options.write_to_json(&mut decomposed_regex);
+        Self {
+            description: def.description,
+            public_parts: def.public_parts,
+            expected_circuit_size: def.expected_circuit_size,
+            decomposed_regex,
+        }
+    }
+}
+
+/// A named pattern in a [`PatternRegistry`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pattern {
+    pub name: String,
+    #[serde(flatten)]
+    pub def: PatternDef,
+}
+
+/// Error definitions of the pattern registry.
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("Failed to read pattern {}: {}", .0.display(), .1)]
+    IoError(PathBuf, #[source] std::io::Error),
+    #[error("Failed to parse pattern {}: {}", .0.display(), .1)]
+    JsonParseError(PathBuf, #[source] serde_json::Error),
+    #[error("Pattern {0} has {1} public parts but {2} public part names")]
+    PublicPartsMismatch(String, usize, usize),
+}
+
+/// A set of named patterns, consisting of the built-in patterns and user-defined pattern packs.
+#[derive(Debug, Clone, Default)]
+pub struct PatternRegistry {
+    patterns: BTreeMap<String, Pattern>,
+}
+
+impl PatternRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry containing the built-in patterns.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        for (name, json) in BUILTIN_PATTERNS {
+            let def = serde_json::from_str(json).expect("built-in patterns must be valid");
+            registry
+                .register(name, def)
+                .expect("built-in patterns must be valid");
+        }
+        registry
+    }
+
+    /// Registers a pattern, replacing any existing pattern with the same name.
+    ///
+    /// If `public_parts` is empty, the public parts are named `{name}_{idx}`.
+    pub fn register(&mut self, name: &str, mut def: PatternDef) -> Result<(), RegistryError> {
+        let num_public_parts = def
+            .config
+            .parts
+            .iter()
+            .filter(|part| part.is_public)
+            .count();
+        if def.public_parts.is_empty() {
+            def.public_parts = (0..num_public_parts)
+                .map(|idx| format!("{}_{}", name, idx))
+                .collect();
+        } else if def.public_parts.len() != num_public_parts {
+            return Err(RegistryError::PublicPartsMismatch(
+                name.to_string(),
+                num_public_parts,
+                def.public_parts.len(),
+            ));
+        }
+
+        self.patterns.insert(
+            name.to_string(),
+            Pattern {
+                name: name.to_string(),
+                def,
+            },
+        );
+        Ok(())
+    }
+
+    /// Registers every `*.json` file in `dir` as a pattern named after its file stem.
+    ///
+    /// Returns the names of the registered patterns.
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> Result<Vec<String>, RegistryError> {
+        let dir = dir.as_ref();
+        let mut paths = fs::read_dir(dir)
+            .map_err(|e| RegistryError::IoError(dir.to_path_buf(), e))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| RegistryError::IoError(dir.to_path_buf(), e))?;
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+        paths.sort();
+
+        let mut names = vec![];
+        for path in paths {
+            let file = File::open(&path).map_err(|e| RegistryError::IoError(path.clone(), e))?;
+            let def: PatternDef = serde_json::from_reader(file)
+                .map_err(|e| RegistryError::JsonParseError(path.clone(), e))?;
+            let name = path
+                .file_stem()
+                .expect("a json file has a file stem")
+                .to_string_lossy()
+                .to_string();
+            self.register(&name, def)?;
+            names.push(name);
+        }
+        Ok(names)
+    }
+
+    /// Returns the pattern with the given name.
+    pub fn get(&self, name: &str) -> Option<&Pattern> {
+        self.patterns.get(name)
+    }
+
+    /// Returns all patterns, sorted by name.
+    pub fn patterns(&self) -> impl Iterator<Item = &Pattern> {
+        self.patterns.values()
+    }
+
+    /// Returns the names of all patterns, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.patterns.keys().map(String::as_str)
+    }
+
+    fn def(&self, name: &str) -> Result<&PatternDef, ExtractSubstrssError> {
+        self.get(name)
+            .map(|pattern| &pattern.def)
+            .ok_or_else(|| ExtractSubstrssError::PatternNotFound(name.to_string()))
+    }
+
+    /// Extracts the indexes of the public parts of the pattern named `name` from `input_str`.
+    pub fn extract_by_name(
+        &self,
+        name: &str,
+        input_str: &str,
+        reveal_private: bool,
+    ) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
+        let def = self.def(name)?;
+        extract_substr_idxes_with_options(input_str, &def.config, &def.options, reveal_private)
+    }
+
+    /// Same as [`PatternRegistry::extract_by_name`], but for raw bytes.
+    pub fn extract_by_name_from_bytes(
+        &self,
+        name: &str,
+        input: &[u8],
+        reveal_private: bool,
+    ) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
+        let def = self.def(name)?;
+        extract_substr_idxes_from_bytes_with_options(
+            input,
+            &def.config,
+            &def.options,
+            reveal_private,
+        )
+    }
+}
+
+/// Parses the definition of the built-in pattern named `name`, without building the whole registry.
+fn builtin_def(name: &str) -> Result<PatternDef, ExtractSubstrssError> {
+    BUILTIN_PATTERNS
+        .iter()
+        .find(|(builtin_name, _)| *builtin_name == name)
+        .map(|(_, json)| serde_json::from_str(json).expect("built-in patterns must be valid"))
+        .ok_or_else(|| ExtractSubstrssError::PatternNotFound(name.to_string()))
+}
+
+/// Extracts the indexes of the public parts of the built-in pattern named `name` from `input_str`.
+pub fn extract_by_name(
+    name: &str,
+    input_str: &str,
+) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
+    let def = builtin_def(name)?;
+    extract_substr_idxes_with_options(input_str, &def.config, &def.options, false)
+}
+
+/// Same as [`extract_by_name`], but for raw bytes.
+pub fn extract_by_name_from_bytes(
+    name: &str,
+    input: &[u8],
+) -> Result<Vec<(usize, usize)>, ExtractSubstrssError> {
+    let def = builtin_def(name)?;
+    extract_substr_idxes_from_bytes_with_options(input, &def.config, &def.options, false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builtin_patterns() {
+        let registry = PatternRegistry::builtin();
+        assert_eq!(registry.names().count(), BUILTIN_PATTERNS.len());
+        for pattern in registry.patterns() {
+            assert!(!pattern.def.description.is_empty(), "{}", pattern.name);
+            assert!(
+                pattern.def.expected_circuit_size.is_some(),
+                "{}",
+                pattern.name
+            );
+        }
+        assert_eq!(
+            registry.get("from_addr").unwrap().def.public_parts,
+            vec!["from_addr".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_extract_by_name() {
+        let input_str = "from:dummy@a.com <suegamisora@gmail.com>\r\n";
+        assert_eq!(
+            extract_by_name("from_addr", input_str).unwrap(),
+            vec![(18, 39)]
+        );
+        assert!(matches!(
+            extract_by_name("unknown", input_str),
+            Err(ExtractSubstrssError::PatternNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = std::env::temp_dir().join(format!("zk-regex-registry-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("invoice_id.json"),
+            r#"{
+                "description": "Invoice id in a subject line",
+                "public_parts": ["invoice_id"],
+                "parts": [
+                    { "is_public": false, "regex_def": "Invoice #" },
+                    { "is_public": true, "regex_def": "[0-9]+" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        fs::write(dir.join("README.md"), "not a pattern").unwrap();
+
+        let mut registry = PatternRegistry::builtin();
+        let names = registry.load_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(names, vec!["invoice_id".to_string()]);
+        assert_eq!(
+            registry
+                .extract_by_name("invoice_id", "Re: Invoice #1234 paid", false)
+                .unwrap(),
+            vec![(13, 17)]
+        );
+    }
+}
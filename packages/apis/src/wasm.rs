@@ -1,9 +1,11 @@
 use crate::extract_substrs::*;
+use crate::registry::{PatternDef, PatternRegistry};
 use crate::*;
 use console_error_panic_hook;
-use js_sys::Array;
+use js_sys::{Array, Object, Uint8Array};
+use serde::Serialize;
 use serde_json::Value;
-use std::panic;
+use std::{cell::RefCell, panic};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -19,6 +21,49 @@ pub fn padString(str: &str, paddedBytesSize: usize) -> Array {
     arr
 }
 
+/// Packs bytes into field elements of 31 little-endian bytes each, e.g. the `msg_packed` input of a template
+/// generated with packed inputs, and returns their decimal strings.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn packBytes(bytes: &[u8]) -> Array {
+    let fields = packing::pack_bytes(bytes);
+
+    let arr = Array::new_with_length(fields.len() as u32);
+    for (i, field) in fields.iter().enumerate() {
+        arr.set(i as u32, JsValue::from_str(field));
+    }
+
+    arr
+}
+
+/// Unpacks `numBytes` bytes from the decimal strings of field elements, e.g. the `reveal{idx}_fields` outputs of
+/// a template generated with packed reveals.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn unpackBytes(fields: Vec<String>, numBytes: usize) -> Result<Uint8Array, JsValue> {
+    let bytes = packing::unpack_bytes(&fields, numBytes)
+        .map_err(|e| JsValue::from_str(&format!("Failed to unpack bytes: {}", e)))?;
+    Ok(Uint8Array::from(bytes.as_slice()))
+}
+
+/// Computes the `reveal{idx}_commitment` output of a committed part for its substring, `maxLen` and the decimal
+/// string of its salt, and returns its decimal string.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn commitBytes(substr: &[u8], maxLen: usize, salt: &str) -> Result<String, JsValue> {
+    commitment::commit_bytes(substr, maxLen, salt)
+        .map_err(|e| JsValue::from_str(&format!("Failed to commit bytes: {}", e)))
+}
+
+/// Converts the substring of a part with the `uint` output into the decimal string of its `reveal{idx}_value`
+/// output, scaled by `10 ** decimals`.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn parseUint(substr: &[u8], decimals: usize) -> Result<String, JsValue> {
+    numeric::parse_uint(substr, decimals)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse the value: {}", e)))
+}
+
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn extractSubstrIdxes(
@@ -26,62 +71,306 @@ pub fn extractSubstrIdxes(
     regexConfig: JsValue,
     reveal_private: bool,
 ) -> Result<Array, JsValue> {
-    let regex_config = parse_js_regex_config(regexConfig)?;
+    let (regex_config, options) = parse_js_regex_config(regexConfig)?;
 
-    let idxes = extract_substrs::extract_substr_idxes(inputStr, &regex_config, reveal_private)
-        .map_err(|e| {
-            println!("e: {:?}", e);
-            let error_msg = format!("Failed to extract indxes: {}", e);
-            JsValue::from_str(&error_msg)
-        })?;
+    let idxes = extract_substrs::extract_substr_idxes_with_options(
+        inputStr,
+        &regex_config,
+        &options,
+        reveal_private,
+    )
+    .map_err(|e| {
+        println!("e: {:?}", e);
+        let error_msg = format!("Failed to extract indxes: {}", e);
+        JsValue::from_str(&error_msg)
+    })?;
 
-    let arr = Array::new_with_length(idxes.len() as u32);
-    for (i, idx) in idxes.iter().enumerate() {
-        let js_arr = Array::new_with_length(2);
-        js_arr.set(0, JsValue::from(idx.0 as u32));
-        js_arr.set(1, JsValue::from(idx.1 as u32));
-        arr.set(i as u32, JsValue::from(js_arr));
+    Ok(idxes_to_js_array(&idxes))
+}
+
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn extractSubstr(
+    inputStr: &str,
+    regexConfig: JsValue,
+    reveal_private: bool,
+) -> Result<Array, JsValue> {
+    let (regex_config, options) = parse_js_regex_config(regexConfig)?;
+
+    let idxes = extract_substrs::extract_substr_idxes_with_options(
+        inputStr,
+        &regex_config,
+        &options,
+        reveal_private,
+    )
+    .map_err(|e| {
+        println!("e: {:?}", e);
+        let error_msg = format!("Failed to extract strings: {}", e);
+        JsValue::from_str(&error_msg)
+    })?;
+
+    let js_array = Array::new_with_length(idxes.len() as u32);
+    for (i, &(start, end)) in idxes.iter().enumerate() {
+        js_array.set(i as u32, JsValue::from_str(&inputStr[start..end]));
     }
 
-    Ok(arr)
+    Ok(js_array)
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const SUBSTR_OFFSETS_TS: &'static str = r#"
+export interface SubstrOffsets {
+    byteStart: number;
+    byteEnd: number;
+    utf16Start: number;
+    utf16End: number;
+    charStart: number;
+    charEnd: number;
 }
+"#;
 
+/// Same as `extractSubstrIdxes`, but returns `SubstrOffsets` objects with the UTF-8 byte,
+/// UTF-16 code unit (JavaScript string) and Unicode scalar offsets of each substring.
 #[wasm_bindgen]
 #[allow(non_snake_case)]
-pub fn extractSubstr(
+pub fn extractSubstrOffsets(
     inputStr: &str,
     regexConfig: JsValue,
     reveal_private: bool,
+) -> Result<JsValue, JsValue> {
+    let (regex_config, options) = parse_js_regex_config(regexConfig)?;
+
+    let offsets = extract_substrs::extract_substr_idxes_with_options(
+        inputStr,
+        &regex_config,
+        &options,
+        reveal_private,
+    )
+    .and_then(|idxes| extract_substrs::byte_idxes_to_offsets(inputStr, &idxes))
+    .map_err(|e| {
+        let error_msg = format!("Failed to extract indxes: {}", e);
+        JsValue::from_str(&error_msg)
+    })?;
+
+    serde_wasm_bindgen::to_value(&offsets).map_err(JsValue::from)
+}
+
+/// Converts the byte indexes returned by the `extract*Idxes` functions into `SubstrOffsets` objects.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn idxesToOffsets(inputStr: &str, idxes: JsValue) -> Result<JsValue, JsValue> {
+    let idxes: Vec<(usize, usize)> = serde_wasm_bindgen::from_value(idxes)
+        .map_err(|e| JsValue::from_str(&simplify_error(&e)))?;
+
+    let offsets = extract_substrs::byte_idxes_to_offsets(inputStr, &idxes)
+        .map_err(|e| JsValue::from_str(&format!("Failed to convert indxes: {}", e)))?;
+
+    serde_wasm_bindgen::to_value(&offsets).map_err(JsValue::from)
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const MATCH_FAILURE_TS: &'static str = r#"
+export interface MatchFailure {
+    matchedParts: number;
+    stoppedAt: number;
+    expectedPart: number;
+    expectedRegexDef: string;
+    snippetStart: number;
+    snippet: string;
+}
+"#;
+
+/// Explains why `inputStr` does not match the regex: returns a `MatchFailure` object, or `null` if it matches.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn diagnoseMatch(inputStr: &str, regexConfig: JsValue) -> Result<JsValue, JsValue> {
+    let (regex_config, _) = parse_js_regex_config(regexConfig)?;
+
+    let failure = extract_substrs::diagnose_match(inputStr, &regex_config)
+        .map_err(|e| JsValue::from_str(&format!("Failed to diagnose match: {}", e)))?;
+
+    failure
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(JsValue::from)
+}
+
+/// Same as `diagnoseMatch`, but for raw bytes.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn diagnoseMatchFromBytes(inputBytes: &[u8], regexConfig: JsValue) -> Result<JsValue, JsValue> {
+    let (regex_config, _) = parse_js_regex_config(regexConfig)?;
+
+    let failure = extract_substrs::diagnose_match_in_bytes(inputBytes, &regex_config)
+        .map_err(|e| JsValue::from_str(&format!("Failed to diagnose match: {}", e)))?;
+
+    failure
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(JsValue::from)
+}
+
+/// Checks the constraints of several regexes matched on the same bytes, including the constraints between their
+/// parts. `regexConfigs` is an object mapping the name of each regex to its config.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn checkPartConstraints(inputBytes: &[u8], regexConfigs: JsValue) -> Result<(), JsValue> {
+    let regex_configs = regexConfigs
+        .dyn_into::<Object>()
+        .map_err(|_| JsValue::from_str("The regex configs must be an object"))?;
+    let regexes = Object::entries(&regex_configs)
+        .iter()
+        .map(|entry| {
+            let entry = Array::from(&entry);
+            let name = entry
+                .get(0)
+                .as_string()
+                .ok_or_else(|| JsValue::from_str("The regex names must be strings"))?;
+            let (regex_config, options) = parse_js_regex_config(entry.get(1))?;
+            Ok((name, regex_config, options))
+        })
+        .collect::<Result<Vec<_>, JsValue>>()?;
+
+    extract_substrs::check_part_constraints(inputBytes, &regexes)
+        .map_err(|e| JsValue::from_str(&format!("Failed to check the constraints: {}", e)))
+}
+
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn extractSubstrIdxesFromBytes(
+    inputBytes: &[u8],
+    regexConfig: JsValue,
+    reveal_private: bool,
 ) -> Result<Array, JsValue> {
-    let regex_config = parse_js_regex_config(regexConfig)?;
+    let (regex_config, options) = parse_js_regex_config(regexConfig)?;
 
-    let result_strs = extract_substrs::extract_substr(inputStr, &regex_config, reveal_private)
-        .map_err(|e| {
-            println!("e: {:?}", e);
-            let error_msg = format!("Failed to extract strings: {}", e);
-            JsValue::from_str(&error_msg)
-        })?;
+    let idxes = extract_substrs::extract_substr_idxes_from_bytes_with_options(
+        inputBytes,
+        &regex_config,
+        &options,
+        reveal_private,
+    )
+    .map_err(|e| {
+        let error_msg = format!("Failed to extract indxes: {}", e);
+        JsValue::from_str(&error_msg)
+    })?;
+
+    Ok(idxes_to_js_array(&idxes))
+}
+
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn extractSubstrFromBytes(
+    inputBytes: &[u8],
+    regexConfig: JsValue,
+    reveal_private: bool,
+) -> Result<Array, JsValue> {
+    let (regex_config, options) = parse_js_regex_config(regexConfig)?;
+
+    let idxes = extract_substrs::extract_substr_idxes_from_bytes_with_options(
+        inputBytes,
+        &regex_config,
+        &options,
+        reveal_private,
+    )
+    .map_err(|e| {
+        let error_msg = format!("Failed to extract bytes: {}", e);
+        JsValue::from_str(&error_msg)
+    })?;
 
-    let js_array = Array::new_with_length(result_strs.len() as u32);
-    for (i, s) in result_strs.into_iter().enumerate() {
-        js_array.set(i as u32, JsValue::from_str(&s));
+    let js_array = Array::new_with_length(idxes.len() as u32);
+    for (i, &(start, end)) in idxes.iter().enumerate() {
+        js_array.set(
+            i as u32,
+            JsValue::from(Uint8Array::from(&inputBytes[start..end])),
+        );
     }
 
     Ok(js_array)
 }
 
+thread_local! {
+    static REGISTRY: RefCell<PatternRegistry> = RefCell::new(PatternRegistry::builtin());
+}
+
+/// Extracts the indexes of the public parts of the registered pattern named `name`.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn extractByName(name: &str, inputStr: &str, reveal_private: bool) -> Result<Array, JsValue> {
+    let idxes = REGISTRY
+        .with(|registry| {
+            registry
+                .borrow()
+                .extract_by_name(name, inputStr, reveal_private)
+        })
+        .map_err(|e| JsValue::from_str(&format!("Failed to extract indxes: {}", e)))?;
+
+    Ok(idxes_to_js_array(&idxes))
+}
+
+/// Same as `extractByName`, but for raw bytes.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn extractByNameFromBytes(
+    name: &str,
+    inputBytes: &[u8],
+    reveal_private: bool,
+) -> Result<Array, JsValue> {
+    let idxes = REGISTRY
+        .with(|registry| {
+            registry
+                .borrow()
+                .extract_by_name_from_bytes(name, inputBytes, reveal_private)
+        })
+        .map_err(|e| JsValue::from_str(&format!("Failed to extract indxes: {}", e)))?;
+
+    Ok(idxes_to_js_array(&idxes))
+}
+
+/// Returns the registered patterns with their name, description, public parts, expected circuit size and parts.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn listPatterns() -> Result<Array, JsValue> {
+    REGISTRY.with(|registry| {
+        let registry = registry.borrow();
+        let arr = Array::new();
+        for pattern in registry.patterns() {
+            let value = pattern
+                .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+                .map_err(JsValue::from)?;
+            arr.push(&value);
+        }
+        Ok(arr)
+    })
+}
+
+/// Registers a pattern definition, given as a json string or an object, under `name`.
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn registerPattern(name: &str, patternDef: JsValue) -> Result<(), JsValue> {
+    let def: PatternDef = if patternDef.is_string() {
+        serde_json::from_str(&patternDef.as_string().unwrap()).map_err(|e| {
+            let error_msg = format!("Failed to parse JSON string: {}", e);
+            JsValue::from_str(&error_msg)
+        })?
+    } else {
+        serde_wasm_bindgen::from_value(patternDef)
+            .map_err(|e| JsValue::from_str(&simplify_error(&e)))?
+    };
+
+    REGISTRY
+        .with(|registry| registry.borrow_mut().register(name, def))
+        .map_err(|e| JsValue::from_str(&format!("Failed to register pattern: {}", e)))
+}
+
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn extractEmailAddrIdxes(inputStr: &str) -> Result<Array, JsValue> {
-    let regex_config = include_str!("./decomposed_defs/email_addr.json");
-    extractSubstrIdxes(inputStr, JsValue::from_str(regex_config), false)
+    extract_builtin_idxes("email_addr", inputStr)
 }
 
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn extractEmailDomainIdxes(inputStr: &str) -> Result<Array, JsValue> {
-    let regex_config = include_str!("./decomposed_defs/email_domain.json");
-    extractSubstrIdxes(inputStr, JsValue::from_str(regex_config), false)
+    extract_builtin_idxes("email_domain", inputStr)
 }
 
 // #[wasm_bindgen]
@@ -94,64 +383,75 @@ pub fn extractEmailDomainIdxes(inputStr: &str) -> Result<Array, JsValue> {
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn extractFromAllIdxes(inputStr: &str) -> Result<Array, JsValue> {
-    let regex_config = include_str!("./decomposed_defs/from_all.json");
-    extractSubstrIdxes(inputStr, JsValue::from_str(regex_config), false)
+    extract_builtin_idxes("from_all", inputStr)
 }
 
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn extractFromAddrIdxes(inputStr: &str) -> Result<Array, JsValue> {
-    let regex_config = include_str!("./decomposed_defs/from_addr.json");
-    extractSubstrIdxes(inputStr, JsValue::from_str(regex_config), false)
+    extract_builtin_idxes("from_addr", inputStr)
 }
 
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn extractToAllIdxes(inputStr: &str) -> Result<Array, JsValue> {
-    let regex_config = include_str!("./decomposed_defs/to_all.json");
-    extractSubstrIdxes(inputStr, JsValue::from_str(regex_config), false)
+    extract_builtin_idxes("to_all", inputStr)
 }
 
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn extractToAddrIdxes(inputStr: &str) -> Result<Array, JsValue> {
-    let regex_config = include_str!("./decomposed_defs/to_addr.json");
-    extractSubstrIdxes(inputStr, JsValue::from_str(regex_config), false)
+    extract_builtin_idxes("to_addr", inputStr)
 }
 
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn extractSubjectAllIdxes(inputStr: &str) -> Result<Array, JsValue> {
-    let regex_config = include_str!("./decomposed_defs/subject_all.json");
-    extractSubstrIdxes(inputStr, JsValue::from_str(regex_config), false)
+    extract_builtin_idxes("subject_all", inputStr)
 }
 
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn extractBodyHashIdxes(inputStr: &str) -> Result<Array, JsValue> {
-    let regex_config = include_str!("./decomposed_defs/body_hash.json");
-    extractSubstrIdxes(inputStr, JsValue::from_str(regex_config), false)
+    extract_builtin_idxes("body_hash", inputStr)
 }
 
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn extractTimestampIdxes(inputStr: &str) -> Result<Array, JsValue> {
-    let regex_config = include_str!("./decomposed_defs/timestamp.json");
-    extractSubstrIdxes(inputStr, JsValue::from_str(regex_config), false)
+    extract_builtin_idxes("timestamp", inputStr)
 }
 
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn extractMessageIdIdxes(inputStr: &str) -> Result<Array, JsValue> {
-    let regex_config = include_str!("./decomposed_defs/message_id.json");
-    extractSubstrIdxes(inputStr, JsValue::from_str(regex_config), false)
+    extract_builtin_idxes("message_id", inputStr)
+}
+
+// Extracts the indexes of the public parts of a built-in pattern through the shared registry
+fn extract_builtin_idxes(name: &str, input_str: &str) -> Result<Array, JsValue> {
+    extractByName(name, input_str, false)
+}
+
+// Converts (start, end) index pairs into a js array of [start, end] arrays
+fn idxes_to_js_array(idxes: &[(usize, usize)]) -> Array {
+    let arr = Array::new_with_length(idxes.len() as u32);
+    for (i, idx) in idxes.iter().enumerate() {
+        let js_arr = Array::new_with_length(2);
+        js_arr.set(0, JsValue::from(idx.0 as u32));
+        js_arr.set(1, JsValue::from(idx.1 as u32));
+        arr.set(i as u32, JsValue::from(js_arr));
+    }
+    arr
 }
 
 // Accepts regexConfig either as string or js object
-fn parse_js_regex_config(regex_config: JsValue) -> Result<DecomposedRegexConfig, JsValue> {
+fn parse_js_regex_config(
+    regex_config: JsValue,
+) -> Result<(DecomposedRegexConfig, DecomposedRegexOptions), JsValue> {
     // Checks if regexConfig is passed as string or object
     // As string
-    let parsed_config: DecomposedRegexConfig = if regex_config.is_string() {
+    let parsed_config: Value = if regex_config.is_string() {
         let config_str = regex_config.as_string().unwrap();
         serde_json::from_str(&config_str).map_err(|e| {
             let error_msg = format!("Failed to parse JSON string: {}", e);
@@ -165,7 +465,7 @@ fn parse_js_regex_config(regex_config: JsValue) -> Result<DecomposedRegexConfig,
         })?
     };
 
-    Ok(parsed_config)
+    parse_decomposed_regex(&parsed_config).map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
 fn simplify_error(e: &serde_wasm_bindgen::Error) -> String {
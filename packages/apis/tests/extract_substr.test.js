@@ -1,4 +1,15 @@
-import { extractSubstrIdxes, extractSubstr } from '../pkg/zk_regex_apis';
+import {
+    extractSubstrIdxes,
+    extractSubstr,
+    extractSubstrIdxesFromBytes,
+    extractSubstrFromBytes,
+    extractSubstrOffsets,
+    idxesToOffsets,
+    extractByName,
+    listPatterns,
+    registerPattern,
+    diagnoseMatch
+} from '../pkg/zk_regex_apis';
 import airbnbEml from './airbnb_eml';
 
 describe('Extract substr test suite', async () => {
@@ -144,6 +155,31 @@ describe('Extract substr test suite', async () => {
         throw new Error('Did not throw an error');
     });
 
+    test('Should report where matching stopped', () => {
+        const parts = {
+            parts: [
+                {
+                    isPublic: true,
+                    regexDef: 'Hello '
+                },
+                {
+                    isPublic: false,
+                    regexDef: 'yall!'
+                }
+            ]
+        };
+        const input = 'Well, Hello there!';
+        expect(diagnoseMatch(input, parts)).toEqual({
+            matchedParts: 1,
+            stoppedAt: 12,
+            expectedPart: 1,
+            expectedRegexDef: 'yall!',
+            snippetStart: 0,
+            snippet: 'Well, Hello there!'
+        });
+        expect(diagnoseMatch('Hello yall!', parts)).toBeNull();
+    });
+
     test('extractSubstr should return actual matched string', () => {
         const parts = {
             parts: [
@@ -157,6 +193,58 @@ describe('Extract substr test suite', async () => {
         expect(strs[0]).toBe('Hello');
     });
 
+    test('Should extract byte indicies from non UTF-8 input', () => {
+        const parts = {
+            parts: [
+                {
+                    isPublic: false,
+                    regexDef: 'Hello '
+                },
+                {
+                    isPublic: true,
+                    regexDef: '[a-z]+'
+                }
+            ]
+        };
+        const input = new Uint8Array([
+            0xe9, 0x20, ...new TextEncoder().encode('Hello guys!')
+        ]);
+        const result = extractSubstrIdxesFromBytes(input, parts, false);
+        expect(result).toEqual([[8, 12]]);
+        const bytes = extractSubstrFromBytes(input, parts, false);
+        expect(new TextDecoder().decode(bytes[0])).toBe('guys');
+    });
+
+    test('Should return UTF-16 offsets for emoji and CJK characters', () => {
+        const parts = {
+            parts: [
+                {
+                    isPublic: false,
+                    regexDef: 'subject:'
+                },
+                {
+                    isPublic: true,
+                    regexDef: '[^\r\n]+'
+                }
+            ]
+        };
+        const input = 'subject:🎉 日本 party';
+        const [offsets] = extractSubstrOffsets(input, parts, false);
+        expect(offsets).toEqual({
+            byteStart: 8,
+            byteEnd: 25,
+            utf16Start: 8,
+            utf16End: 19,
+            charStart: 8,
+            charEnd: 18
+        });
+        expect(input.slice(offsets.utf16Start, offsets.utf16End)).toBe(
+            '🎉 日本 party'
+        );
+        const idxes = extractSubstrIdxes(input, parts, false);
+        expect(idxesToOffsets(input, idxes)).toEqual([offsets]);
+    });
+
     test('extractSubstr should return an empty array on all private fields', () => {
         const parts = {
             parts: [
@@ -169,4 +257,22 @@ describe('Extract substr test suite', async () => {
         const strs = extractSubstr(airbnbEml, parts, false);
         expect(strs.length).toBe(0);
     });
+
+    test('Should extract by the name of a registered pattern', () => {
+        const names = listPatterns().map(pattern => pattern.name);
+        expect(names).toContain('from_addr');
+        const input = 'from:dummy@a.com <suegamisora@gmail.com>\r\n';
+        expect(extractByName('from_addr', input, false)).toEqual([[18, 39]]);
+
+        registerPattern('invoice_id', {
+            description: 'Invoice id in a subject line',
+            publicParts: ['invoice_id'],
+            parts: [
+                { isPublic: false, regexDef: 'Invoice #' },
+                { isPublic: true, regexDef: '[0-9]+' }
+            ]
+        });
+        expect(extractByName('invoice_id', 'Re: Invoice #1234 paid', false)).toEqual([[13, 17]]);
+        expect(() => extractByName('unknown', input, false)).toThrow();
+    });
 });
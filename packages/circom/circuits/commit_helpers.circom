@@ -0,0 +1,20 @@
+pragma circom 2.0.3;
+
+include "circomlib/circuits/poseidon.circom";
+include "./regex_helpers.circom";
+
+// Commits to num_bytes bytes with the Poseidon hash of their packed field elements and the salt.
+template CommitBytes(num_bytes) {
+    var num_fields = (num_bytes + 30) \ 31;
+    signal input in[num_bytes];
+    signal input salt;
+    signal output out;
+
+    signal packed[num_fields] <== PackBytes(num_bytes)(in);
+    component hasher = Poseidon(num_fields + 1);
+    for (var k = 0; k < num_fields; k++) {
+        hasher.inputs[k] <== packed[k];
+    }
+    hasher.inputs[num_fields] <== salt;
+    out <== hasher.out;
+}
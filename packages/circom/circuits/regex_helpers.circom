@@ -46,6 +46,19 @@ template MultiOR(n) {
     out <== 1 - is_zero.out;
 }
 
+// Outputs the sum of the inputs, which must be 0 or 1, i.e., at most one input is 1 if they are all 0 or 1.
+template OneHotSum(n) {
+    signal input in[n];
+    signal output out;
+
+    var sum = 0;
+    for (var i = 0; i < n; i++) {
+        sum += in[i];
+    }
+    out <== sum;
+    out * (out - 1) === 0;
+}
+
 template MultiNOR(n) {
     signal input in[n];
     signal output out;
@@ -71,4 +84,37 @@ template IsNotZeroAcc() {
 
     signal is_zero <== IsZero()(in);
     out <== acc + (1 - is_zero);
-}
\ No newline at end of file
+}
+
+// Packs num_bytes bytes into field elements of 31 little-endian bytes each.
+template PackBytes(num_bytes) {
+    var num_fields = (num_bytes + 30) \ 31;
+    signal input in[num_bytes];
+    signal output out[num_fields];
+
+    for (var k = 0; k < num_fields; k++) {
+        var sum = 0;
+        for (var j = 0; j < 31 && 31 * k + j < num_bytes; j++) {
+            sum += in[31 * k + j] * (256 ** j);
+        }
+        out[k] <== sum;
+    }
+}
+
+// Unpacks num_bytes bytes from field elements of 31 little-endian bytes each, constraining each byte to 8 bits.
+template UnpackBytes(num_bytes) {
+    var num_fields = (num_bytes + 30) \ 31;
+    signal input in[num_fields];
+    signal output out[num_bytes];
+
+    component bits[num_bytes];
+    for (var i = 0; i < num_bytes; i++) {
+        out[i] <-- (in[i \ 31] >> (8 * (i % 31))) & 255;
+        bits[i] = Num2Bits(8);
+        bits[i].in <== out[i];
+    }
+    signal packed[num_fields] <== PackBytes(num_bytes)(out);
+    for (var k = 0; k < num_fields; k++) {
+        packed[k] === in[k];
+    }
+}
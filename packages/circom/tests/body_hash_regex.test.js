@@ -13,7 +13,7 @@ describe('Bodyhash Regex', () => {
     let circuit;
     beforeAll(async () => {
         const email_addr_json = readFileSync(
-            path.join(__dirname, '../circuits/common/body_hash.json'),
+            path.join(__dirname, '../../apis/src/decomposed_defs/body_hash.json'),
             'utf8'
         );
         const circom = compiler.genFromDecomposed(
@@ -43,7 +43,7 @@ describe('Bodyhash Regex', () => {
         const prefixIdxes = apis.extractSubstrIdxes(
             signatureField,
             readFileSync(
-                path.join(__dirname, '../circuits/common/body_hash.json'),
+                path.join(__dirname, '../../apis/src/decomposed_defs/body_hash.json'),
                 'utf8'
             ),
             false
@@ -69,7 +69,7 @@ describe('Bodyhash Regex', () => {
         const prefixIdxes = apis.extractSubstrIdxes(
             signatureField,
             readFileSync(
-                path.join(__dirname, '../circuits/common/body_hash.json'),
+                path.join(__dirname, '../../apis/src/decomposed_defs/body_hash.json'),
                 'utf8'
             ),
             false
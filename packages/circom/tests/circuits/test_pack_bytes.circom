@@ -0,0 +1,12 @@
+pragma circom 2.1.5;
+
+include "@zk-email/zk-regex-circom/circuits/regex_helpers.circom";
+
+template PackUnpackBytes(num_bytes) {
+    var num_fields = (num_bytes + 30) \ 31;
+    signal input in[num_bytes];
+    signal output packed[num_fields] <== PackBytes(num_bytes)(in);
+    signal output unpacked[num_bytes] <== UnpackBytes(num_bytes)(packed);
+}
+
+component main = PackUnpackBytes(70);
@@ -0,0 +1,5 @@
+pragma circom 2.1.5;
+
+include "@zk-email/zk-regex-circom/circuits/regex_helpers.circom";
+
+component main = UnpackBytes(31);
@@ -13,7 +13,7 @@ describe("Email Address Regex", () => {
   let circuit;
   beforeAll(async () => {
     const email_addr_json = readFileSync(
-      path.join(__dirname, "../circuits/common/email_addr.json"),
+      path.join(__dirname, "../../apis/src/decomposed_defs/email_addr.json"),
       "utf8"
     );
     const circom = compiler.genFromDecomposed(
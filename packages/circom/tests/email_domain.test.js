@@ -13,7 +13,7 @@ describe("Email Domain Regex", () => {
   let circuit;
   beforeAll(async () => {
     const email_addr_json = readFileSync(
-      path.join(__dirname, "../circuits/common/email_domain.json"),
+      path.join(__dirname, "../../apis/src/decomposed_defs/email_domain.json"),
       "utf8"
     );
     const circom = compiler.genFromDecomposed(
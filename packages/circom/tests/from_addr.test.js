@@ -14,7 +14,7 @@ describe("From Addr Regex", () => {
   beforeAll(async () => {
     {
       const email_addr_json = readFileSync(
-        path.join(__dirname, "../circuits/common/from_all.json"),
+        path.join(__dirname, "../../apis/src/decomposed_defs/from_all.json"),
         "utf8"
       );
       const circom = compiler.genFromDecomposed(
@@ -45,7 +45,7 @@ describe("From Addr Regex", () => {
     }
     {
       const email_addr_json = readFileSync(
-        path.join(__dirname, "../circuits/common/email_addr.json"),
+        path.join(__dirname, "../../apis/src/decomposed_defs/email_addr.json"),
         "utf8"
       );
       const circom = compiler.genFromDecomposed(
@@ -0,0 +1,163 @@
+import circom_tester from "circom_tester";
+import * as path from "path";
+import { mkdirSync, readFileSync, writeFileSync } from "fs";
+import apis from "../../apis/pkg";
+import compiler from "../../compiler/pkg";
+const option = {
+  include: path.join(__dirname, "../../../node_modules"),
+};
+const wasm_tester = circom_tester.wasm;
+const generatedDir = path.join(__dirname, "./circuits/generated");
+
+// Generates the template of a decomposed regex with the given Circom options, e.g. `{ searchMode: "anchored" }`,
+// and compiles it as the main component over messages of `msgBytes` bytes.
+export async function compileDecomposed(
+  templateName,
+  decomposedRegex,
+  circomOptions = {},
+  { msgBytes = 64, publicInputs = [] } = {}
+) {
+  return compileTemplate(
+    templateName,
+    compiler.genFromDecomposedWithOptions(
+      JSON.stringify(decomposedRegex),
+      templateName,
+      JSON.stringify(circomOptions)
+    ),
+    { msgBytes, publicInputs }
+  );
+}
+
+// Generates the combined template of named decomposed regexes, given as [name, decomposedRegex] pairs, whose `out`
+// is `1` if all (`combine = "all"`) or any (`combine = "any"`) of them match, and compiles it as the main component.
+export async function compileCombined(
+  templateName,
+  regexes,
+  combine = "all",
+  circomOptions = {},
+  { msgBytes = 64, publicInputs = [] } = {}
+) {
+  return compileTemplate(
+    templateName,
+    compiler.genCombinedFromDecomposed(
+      JSON.stringify(regexes),
+      templateName,
+      combine,
+      JSON.stringify(circomOptions)
+    ),
+    { msgBytes, publicInputs }
+  );
+}
+
+// Writes the generated template and a main component over messages of `msgBytes` bytes, and compiles it.
+async function compileTemplate(
+  templateName,
+  circom,
+  { msgBytes, publicInputs }
+) {
+  mkdirSync(generatedDir, { recursive: true });
+  writeFileSync(path.join(generatedDir, `${templateName}.circom`), circom);
+  const publicSignals =
+    publicInputs.length > 0 ? ` {public [${publicInputs.join(", ")}]}` : "";
+  const mainPath = path.join(generatedDir, `test_${templateName}.circom`);
+  writeFileSync(
+    mainPath,
+    `pragma circom 2.1.5;\n\ninclude "./${templateName}.circom";\ncomponent main${publicSignals} = ${templateName}(${msgBytes});\n`
+  );
+  const circuit = await wasm_tester(mainPath, option);
+  await circuit.loadSymbols();
+  return circuit;
+}
+
+// Calculates the witness of the inputs and checks the constraints. Returns a function that reads a signal of the
+// main component by name, e.g. `reveal0[3]`.
+export async function calculateSignals(circuit, inputs) {
+  const witness = await circuit.calculateWitness(inputs);
+  await circuit.checkConstraints(witness);
+  return (name) => witness[circuit.symbols[`main.${name}`].varIdx];
+}
+
+// Returns the [start, end) ranges of the bytes revealed in the `reveal{idx}` output.
+export function revealedSpans(signal, idx, msgBytes) {
+  const spans = [];
+  for (let i = 0; i < msgBytes; i++) {
+    if (signal(`reveal${idx}[${i}]`) === 0n) {
+      continue;
+    }
+    if (spans.length > 0 && spans[spans.length - 1][1] === i) {
+      spans[spans.length - 1][1] = i + 1;
+    } else {
+      spans.push([i, i + 1]);
+    }
+  }
+  return spans;
+}
+
+// Returns the decomposed regex of the given parts, each a [regex_def, is_public] pair or a part object.
+export function decomposed(parts, config = {}) {
+  return {
+    parts: parts.map((part) =>
+      Array.isArray(part) ? { regex_def: part[0], is_public: part[1] } : part
+    ),
+    ...config,
+  };
+}
+
+// Returns the `out` signal and the `reveal{idx}` arrays of the main component, to compare the witnesses of
+// circuits generated with different options.
+export function outputSignals(circuit, signal, msgBytes) {
+  const outputs = { out: signal("out") };
+  for (let idx = 0; `main.reveal${idx}[0]` in circuit.symbols; idx++) {
+    outputs[`reveal${idx}`] = Array.from({ length: msgBytes }, (_, i) =>
+      signal(`reveal${idx}[${i}]`)
+    );
+  }
+  return outputs;
+}
+
+// Returns the decomposed regex of a json file of `zk-regex-apis`, e.g. `from_addr`.
+export function decomposedDef(name) {
+  return JSON.parse(
+    readFileSync(
+      path.join(__dirname, `../../apis/src/decomposed_defs/${name}.json`),
+      "utf8"
+    )
+  );
+}
+
+// The names of the json files of `zk-regex-apis`.
+export const decomposedDefNames = [
+  "body_hash",
+  "email_addr",
+  "email_domain",
+  "from_addr",
+  "from_all",
+  "message_id",
+  "subject_all",
+  "timestamp",
+  "to_addr",
+  "to_all",
+];
+
+// Returns the template name of a json file name, e.g. `FromAddr` for `from_addr`.
+export function toTemplateName(name) {
+  return name
+    .split("_")
+    .map((word) => word[0].toUpperCase() + word.slice(1))
+    .join("");
+}
+
+// Messages of 64 bytes for the regexes of the json files, which together contain every byte below 255.
+export const headerMessages = [
+  apis.padString(
+    "from:alice@zkemail.com\r\nsubject:hi there\r\nto:bob@zkemail.com\r\n",
+    64
+  ),
+  apis.padString(
+    "dkim-signature:v=1; a=rsa-sha256; d=gmail.com; bh=abc=; t=1694989812;",
+    64
+  ),
+  ...[0, 64, 128, 192].map((first) =>
+    Array.from({ length: 64 }, (_, i) => Math.min(first + i, 254))
+  ),
+];
@@ -0,0 +1,61 @@
+import circom_tester from "circom_tester";
+import * as path from "path";
+import apis from "../../apis/pkg";
+import { calculateSignals } from "./generated_circuits";
+const option = {
+  include: path.join(__dirname, "../../../node_modules"),
+};
+const wasm_tester = circom_tester.wasm;
+
+jest.setTimeout(240000);
+describe("Helper Templates", () => {
+  // Compiles a main component of `circuits/`, e.g. `test_pack_bytes.circom`.
+  const compileMain = async (file) => {
+    const circuit = await wasm_tester(
+      path.join(__dirname, "./circuits", file),
+      option
+    );
+    await circuit.loadSymbols();
+    return circuit;
+  };
+
+  it("PackBytes and UnpackBytes round trip", async () => {
+    const circuit = await compileMain("test_pack_bytes.circom");
+    const bytes = Uint8Array.from({ length: 70 }, (_, i) => (i * 37 + 1) % 256);
+    const signal = await calculateSignals(circuit, { in: Array.from(bytes) });
+    const fields = apis.packBytes(bytes);
+    expect(fields.length).toEqual(3);
+    for (let k = 0; k < fields.length; k++) {
+      expect(signal(`packed[${k}]`)).toEqual(BigInt(fields[k]));
+    }
+    for (let i = 0; i < bytes.length; i++) {
+      expect(signal(`unpacked[${i}]`)).toEqual(BigInt(bytes[i]));
+    }
+    expect(apis.unpackBytes(fields, bytes.length)).toEqual(bytes);
+  });
+
+  it("UnpackBytes rejects a field of more than 31 bytes", async () => {
+    const circuit = await compileMain("test_unpack_bytes.circom");
+    await calculateSignals(circuit, { in: ["1"] });
+    await expect(
+      circuit.calculateWitness({ in: [(256n ** 31n).toString()] })
+    ).rejects.toThrow();
+  });
+
+  it("CommitBytes matches commitBytes", async () => {
+    const circuit = await compileMain("test_commit_bytes.circom");
+    const signal = await calculateSignals(circuit, {
+      in: apis.padString("alice@zkemail.com", 40),
+      salt: "12345",
+    });
+    expect(signal("out")).toEqual(
+      BigInt(
+        apis.commitBytes(
+          new TextEncoder().encode("alice@zkemail.com"),
+          40,
+          "12345"
+        )
+      )
+    );
+  });
+});
@@ -13,7 +13,7 @@ describe("Message Id Regex", () => {
   let circuit;
   beforeAll(async () => {
     const email_addr_json = readFileSync(
-      path.join(__dirname, "../circuits/common/message_id.json"),
+      path.join(__dirname, "../../apis/src/decomposed_defs/message_id.json"),
       "utf8"
     );
     const circom = compiler.genFromDecomposed(
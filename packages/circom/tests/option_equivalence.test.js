@@ -0,0 +1,98 @@
+import apis from "../../apis/pkg";
+import {
+  calculateSignals,
+  compileDecomposed,
+  decomposedDef,
+  decomposedDefNames,
+  headerMessages,
+  outputSignals,
+  revealedSpans,
+  toTemplateName,
+} from "./generated_circuits";
+
+jest.setTimeout(1200000);
+describe("Option Equivalence", () => {
+  // The options whose circuits compute the `out` and `reveal{idx}` signals of the default circuit.
+  const equivalentOptions = [
+    ["Classes", { alphabetClasses: true }],
+    ["Index", { stateEncoding: "index" }],
+    ["OneHot", { oneHot: true }],
+    ["OneHotClasses", { oneHot: true, alphabetClasses: true }],
+  ];
+  // The `out` signal and the spans revealed in `reveal0` by the default circuits of some regexes.
+  const defaultCases = [
+    ["email_addr", "from:alice@gm.com x", 1n, [[5, 17]]],
+    ["email_addr", "a@b@c.d", 1n, [[0, 7]]],
+    ["email_addr", "no address", 0n, []],
+    ["email_addr", "x.y@z\n", 1n, [[0, 5]]],
+    ["subject_all", "subject:ab\r\n", 1n, [[8, 10]]],
+    ["subject_all", "x\r\nsubject:hi there\r\nsubject:c\r\n", 1n, [[11, 19]]],
+    ["subject_all", "subject:\r\n", 0n, []],
+    ["subject_all", "subject:ab", 0n, []],
+  ];
+  const messages = [
+    ...headerMessages,
+    ...defaultCases.map(([, input]) => apis.padString(input, 64)),
+  ];
+  // On these messages the default circuits find every match, so the `out` of the negated circuits is the opposite.
+  const negatedMessages = [
+    "from:alice@zkemail.com\r\nsubject:hi\r\n",
+    "to:bob\r\nx",
+    "\0\0\0",
+    "dkim-signature:v=1; a=rsa-sha256; bh=abc=; d=gmail.com;",
+  ].map((input) => apis.padString(input, 64));
+
+  for (const name of decomposedDefNames) {
+    describe(`${name} regex`, () => {
+      const circuits = {};
+      beforeAll(async () => {
+        for (const [variant, options] of [
+          ["Default", {}],
+          ...equivalentOptions,
+          ["Negated", { negate: true }],
+        ]) {
+          circuits[variant] = await compileDecomposed(
+            `${variant}${toTemplateName(name)}Regex`,
+            decomposedDef(name),
+            options
+          );
+        }
+      });
+
+      const cases = defaultCases
+        .filter(([def]) => def === name)
+        .map(([, ...fields]) => fields);
+      if (cases.length > 0) {
+        it.each(cases)("default signals on %j", async (input, out, spans) => {
+          const signal = await calculateSignals(circuits.Default, {
+            msg: apis.padString(input, 64),
+          });
+          expect(signal("out")).toEqual(out);
+          expect(revealedSpans(signal, 0, 64)).toEqual(spans);
+        });
+      }
+
+      it.each(
+        equivalentOptions.flatMap(([variant]) =>
+          messages.map((msg, i) => [variant, i, msg])
+        )
+      )("%s options on message %i", async (variant, _, msg) => {
+        const expected = await calculateSignals(circuits.Default, { msg });
+        const signal = await calculateSignals(circuits[variant], { msg });
+        expect(outputSignals(circuits[variant], signal, 64)).toEqual(
+          outputSignals(circuits.Default, expected, 64)
+        );
+      });
+
+      it.each(negatedMessages.map((msg, i) => [i, msg]))(
+        "negated on message %i",
+        async (_, msg) => {
+          const expected = await calculateSignals(circuits.Default, { msg });
+          const signal = await calculateSignals(circuits.Negated, { msg });
+          expect(signal("out")).toEqual(1n - expected("out"));
+          expect("main.reveal0[0]" in circuits.Negated.symbols).toBe(false);
+        }
+      );
+    });
+  }
+});
@@ -0,0 +1,404 @@
+import apis from "../../apis/pkg";
+import {
+  calculateSignals,
+  compileCombined,
+  compileDecomposed,
+  decomposed,
+  revealedSpans,
+} from "./generated_circuits";
+
+jest.setTimeout(1200000);
+describe("Option Signals", () => {
+  const encoder = new TextEncoder();
+  const capitalize = (word) => word[0].toUpperCase() + word.slice(1);
+  // The match policies with the spans revealed in `policyInput`.
+  const policyInput = "x=abc;x=de;x=f";
+  const policies = [
+    ["greedy", "Greedy", [[2, 5], [8, 10], [13, 14]]],
+    ["leftmost-longest", "LeftmostLongest", [[2, 5]]],
+    ["leftmost-shortest", "LeftmostShortest", [[2, 3]]],
+  ];
+  const amount = (decimals) =>
+    decomposed([
+      ["(\r\n|^)amount:", false],
+      {
+        regex_def: "[0-9a-z.]+",
+        is_public: true,
+        max_len: 6,
+        output: "uint",
+        ...(decimals > 0 ? { decimals } : {}),
+      },
+      ["\r\n", false],
+    ]);
+  const address = (prefix, config) =>
+    decomposed(
+      [
+        [prefix, false],
+        { regex_def: "[a-z]+", is_public: true, max_len: 4 },
+        ["\\.com", false],
+      ],
+      config
+    );
+
+  // The decomposed regexes of the cases by name, or the [name, decomposedRegex] pairs of a combined template.
+  const regexes = {
+    Subject: decomposed([
+      ["(\r\n|^)subject:", false],
+      ["[^\r\n]+", true],
+      ["\r\n", false],
+    ]),
+    LowerSubject: decomposed([
+      ["(\r\n|^)subject:", false],
+      ["[a-z]+", true],
+      ["\r\n", false],
+    ]),
+    Abac: decomposed([["abac", true]]),
+    PackedSubject: decomposed([
+      ["(\r\n|^)subject:", false],
+      { regex_def: "[a-z]+", is_public: true, max_len: 4 },
+      ["\r\n", false],
+    ]),
+    PackedX: decomposed([{ regex_def: "x", is_public: true, max_len: 4 }]),
+    CommittedSubject: decomposed([
+      ["(\r\n|^)subject:", false],
+      { regex_def: "[a-z]+", is_public: true, max_len: 4, committed: true },
+      ["\r\n", false],
+    ]),
+    Amount0: amount(0),
+    Amount2: amount(2),
+    FromId: decomposed(
+      [
+        ["from:[a-z]+@", false],
+        { regex_def: "[a-z]+", is_public: true, max_len: 6 },
+        ["\\.com;id:[a-z]+@", false],
+        { regex_def: "[a-z]+", is_public: true, max_len: 4 },
+        ["\\.com", false],
+      ],
+      { constraints: [{ equal: [1, 3] }] }
+    ),
+    EqualFromId: [
+      [
+        "equal_from",
+        address("from:[a-z]+@", {
+          constraints: [{ equal: [1, { regex: "equal_id", part: 1 }] }],
+        }),
+      ],
+      ["equal_id", address("id:[a-z]+@")],
+    ],
+  };
+  for (const [match_policy, name] of policies) {
+    regexes[`X${name}`] = decomposed(
+      [
+        ["x=", false],
+        ["[a-z]+", true],
+      ],
+      { match_policy }
+    );
+    regexes[`AB${name}`] = decomposed(
+      [
+        ["a", false],
+        ["b+", true],
+      ],
+      { match_policy }
+    );
+  }
+
+  // The Circom options of the cases by name.
+  const generators = [
+    ["Default", {}],
+    ["OneHot", { oneHot: true }],
+    ["Index", { stateEncoding: "index" }],
+  ];
+  const options = {
+    Anchored: { searchMode: "anchored" },
+    AtIndex: { searchMode: "at-index" },
+    PackedFields: { packedMsg: true, packedReveals: true },
+  };
+  for (const [generator, generatorOptions] of generators) {
+    options[generator] = generatorOptions;
+    for (const occurrence of ["first", "unique"]) {
+      options[`${generator}${capitalize(occurrence)}`] = {
+        ...generatorOptions,
+        occurrence,
+      };
+    }
+  }
+  const searchModes = ["Default", "Anchored"];
+
+  // Each case is [regex, options, input, expected], where `input` is the message or the inputs of the circuit, and
+  // `expected` maps signal names to their values, with `spans` for the spans revealed in `reveal0` and `hidden` for
+  // the outputs the circuit does not have, or is `null` if the constraints reject the input.
+  const cases = [];
+
+  // Match policies.
+  for (const [generator] of generators) {
+    for (const [, name, spans] of policies) {
+      cases.push([`X${name}`, generator, policyInput, { out: 1n, spans }]);
+    }
+  }
+
+  // Search modes.
+  for (const [input, spans] of [
+    ["subject:hi there\r\nto:bob\r\n", [[8, 16]]],
+    ["\r\nsubject:hi\r\n", [[10, 12]]],
+  ]) {
+    const msg = apis.padString(input, 64);
+    cases.push(
+      ["Subject", "Default", input, { out: 1n, spans }],
+      ["Subject", "Anchored", input, { out: 1n, spans }],
+      ["Subject", "AtIndex", { msg, start_index: 0 }, { out: 1n, spans }]
+    );
+  }
+  const notAtStart = "to:bob\r\nsubject:hi\r\n";
+  cases.push(
+    ["Subject", "Default", notAtStart, { out: 1n, spans: [[16, 18]] }],
+    ["Subject", "Anchored", notAtStart, { out: 0n, spans: [] }]
+  );
+  // The start is the beginning of the input for `^`, and `\r\n` may also be read from it.
+  for (const start_index of [6, 8]) {
+    cases.push([
+      "Subject",
+      "AtIndex",
+      { msg: apis.padString(notAtStart, 64), start_index },
+      { out: 1n, spans: [[16, 18]] },
+    ]);
+  }
+  for (const start_index of [0, 7, 9, 64]) {
+    cases.push([
+      "Subject",
+      "AtIndex",
+      { msg: apis.padString(notAtStart, 64), start_index },
+      { out: 0n, spans: [] },
+    ]);
+  }
+  cases.push([
+    "Subject",
+    "AtIndex",
+    { msg: apis.padString("subject:hi\r\n", 64), start_index: 65 },
+    null,
+  ]);
+  for (const [name, spans] of [
+    ["Greedy", [[1, 4]]],
+    ["LeftmostLongest", [[1, 4]]],
+    ["LeftmostShortest", [[1, 2]]],
+  ]) {
+    cases.push([`AB${name}`, "Anchored", "abbbab", { out: 1n, spans }]);
+  }
+
+  // Occurrences: the spans revealed by every occurrence, or null if another occurrence ends after the first one.
+  for (const [generator] of generators) {
+    for (const [input, anySpans, unique] of [
+      ["subject:ab\r\nx", [[8, 10]], true],
+      ["subject:ab\r\n\r\nsubject:cd\r\nx", [[8, 10], [22, 24]], false],
+      ["x\r\nsubject:ab\r\n\r\nsubject:c\r\n", [[11, 13], [25, 26]], false],
+      // The DFA misses the second occurrence, whose line break ends the first one.
+      ["subject:ab\r\nsubject:cd\r\n", [[8, 10]], false],
+    ]) {
+      const first = { out: 1n, spans: [anySpans[0]] };
+      cases.push(
+        ["LowerSubject", generator, input, { out: 1n, spans: anySpans }],
+        ["LowerSubject", `${generator}First`, input, first],
+        ["LowerSubject", `${generator}Unique`, input, unique ? first : null]
+      );
+    }
+  }
+  // The DFA misses the occurrence starting inside a failed run, so the first occurrence it reveals would be a later
+  // one.
+  cases.push(
+    ["Abac", "DefaultFirst", "ababac;abac", null],
+    ["Abac", "DefaultFirst", "abac;ababac", { out: 1n, spans: [[0, 4]] }]
+  );
+
+  // Packed reveals, which pack only the first substring.
+  for (const searchMode of searchModes) {
+    cases.push(
+      [
+        "PackedSubject",
+        searchMode,
+        "subject:ab\r\n\r\nsubject:cd\r\nx",
+        {
+          out: 1n,
+          reveal0_found: 1n,
+          reveal0_start: 8n,
+          reveal0_len: 2n,
+          reveal0_packed: [97n, 98n, 0n, 0n],
+        },
+      ],
+      [
+        "PackedSubject",
+        searchMode,
+        "x",
+        {
+          out: 0n,
+          reveal0_found: 0n,
+          reveal0_start: 0n,
+          reveal0_len: 0n,
+          reveal0_packed: [0n, 0n, 0n, 0n],
+        },
+      ],
+      ["PackedSubject", searchMode, "subject:abcde\r\n", null]
+    );
+  }
+  // The substrings of adjacent runs are not packed together.
+  cases.push(
+    [
+      "PackedX",
+      "Default",
+      "axxa",
+      {
+        spans: [[1, 3]],
+        reveal0_found: 1n,
+        reveal0_start: 1n,
+        reveal0_len: 1n,
+      },
+    ],
+    ["PackedX", "Default", "ab", { reveal0_found: 0n, reveal0_len: 0n }]
+  );
+  cases.push([
+    "PackedSubject",
+    "PackedFields",
+    {
+      msg_packed: apis.packBytes(
+        Uint8Array.from(apis.padString("subject:ab\r\nx", 64))
+      ),
+    },
+    {
+      out: 1n,
+      reveal0_fields: [BigInt(apis.packBytes(encoder.encode("ab"))[0])],
+    },
+  ]);
+
+  // Committed reveals.
+  for (const searchMode of searchModes) {
+    cases.push([
+      "CommittedSubject",
+      searchMode,
+      { msg: apis.padString("subject:ab\r\nx", 64), reveal0_salt: "7" },
+      {
+        out: 1n,
+        reveal0_commitment: BigInt(
+          apis.commitBytes(encoder.encode("ab"), 4, "7")
+        ),
+        hidden: ["reveal0"],
+      },
+    ]);
+  }
+
+  // Uint reveals, whose value is computed by parseUint, or rejected if it is not a number.
+  for (const decimals of [0, 2]) {
+    for (const searchMode of searchModes) {
+      for (const value of [
+        "1694",
+        "007",
+        "12.5",
+        "12",
+        ".05",
+        "3.25",
+        "1.234",
+        "1.2.3",
+        "12a",
+        "a",
+      ]) {
+        let expected;
+        try {
+          expected = {
+            out: 1n,
+            reveal0_value: BigInt(
+              apis.parseUint(encoder.encode(value), decimals)
+            ),
+          };
+        } catch (e) {
+          expected = null;
+        }
+        cases.push([
+          `Amount${decimals}`,
+          searchMode,
+          `amount:${value}\r\n`,
+          expected,
+        ]);
+      }
+    }
+  }
+
+  // Equal reveals, of the parts of a regex and of two regexes of a combined template.
+  for (const searchMode of searchModes) {
+    cases.push([
+      "FromId",
+      searchMode,
+      "from:al@gm.com;id:x@gm.com",
+      { out: 1n, reveal0_len: 2n, reveal1_len: 2n },
+    ]);
+    for (const input of [
+      "from:al@gm.com;id:x@ev.com",
+      "from:al@gm.com;id:x@gmx.com",
+      "from:al@gmx.com;id:x@gm.com",
+    ]) {
+      cases.push(["FromId", searchMode, input, null]);
+    }
+  }
+  cases.push(
+    [
+      "EqualFromId",
+      "Default",
+      "from:al@gm.com;id:x@gm.com",
+      { out: 1n, equal_from_reveal0_len: 2n },
+    ],
+    ["EqualFromId", "Default", "from:al@gm.com;id:x@ev.com", null]
+  );
+
+  // The circuits are compiled once per regex and options.
+  const circuits = new Map();
+  const compile = (regexName, optionsName) => {
+    const templateName = `${regexName}${optionsName}Regex`;
+    if (!circuits.has(templateName)) {
+      const regex = regexes[regexName];
+      const circomOptions = options[optionsName];
+      circuits.set(
+        templateName,
+        Array.isArray(regex)
+          ? compileCombined(templateName, regex, "all", circomOptions)
+          : compileDecomposed(templateName, regex, circomOptions, {
+              publicInputs:
+                circomOptions.searchMode === "at-index" ? ["start_index"] : [],
+            })
+      );
+    }
+    return circuits.get(templateName);
+  };
+
+  it.each(cases)(
+    "%s regex with the %s options on %j",
+    async (regexName, optionsName, input, expected) => {
+      const circuit = await compile(regexName, optionsName);
+      const inputs =
+        typeof input === "string" ? { msg: apis.padString(input, 64) } : input;
+      if (expected === null) {
+        await expect(calculateSignals(circuit, inputs)).rejects.toThrow();
+        return;
+      }
+      const signal = await calculateSignals(circuit, inputs);
+      for (const [name, value] of Object.entries(expected)) {
+        if (name === "spans") {
+          expect(revealedSpans(signal, 0, 64)).toEqual(value);
+        } else if (name === "hidden") {
+          for (const output of value) {
+            expect(`main.${output}[0]` in circuit.symbols).toBe(false);
+          }
+        } else if (Array.isArray(value)) {
+          expect(value.map((_, j) => signal(`${name}[${j}]`))).toEqual(value);
+        } else {
+          expect(signal(name)).toEqual(value);
+        }
+      }
+    }
+  );
+
+  it.each(policies)(
+    "extraction with the %s policy returns the first revealed match",
+    (match_policy, name, spans) => {
+      expect(
+        apis.extractSubstrIdxes(policyInput, regexes[`X${name}`], false)[0]
+      ).toEqual(spans[0]);
+    }
+  );
+});
@@ -13,7 +13,7 @@ describe("Subject All Regex", () => {
   let circuit;
   beforeAll(async () => {
     const email_addr_json = readFileSync(
-      path.join(__dirname, "../circuits/common/subject_all.json"),
+      path.join(__dirname, "../../apis/src/decomposed_defs/subject_all.json"),
       "utf8"
     );
     const circom = compiler.genFromDecomposed(
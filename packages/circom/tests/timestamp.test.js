@@ -13,7 +13,7 @@ describe("Timestamp Regex", () => {
   let circuit;
   beforeAll(async () => {
     const email_addr_json = readFileSync(
-      path.join(__dirname, "../circuits/common/timestamp.json"),
+      path.join(__dirname, "../../apis/src/decomposed_defs/timestamp.json"),
       "utf8"
     );
     const circom = compiler.genFromDecomposed(
@@ -1,8 +1,11 @@
 //! ZK Regex Compiler CLI
 //!
 //! This binary provides a command-line interface for the ZK Regex Compiler.
-//! It supports two main commands: `Decomposed` for working with decomposed regex files,
-//! and `Raw` for working with raw regex strings.
+//! It supports three main commands: `Decomposed` for working with decomposed regex files,
+//! `Raw` for working with raw regex strings, and `Registry` for working with named patterns.
+//! The `Combine` command compiles several regexes into one Circom template.
+//! The `Halo2Multi` command combines the Halo2 tables of several regexes into one.
+//! The `Match` command runs an input through the circuit of a decomposed regex file.
 //!
 //! # Usage
 //!
@@ -15,14 +18,14 @@
 //!
 //! Options:
 //! - `-d, --decomposed-regex-path <PATH>`: Path to the decomposed regex JSON file (required)
-//! - `-h, --halo2-dir-path <PATH>`: Directory path for Halo2 output
+//! - `-H, --halo2-dir-path <PATH>`: Directory path for Halo2 output
 //! - `-c, --circom-file-path <PATH>`: File path for Circom output
 //! - `-t, --template-name <NAME>`: Template name
 //! - `-g, --gen-substrs`: Generate substrings
 //!
 //! Example:
 //! ```
-//! zk-regex decomposed -d regex.json -h ./halo2_output -c ./circom_output.circom -t MyTemplate -g true
+//! zk-regex decomposed -d regex.json -H ./halo2_output -c ./circom_output.circom -t MyTemplate -g true
 //! ```
 //!
 //! ## Raw Command
@@ -35,18 +38,159 @@
 //! Options:
 //! - `-r, --raw-regex <REGEX>`: Raw regex string (required)
 //! - `-s, --substrs-json-path <PATH>`: Path to substrings JSON file
-//! - `-h, --halo2-dir-path <PATH>`: Directory path for Halo2 output
+//! - `-H, --halo2-dir-path <PATH>`: Directory path for Halo2 output
 //! - `-c, --circom-file-path <PATH>`: File path for Circom output
 //! - `-t, --template-name <NAME>`: Template name
 //! - `-g, --gen-substrs`: Generate substrings
 //!
 //! Example:
 //! ```
-//! zk-regex raw -r "a*b+c?" -s substrings.json -h ./halo2_output -c ./circom_output.circom -t MyTemplate -g true
+//! zk-regex raw -r "a*b+c?" -s substrings.json -H ./halo2_output -c ./circom_output.circom -t MyTemplate -g true
+//! ```
+//!
+//! ## Registry Command
+//! Process a pattern of the pattern registry shared with `zk-regex-apis`, or list the patterns if no name is given:
+//!
+//! ```
+//! zk-regex registry [NAME] [OPTIONS]
+//! ```
+//!
+//! Options:
+//! - `-p, --pattern-dir <PATH>`: Directory of additional pattern JSON files
+//! - `-H, --halo2-dir-path <PATH>`: Directory path for Halo2 output
+//! - `-c, --circom-file-path <PATH>`: File path for Circom output
+//! - `-t, --template-name <NAME>`: Template name (defaults to the CamelCase pattern name followed by `Regex`)
+//! - `-g, --gen-substrs`: Generate substrings
+//!
+//! Example:
+//! ```
+//! zk-regex registry from_addr -c ./from_addr_regex.circom
+//! ```
+//!
+//! ## Halo2 Tables
+//! The `decomposed`, `raw` and `registry` commands write `allstr.txt` and `substr_{idx}.txt` into the Halo2
+//! directory, along with `table.json`, a versioned table with byte-range transitions, the accept states, the
+//! substring start and end states, and the public part names. Pass the `--halo2-binary` flag to also
+//! write the same table in the compact binary format to `table.bin`. Both are loaded with `Halo2Table::load`.
+//!
+//! ```
+//! zk-regex decomposed --halo2-binary -d regex.json -H ./halo2_output
+//! ```
+//!
+//! ## Stats Command
+//! Print the number of components per input byte of the Circom circuits of regexes, with the default options,
+//! the alphabet classes, the one-hot sums, both, and the index state encoding, where each regex is a registry
+//! pattern name or a path to a decomposed regex JSON file. The constraints per byte are estimated from the
+//! components, not counted by `circom --r1cs`:
+//!
+//! ```
+//! zk-regex stats <PATTERNS>... [OPTIONS]
+//! ```
+//!
+//! Options:
+//! - `-p, --pattern-dir <PATH>`: Directory of additional pattern JSON files
+//!
+//! Pass the `--alphabet-classes` and `--one-hot` flags to the `decomposed`, `raw` and `registry` commands
+//! to generate the circuit with the alphabet classes and the one-hot sums, respectively. Pass
+//! `--state-encoding index` to encode the active state of each position as a single state index signal instead of
+//! one signal per state, which also adapts the reveal logic to the index.
+//! Pass `--negate` to generate a template whose `out` is `1` if and only if no substring of the input matches the
+//! regex, without reveal outputs.
+//! Pass `--search-mode anchored` to generate a template that only matches at the beginning of the input, or
+//! `--search-mode at-index` to generate a template with a `start_index` input where the match must start.
+//! Pass `--occurrence first` to reveal only the first occurrence of the regex, or `--occurrence unique` to also
+//! make the template unsatisfiable if another occurrence ends after it, including one the DFA would miss.
+//! Pass `--packed-msg` to take the message as `msg_packed`, field elements of 31 little-endian bytes each, and
+//! `--packed-reveals` to also output the packed bytes of each public part with a `max_len` as `reveal{idx}_fields`.
+//!
+//! ## Shared Command
+//! Generate one Circom file with a template that range-checks the message and computes every byte predicate of
+//! several regexes once per position, and a template per regex that takes its `in` and `pred` outputs instead of
+//! `msg`, where each regex is a registry pattern name or a path to a decomposed regex JSON file:
+//!
+//! ```
+//! zk-regex shared <PATTERNS>... --circom-file-path <PATH> [OPTIONS]
+//! ```
+//!
+//! Options:
+//! - `-c, --circom-file-path <PATH>`: File path for Circom output (required)
+//! - `-t, --template-name <NAME>`: Name of the shared predicate template, defaults to `SharedPredicates`
+//! - `-g, --gen-substrs <BOOL>`: Generate substrings (default: true)
+//! - `-p, --pattern-dir <PATH>`: Directory of additional pattern JSON files
+//!
+//! Example:
+//! ```
+//! zk-regex shared from_addr to_addr subject_all -c ./header_regexes.circom -t HeaderPredicates
+//! ```
+//!
+//! ## Combine Command
+//! Generate one Circom template that matches several regexes with the circuit of their product automaton, where each
+//! regex is a registry pattern name or a path to a decomposed regex JSON file. The template outputs `{name}_out` and
+//! the `{name}_reveal{idx}` signals of each regex, and `out`, which is `1` if all (or, with `--any`, any) of the
+//! regexes match:
+//!
+//! ```
+//! zk-regex combine <PATTERNS>... --circom-file-path <PATH> --template-name <NAME> [OPTIONS]
+//! ```
+//!
+//! Options:
+//! - `-c, --circom-file-path <PATH>`: File path for Circom output (required)
+//! - `-t, --template-name <NAME>`: Template name (required)
+//! - `-g, --gen-substrs <BOOL>`: Generate substrings (default: true)
+//! - `-p, --pattern-dir <PATH>`: Directory of additional pattern JSON files
+//! - `--any`: Set `out` if any regex matches instead of all of them
+//!
+//! Example:
+//! ```
+//! zk-regex combine from_addr subject_all -c ./header_regex.circom -t HeaderRegex
+//! ```
+//!
+//! ## Halo2Multi Command
+//! Write the combined Halo2 lookup table of several regexes to `multi_table.json`, keyed by
+//! `(regex_id, state, byte)` with globally unique state ids, where each regex is a registry pattern name or a path
+//! to a decomposed regex JSON file:
+//!
+//! ```
+//! zk-regex halo2-multi <PATTERNS>... --halo2-dir-path <PATH> [OPTIONS]
+//! ```
+//!
+//! Options:
+//! - `-H, --halo2-dir-path <PATH>`: Directory path for Halo2 output (required)
+//! - `-p, --pattern-dir <PATH>`: Directory of additional pattern JSON files
+//!
+//! Example:
+//! ```
+//! zk-regex halo2-multi from_addr to_addr subject_all body_hash timestamp -H ./halo2_output
+//! ```
+//!
+//! ## Match Command
+//! Print the circuit signals for an input, i.e., the active DFA states, `from_zero_enabled`, `state_changed`
+//! and `is_consecutive` of each byte, `out`, whether the circuit with the one-hot sums computes the same signals,
+//! the revealed substrings, and the outputs of the public parts with a `max_len` and whether their assertions hold:
+//!
+//! ```
+//! zk-regex match --decomposed-regex-path <PATH> --input <PATH> [OPTIONS]
+//! ```
+//!
+//! Options:
+//! - `-d, --decomposed-regex-path <PATH>`: Path to the decomposed regex JSON file (required)
+//! - `-i, --input <PATH>`: Path to the input file (required)
+//! - `-m, --max-bytes <NUM>`: The `msg_bytes` of the circuit, defaults to the input length
+//!
+//! Example:
+//! ```
+//! zk-regex match -d regex.json -i email.txt -m 1024
 //! ```
 
-use clap::{Parser, Subcommand};
-use zk_regex_compiler::{gen_from_decomposed, gen_from_raw};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::fs::{self, File};
+use zk_regex_compiler::{
+    circom_stats, gen_combined_circom, gen_from_decomposed_with_options, gen_from_raw_with_options,
+    gen_from_registry_with_options, gen_halo2_multi_table, gen_shared_circom,
+    load_pattern_registry, trace_from_decomposed, write_halo2_binary, write_halo2_multi_binary,
+    CircomOptions, CircomStats, CombineMode, CompilerError, DecomposedRegexConfig, Occurrence,
+    SearchMode, StateEncoding,
+};
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -55,12 +199,104 @@ struct Cli {
     pub command: Commands,
 }
 
+/// The options of the Circom code generation.
+#[derive(Args, Debug, Clone)]
+struct CircomArgs {
+    /// Classify each input byte once into its byte equivalence class in the generated Circom circuit
+    #[arg(long)]
+    pub alphabet_classes: bool,
+    /// Replace the ORs of mutually exclusive conditions with sums and constrain the states to be one-hot in the
+    /// generated Circom circuit
+    #[arg(long)]
+    pub one_hot: bool,
+    /// The encoding of the DFA states in the generated Circom circuit
+    #[arg(long, value_enum, default_value_t = StateEncodingArg::Vector)]
+    pub state_encoding: StateEncodingArg,
+    /// Generate a Circom template whose `out` is 1 if and only if no substring of the input matches the regex
+    #[arg(
+        long,
+        conflicts_with_all = ["alphabet_classes", "one_hot", "state_encoding", "search_mode", "occurrence", "packed_reveals"]
+    )]
+    pub negate: bool,
+    /// Where a match of the regex may start in the input of the generated Circom circuit
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = SearchModeArg::Anywhere,
+        conflicts_with_all = ["alphabet_classes", "one_hot", "state_encoding", "occurrence"]
+    )]
+    pub search_mode: SearchModeArg,
+    /// Which occurrences of the regex are accepted and revealed by the generated Circom circuit
+    #[arg(long, value_enum, default_value_t = OccurrenceArg::Any)]
+    pub occurrence: OccurrenceArg,
+    /// Take the message of the generated Circom circuit as field elements of 31 bytes each
+    #[arg(long)]
+    pub packed_msg: bool,
+    /// Also output the packed bytes of each public part with a `max_len` as field elements of 31 bytes each
+    #[arg(long)]
+    pub packed_reveals: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum StateEncodingArg {
+    /// One signal per state and position
+    Vector,
+    /// One state index signal per position
+    Index,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SearchModeArg {
+    /// At any position
+    Anywhere,
+    /// At the beginning of the input
+    Anchored,
+    /// At the index given by the `start_index` input signal
+    AtIndex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OccurrenceArg {
+    /// Every occurrence
+    Any,
+    /// Only the first occurrence is revealed
+    First,
+    /// Only the first occurrence is revealed, and no other occurrence is allowed
+    Unique,
+}
+
+impl CircomArgs {
+    fn circom_options(&self) -> CircomOptions {
+        CircomOptions {
+            alphabet_classes: self.alphabet_classes,
+            one_hot: self.one_hot,
+            state_encoding: match self.state_encoding {
+                StateEncodingArg::Vector => StateEncoding::Vector,
+                StateEncodingArg::Index => StateEncoding::Index,
+            },
+            negate: self.negate,
+            search_mode: match self.search_mode {
+                SearchModeArg::Anywhere => SearchMode::Anywhere,
+                SearchModeArg::Anchored => SearchMode::Anchored,
+                SearchModeArg::AtIndex => SearchMode::AtIndex,
+            },
+            occurrence: match self.occurrence {
+                OccurrenceArg::Any => Occurrence::Any,
+                OccurrenceArg::First => Occurrence::First,
+                OccurrenceArg::Unique => Occurrence::Unique,
+            },
+            packed_msg: self.packed_msg,
+            packed_reveals: self.packed_reveals,
+        }
+    }
+}
+
 #[derive(Debug, Subcommand, Clone)]
 enum Commands {
     Decomposed {
         #[arg(short, long)]
         decomposed_regex_path: String,
-        #[arg(short, long)]
+        #[arg(short = 'H', long)]
         halo2_dir_path: Option<String>,
         #[arg(short, long)]
         circom_file_path: Option<String>,
@@ -68,13 +304,92 @@ enum Commands {
         template_name: Option<String>,
         #[arg(short, long)]
         gen_substrs: Option<bool>,
+        /// Also write the Halo2 table in the compact binary format
+        #[arg(long, requires = "halo2_dir_path")]
+        halo2_binary: bool,
+        #[command(flatten)]
+        circom: CircomArgs,
     },
     Raw {
         #[arg(short, long)]
         raw_regex: String,
         #[arg(short, long)]
         substrs_json_path: Option<String>,
+        #[arg(short = 'H', long)]
+        halo2_dir_path: Option<String>,
+        #[arg(short, long)]
+        circom_file_path: Option<String>,
+        #[arg(short, long)]
+        template_name: Option<String>,
+        #[arg(short, long)]
+        gen_substrs: Option<bool>,
+        /// Also write the Halo2 table in the compact binary format
+        #[arg(long, requires = "halo2_dir_path")]
+        halo2_binary: bool,
+        #[command(flatten)]
+        circom: CircomArgs,
+    },
+    Stats {
+        #[arg(required = true)]
+        patterns: Vec<String>,
+        #[arg(short, long)]
+        pattern_dir: Option<String>,
+    },
+    Shared {
+        #[arg(required = true)]
+        patterns: Vec<String>,
         #[arg(short, long)]
+        pattern_dir: Option<String>,
+        #[arg(short, long)]
+        circom_file_path: String,
+        #[arg(short, long)]
+        template_name: Option<String>,
+        #[arg(short, long)]
+        gen_substrs: Option<bool>,
+        #[command(flatten)]
+        circom: CircomArgs,
+    },
+    Combine {
+        #[arg(required = true)]
+        patterns: Vec<String>,
+        #[arg(short, long)]
+        pattern_dir: Option<String>,
+        #[arg(short, long)]
+        circom_file_path: String,
+        #[arg(short, long)]
+        template_name: String,
+        #[arg(short, long)]
+        gen_substrs: Option<bool>,
+        /// Set `out` if any regex matches instead of all of them
+        #[arg(long)]
+        any: bool,
+        #[command(flatten)]
+        circom: CircomArgs,
+    },
+    Halo2Multi {
+        #[arg(required = true)]
+        patterns: Vec<String>,
+        #[arg(short = 'H', long)]
+        halo2_dir_path: String,
+        #[arg(short, long)]
+        pattern_dir: Option<String>,
+        /// Also write the Halo2 table in the compact binary format
+        #[arg(long)]
+        halo2_binary: bool,
+    },
+    Match {
+        #[arg(short, long)]
+        decomposed_regex_path: String,
+        #[arg(short, long)]
+        input: String,
+        #[arg(short, long)]
+        max_bytes: Option<usize>,
+    },
+    Registry {
+        name: Option<String>,
+        #[arg(short, long)]
+        pattern_dir: Option<String>,
+        #[arg(short = 'H', long)]
         halo2_dir_path: Option<String>,
         #[arg(short, long)]
         circom_file_path: Option<String>,
@@ -82,6 +397,11 @@ enum Commands {
         template_name: Option<String>,
         #[arg(short, long)]
         gen_substrs: Option<bool>,
+        /// Also write the Halo2 table in the compact binary format
+        #[arg(long, requires = "halo2_dir_path")]
+        halo2_binary: bool,
+        #[command(flatten)]
+        circom: CircomArgs,
     },
 }
 
@@ -90,6 +410,12 @@ fn main() {
     match cli.command {
         Commands::Decomposed { .. } => process_decomposed(cli),
         Commands::Raw { .. } => process_raw(cli),
+        Commands::Stats { .. } => process_stats(cli),
+        Commands::Shared { .. } => process_shared(cli),
+        Commands::Combine { .. } => process_combine(cli),
+        Commands::Halo2Multi { .. } => process_halo2_multi(cli),
+        Commands::Match { .. } => process_match(cli),
+        Commands::Registry { .. } => process_registry(cli),
     }
 }
 
@@ -100,15 +426,22 @@ fn process_decomposed(cli: Cli) {
         circom_file_path,
         template_name,
         gen_substrs,
+        halo2_binary,
+        circom,
     } = cli.command
     {
-        if let Err(e) = gen_from_decomposed(
+        if let Err(e) = gen_from_decomposed_with_options(
             &decomposed_regex_path,
             halo2_dir_path.as_deref(),
             circom_file_path.as_deref(),
             template_name.as_deref(),
             gen_substrs,
-        ) {
+            &circom.circom_options(),
+        )
+        .and_then(|_| match halo2_dir_path.as_deref() {
+            Some(halo2_dir_path) if halo2_binary => write_halo2_binary(halo2_dir_path),
+            _ => Ok(()),
+        }) {
             eprintln!("Error: {}", e);
             std::process::exit(1);
         }
@@ -123,18 +456,266 @@ fn process_raw(cli: Cli) {
         circom_file_path,
         template_name,
         gen_substrs,
+        halo2_binary,
+        circom,
     } = cli.command
     {
-        if let Err(e) = gen_from_raw(
+        if let Err(e) = gen_from_raw_with_options(
             &raw_regex,
             substrs_json_path.as_deref(),
             halo2_dir_path.as_deref(),
             circom_file_path.as_deref(),
             template_name.as_deref(),
             gen_substrs,
+            &circom.circom_options(),
+        )
+        .and_then(|_| match halo2_dir_path.as_deref() {
+            Some(halo2_dir_path) if halo2_binary => write_halo2_binary(halo2_dir_path),
+            _ => Ok(()),
+        }) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn process_registry(cli: Cli) {
+    if let Commands::Registry {
+        name,
+        pattern_dir,
+        halo2_dir_path,
+        circom_file_path,
+        template_name,
+        gen_substrs,
+        halo2_binary,
+        circom,
+    } = cli.command
+    {
+        let result = match name {
+            Some(name) => gen_from_registry_with_options(
+                &name,
+                pattern_dir.as_deref(),
+                halo2_dir_path.as_deref(),
+                circom_file_path.as_deref(),
+                template_name.as_deref(),
+                gen_substrs,
+                &circom.circom_options(),
+            )
+            .and_then(|_| match halo2_dir_path.as_deref() {
+                Some(halo2_dir_path) if halo2_binary => write_halo2_binary(halo2_dir_path),
+                _ => Ok(()),
+            }),
+            None => load_pattern_registry(pattern_dir.as_deref()).map(|registry| {
+                for pattern in registry.patterns() {
+                    println!(
+                        "{}: {} (public parts: {})",
+                        pattern.name,
+                        pattern.def.description,
+                        pattern.def.public_parts.join(", ")
+                    );
+                }
+            }),
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// A row of the stats table: its label and the count it reads from the CircomStats.
+type StatsRow = (&'static str, fn(&CircomStats) -> usize);
+
+fn process_stats(cli: Cli) {
+    if let Commands::Stats {
+        patterns,
+        pattern_dir,
+    } = cli.command
+    {
+        let columns = [
+            ("default", CircomOptions::default()),
+            (
+                "alphabet_classes",
+                CircomOptions {
+                    alphabet_classes: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                "one_hot",
+                CircomOptions {
+                    one_hot: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                "both",
+                CircomOptions {
+                    alphabet_classes: true,
+                    one_hot: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                "index",
+                CircomOptions {
+                    state_encoding: StateEncoding::Index,
+                    ..Default::default()
+                },
+            ),
+        ];
+        let result = patterns.iter().try_for_each(|pattern| {
+            let stats = columns
+                .iter()
+                .map(|(_, options)| circom_stats(pattern, pattern_dir.as_deref(), Some(*options)))
+                .collect::<Result<Vec<_>, _>>()?;
+            println!("{}", pattern);
+            print!("{:>28}", "");
+            for (label, _) in &columns {
+                print!("  {:>16}", label);
+            }
+            println!();
+            let rows: [StatsRow; 14] = [
+                ("eq", |stats| stats.eq),
+                ("lt", |stats| stats.lt),
+                ("and", |stats| stats.and),
+                ("multi_or", |stats| stats.multi_or),
+                ("inline_multi_or", |stats| stats.inline_multi_or),
+                ("sums", |stats| stats.sums),
+                ("one_hot_checks", |stats| stats.one_hot_checks),
+                ("byte_classes", |stats| stats.byte_classes),
+                ("class_comparators", |stats| stats.class_comparators),
+                ("class_eqs", |stats| stats.class_eqs),
+                ("state_indicators", |stats| stats.state_indicators),
+                ("class_products", |stats| stats.class_products),
+                ("state_signals", |stats| stats.state_signals),
+                ("estimated constraints/byte", |stats| {
+                    stats.estimated_constraints_per_byte()
+                }),
+            ];
+            for (label, value) in rows {
+                print!("{:>28}", label);
+                for stats in &stats {
+                    print!("  {:>16}", value(stats));
+                }
+                println!();
+            }
+            println!("(the constraints are estimated from the components, not counted by circom)");
+            Ok::<_, CompilerError>(())
+        });
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn process_shared(cli: Cli) {
+    if let Commands::Shared {
+        patterns,
+        pattern_dir,
+        circom_file_path,
+        template_name,
+        gen_substrs,
+        circom,
+    } = cli.command
+    {
+        if let Err(e) = gen_shared_circom(
+            &patterns,
+            pattern_dir.as_deref(),
+            &circom_file_path,
+            template_name.as_deref().unwrap_or("SharedPredicates"),
+            gen_substrs,
+            Some(circom.circom_options()),
         ) {
             eprintln!("Error: {}", e);
             std::process::exit(1);
         }
     }
 }
+
+fn process_combine(cli: Cli) {
+    if let Commands::Combine {
+        patterns,
+        pattern_dir,
+        circom_file_path,
+        template_name,
+        gen_substrs,
+        any,
+        circom,
+    } = cli.command
+    {
+        if let Err(e) = gen_combined_circom(
+            &patterns,
+            pattern_dir.as_deref(),
+            &circom_file_path,
+            &template_name,
+            if any {
+                CombineMode::Any
+            } else {
+                CombineMode::All
+            },
+            gen_substrs,
+            Some(circom.circom_options()),
+        ) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn process_halo2_multi(cli: Cli) {
+    if let Commands::Halo2Multi {
+        patterns,
+        halo2_dir_path,
+        pattern_dir,
+        halo2_binary,
+    } = cli.command
+    {
+        let result = gen_halo2_multi_table(&patterns, pattern_dir.as_deref(), &halo2_dir_path)
+            .and_then(|_| {
+                if halo2_binary {
+                    write_halo2_multi_binary(&halo2_dir_path)?;
+                }
+                Ok(())
+            });
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn process_match(cli: Cli) {
+    if let Commands::Match {
+        decomposed_regex_path,
+        input,
+        max_bytes,
+    } = cli.command
+    {
+        let result = (|| {
+            let mut decomposed_regex_config: DecomposedRegexConfig =
+                serde_json::from_reader(File::open(&decomposed_regex_path)?)?;
+            let input = fs::read(&input)?;
+            trace_from_decomposed(&mut decomposed_regex_config, &input, max_bytes)
+        })();
+        match result {
+            Ok(trace) => print!("{}", trace),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+}
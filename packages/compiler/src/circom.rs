@@ -1,203 +1,776 @@
 use crate::{
     errors::CompilerError,
-    regex::get_accepted_state,
-    structs::{DFAGraph, RegexAndDFA},
+    ir::{AnchoredRun, Backend, ByteClasses, BytePredicate, RegexCircuit},
+    negate::{NegatedDfa, SearchDfa},
+    product::{CombineMode, CrossEquality, ProductAutomaton},
+    structs::RegexAndDFA,
 };
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, BTreeSet},
     fs::File,
     io::Write,
     path::Path,
 };
+use zk_regex_apis::{extract_substrs::MatchPolicy, packing::MAX_BYTES_IN_FIELD};
+
+/// The estimated number of non-linear constraints of an `IsEqual` component.
+const IS_EQUAL_CONSTRAINTS: usize = 2;
+/// The estimated number of non-linear constraints of a `LessEqThan(8)` component.
+const LESS_EQ_THAN_CONSTRAINTS: usize = 9;
+/// The estimated number of non-linear constraints of an `AND` component.
+const AND_CONSTRAINTS: usize = 1;
+/// The estimated number of non-linear constraints of a `MultiOR` component, whose sums are linear.
+const MULTI_OR_CONSTRAINTS: usize = 2;
+/// The estimated number of non-linear constraints of a `OneHotSum` component.
+const ONE_HOT_CONSTRAINTS: usize = 1;
+
+/// The encoding of the DFA states in a generated Circom circuit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StateEncoding {
+    /// `states[i][j]` is `1` if the state `j` is active after reading `in[i-1]`, so that the number of state
+    /// signals is `O(msg_bytes * states)`.
+    #[default]
+    Vector,
+    /// `state[i]` is the index of the active non-zero state, or `0`, and the next state is selected by a
+    /// polynomial in the byte class and the indicators of the states with outgoing transitions.
+    Index,
+}
+
+/// Where a match of the regex may start in the input of a generated Circom circuit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SearchMode {
+    /// A match may start at any position, so the DFA restarts from the state `0` at every position.
+    #[default]
+    Anywhere,
+    /// A match must start at the beginning of `msg`.
+    Anchored,
+    /// A match must start at the index of `msg` given by the `start_index` input signal, which the caller
+    /// declares public or private in its `component main`.
+    AtIndex,
+}
+
+/// Which occurrences of the regex in the input of a generated Circom circuit are accepted and revealed, where an
+/// occurrence is a substring of the input matched by the regex. The options other than [`Occurrence::Any`] find
+/// the occurrences with the [`SearchDfa`] of the regex, which the DFA of the circuit may miss since it restarts
+/// from the state `0` only when a run fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Occurrence {
+    /// Every run of the DFA from the state `0` that reaches the accept state is accepted and revealed.
+    #[default]
+    Any,
+    /// Only the first occurrence is revealed, as with a leftmost match policy, and the template is unsatisfiable
+    /// if the first run of the DFA that reaches the accept state is not the occurrence that ends first.
+    First,
+    /// As [`Occurrence::First`], and the template is also unsatisfiable if another occurrence ends after the
+    /// first one, including one that overlaps it.
+    Unique,
+}
+
+/// The options of the Circom code generation. The default options generate the original circuit.
+///
+/// They are deserialized with the field names in snake or camel case, and the values of the enums in kebab case
+/// as in the CLI flags, e.g., `{"searchMode": "at-index"}`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CircomOptions {
+    /// Classify each input byte once per position into its byte equivalence class, with one comparator per
+    /// class boundary, and express the transitions over the classes.
+    #[serde(alias = "alphabetClasses")]
+    pub alphabet_classes: bool,
+    /// Use the invariant that at most one non-zero state is active per position to replace the MultiOR gates
+    /// over mutually exclusive conditions with sums, and constrain the non-zero states of each position to
+    /// sum to `0` or `1`.
+    #[serde(alias = "oneHot")]
+    pub one_hot: bool,
+    /// The encoding of the states. [`StateEncoding::Index`] always uses the byte classes and computes no ORs, so
+    /// `alphabet_classes` and `one_hot` have no effect with it.
+    #[serde(alias = "stateEncoding")]
+    pub state_encoding: StateEncoding,
+    /// Generate a template whose `out` is `1` if and only if no substring of `in` matches the regex, computed by
+    /// the complete [`NegatedDfa`] of the regex. It has no reveal outputs and cannot be combined with the other
+    /// options except `packed_msg`, and the statistics of [`circom_stats`](crate::circom_stats) ignore it.
+    pub negate: bool,
+    /// Where a match may start. The modes other than [`SearchMode::Anywhere`] run the DFA once from its initial
+    /// state without restarts, treat the start as the beginning of the input for `^`, and cannot be combined with
+    /// the other options. The statistics of [`circom_stats`](crate::circom_stats) ignore it.
+    #[serde(alias = "searchMode")]
+    pub search_mode: SearchMode,
+    /// Which occurrences are accepted and revealed. The options other than [`Occurrence::Any`] require the
+    /// substring constraints and the [`SearchMode::Anywhere`] search mode.
+    pub occurrence: Occurrence,
+    /// Take the message as the `msg_packed` input of field elements of 31 little-endian bytes each, which the
+    /// template unpacks into `msg` with a range check per byte.
+    #[serde(alias = "packedMsg")]
+    pub packed_msg: bool,
+    /// Also output `reveal{idx}_fields`, the `reveal{idx}_packed` bytes packed into field elements of 31
+    /// little-endian bytes each. It requires the substring constraints and a public part with a `max_len`.
+    #[serde(alias = "packedReveals")]
+    pub packed_reveals: bool,
+}
+
+/// The number of components used per input byte by the state transition logic of a generated circuit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CircomStats {
+    /// The number of `IsEqual` components.
+    pub eq: usize,
+    /// The number of `LessEqThan` components of the range checks.
+    pub lt: usize,
+    /// The number of `AND` components.
+    pub and: usize,
+    /// The number of `MultiOR` components.
+    pub multi_or: usize,
+    /// The number of byte classes used by the transitions.
+    pub byte_classes: usize,
+    /// The number of `LessEqThan` components comparing the byte with the class boundaries.
+    pub class_comparators: usize,
+    /// The number of `IsEqual` components checking the single-byte segments of the classes.
+    pub class_eqs: usize,
+    /// The number of anonymous `MultiOR` and `MultiNOR` components of the state updates.
+    pub inline_multi_or: usize,
+    /// The number of ORs of mutually exclusive conditions computed as sums.
+    pub sums: usize,
+    /// The number of one-hot constraints on the states.
+    pub one_hot_checks: usize,
+    /// The number of `IsEqual` components comparing the state index with a state.
+    pub state_indicators: usize,
+    /// The number of products of a byte class and the next state index it selects.
+    pub class_products: usize,
+    /// The number of signals per input byte that hold the active states.
+    pub state_signals: usize,
+}
+
+impl CircomStats {
+    /// Returns the estimated number of non-linear constraints per input byte of the state transition logic.
+    ///
+    /// The estimate weights the components with the constants above, which are not checked against the
+    /// constraints counted by `circom --r1cs`, so it only serves to compare the generator options.
+    pub fn estimated_constraints_per_byte(&self) -> usize {
+        (self.eq + self.class_eqs + self.state_indicators) * IS_EQUAL_CONSTRAINTS
+            + (self.lt + self.class_comparators) * LESS_EQ_THAN_CONSTRAINTS
+            + self.and * AND_CONSTRAINTS
+            + (self.multi_or + self.inline_multi_or) * MULTI_OR_CONSTRAINTS
+            + self.one_hot_checks * ONE_HOT_CONSTRAINTS
+            + self.class_products
+    }
+}
 
-/// Builds a reverse graph from a DFA graph and collects accept nodes.
+/// Adds the check of a byte predicate to the Circom circuit.
 ///
-/// This function creates a reverse graph where the direction of edges is inverted,
-/// and collects all accepting states.
+/// This function either reuses the component of an existing check of the predicate or creates a new one,
+/// adding the necessary Circom code lines and updating the relevant counters. A range predicate uses two
+/// LessEqThan components and an AND component, and an equality predicate uses an IsEqual component.
 ///
 /// # Arguments
 ///
-/// * `state_len` - The number of states in the DFA.
-/// * `dfa_graph` - A reference to the original DFA graph.
+/// * `lines` - A mutable reference to a Vec of Strings containing Circom code lines.
+/// * `predicate_outputs` - A mutable reference to the output components of the predicates checked so far.
+/// * `predicate_idx` - The index of the predicate in the circuit.
+/// * `predicate` - The byte predicate.
+/// * `eq_i` - A mutable reference to the current equality component index.
+/// * `lt_i` - A mutable reference to the current LessThan component index.
+/// * `and_i` - A mutable reference to the current AND component index.
 ///
 /// # Returns
 ///
-/// A Result tuple containing:
-/// * The reverse graph as a `BTreeMap<usize, BTreeMap<usize, Vec<u8>>>`.
-/// * A `BTreeSet<usize>` of accepting state IDs.
-///
-/// # Errors
-///
-/// Returns a `CompilerError::AcceptNodesError` if no accepting states are found.
-fn build_reverse_graph(
-    state_len: usize,
-    dfa_graph: &DFAGraph,
-) -> Result<(BTreeMap<usize, BTreeMap<usize, Vec<u8>>>, BTreeSet<usize>), CompilerError> {
-    let mut rev_graph = BTreeMap::<usize, BTreeMap<usize, Vec<u8>>>::new();
-    let mut accept_nodes = BTreeSet::<usize>::new();
+/// The type and index of the component that outputs the predicate.
+fn add_predicate_check(
+    lines: &mut Vec<String>,
+    predicate_outputs: &mut [Option<(&'static str, usize)>],
+    predicate_idx: usize,
+    predicate: BytePredicate,
+    eq_i: &mut usize,
+    lt_i: &mut usize,
+    and_i: &mut usize,
+) -> (&'static str, usize) {
+    if let Some(output) = predicate_outputs[predicate_idx] {
+        return output;
+    }
+
+    let output = match predicate {
+        BytePredicate::Range(min, max) => {
+            lines.push(format!("\t\tlt[{}][i] = LessEqThan(8);", *lt_i));
+            lines.push(format!("\t\tlt[{}][i].in[0] <== {};", *lt_i, min));
+            lines.push(format!("\t\tlt[{}][i].in[1] <== in[i];", *lt_i));
+            lines.push(format!("\t\tlt[{}][i] = LessEqThan(8);", *lt_i + 1));
+            lines.push(format!("\t\tlt[{}][i].in[0] <== in[i];", *lt_i + 1));
+            lines.push(format!("\t\tlt[{}][i].in[1] <== {};", *lt_i + 1, max));
+            lines.push(format!("\t\tand[{}][i] = AND();", *and_i));
+            lines.push(format!(
+                "\t\tand[{}][i].a <== lt[{}][i].out;",
+                *and_i, *lt_i
+            ));
+            lines.push(format!(
+                "\t\tand[{}][i].b <== lt[{}][i].out;",
+                *and_i,
+                *lt_i + 1
+            ));
+
+            let output = ("and", *and_i);
+            *lt_i += 2;
+            *and_i += 1;
+            output
+        }
+        BytePredicate::Eq(code) => {
+            lines.push(format!("\t\teq[{}][i] = IsEqual();", *eq_i));
+            lines.push(format!("\t\teq[{}][i].in[0] <== in[i];", *eq_i));
+            lines.push(format!("\t\teq[{}][i].in[1] <== {};", *eq_i, code));
+            let output = ("eq", *eq_i);
+            *eq_i += 1;
+            output
+        }
+    };
+    predicate_outputs[predicate_idx] = Some(output);
+    output
+}
+
+/// A term of the linear expression of a `byte_class` signal, which is `1` if the input byte is in a segment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ClassTerm {
+    /// The sum of the `byte_eq` signals of the bytes of the segment.
+    Eq(Vec<u8>),
+    /// The difference of the `byte_ge` signals of the bounds `min` and `max + 1` of the segment, where the
+    /// comparisons with `0` and `256` are the constants `1` and `0`.
+    Range(u16, u16),
+}
+
+/// The linear expression of a `byte_class` signal: the sum of its terms, or `1` minus the sum if `complement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ClassExpr {
+    complement: bool,
+    terms: Vec<ClassTerm>,
+}
+
+/// The checks of the input byte that classify it into the byte classes used by the transitions.
+struct ByteClassification {
+    /// The thresholds `t` of the `byte_ge` signals, i.e., `t <= in[i]`.
+    thresholds: Vec<u16>,
+    /// The bytes of the `byte_eq` signals.
+    eq_bytes: Vec<u8>,
+    /// The linear expression of each `byte_class` signal.
+    class_exprs: Vec<ClassExpr>,
+}
 
-    for i in 0..state_len {
-        rev_graph.insert(i, BTreeMap::new());
+impl ByteClassification {
+    /// Plans the checks of the classes computed directly. A segment is either the difference of the comparisons
+    /// with its bounds, which are shared with the adjacent segments, or the sum of an `IsEqual` per byte when
+    /// that is cheaper than the comparisons it alone needs.
+    fn checks(byte_classes: &ByteClasses, classes: &[usize]) -> (BTreeSet<u16>, BTreeSet<u8>) {
+        let segments = byte_classes
+            .segments
+            .iter()
+            .filter(|(min, _)| classes.contains(&byte_classes.class_of[*min as usize]))
+            .collect::<Vec<_>>();
+        let is_free = |threshold: &u16| *threshold == 0 || *threshold == 256;
+        let bounds = |&&(min, max): &&(u8, u8)| [min as u16, max as u16 + 1];
+
+        let mut use_eq = segments
+            .iter()
+            .map(|(min, max)| min == max)
+            .collect::<Vec<_>>();
+        let thresholds = loop {
+            let thresholds = segments
+                .iter()
+                .zip(&use_eq)
+                .filter(|(_, &use_eq)| !use_eq)
+                .flat_map(|(segment, _)| bounds(segment))
+                .filter(|threshold| !is_free(threshold))
+                .collect::<Vec<_>>();
+            let cheaper_with_eq = segments.iter().enumerate().find(|&(seg_i, segment)| {
+                let own_thresholds = bounds(segment)
+                    .iter()
+                    .filter(|threshold| {
+                        !is_free(threshold)
+                            && thresholds.iter().filter(|&t| t == *threshold).count() == 1
+                    })
+                    .count();
+                !use_eq[seg_i]
+                    && (segment.1 - segment.0 + 1) as usize * IS_EQUAL_CONSTRAINTS
+                        < own_thresholds * LESS_EQ_THAN_CONSTRAINTS
+            });
+            match cheaper_with_eq {
+                Some((seg_i, _)) => use_eq[seg_i] = true,
+                None => break thresholds.into_iter().collect::<BTreeSet<_>>(),
+            }
+        };
+
+        let mut eq_bytes = segments
+            .iter()
+            .zip(&use_eq)
+            .filter(|(_, &use_eq)| use_eq)
+            .flat_map(|(&&(min, max), _)| min..=max)
+            .collect::<BTreeSet<_>>();
+        // A single byte between two comparisons needed anyway is their difference.
+        eq_bytes.retain(|&byte| {
+            !(byte_classes.segments.contains(&(byte, byte))
+                && [byte as u16, byte as u16 + 1]
+                    .iter()
+                    .all(|threshold| is_free(threshold) || thresholds.contains(threshold)))
+        });
+        (thresholds, eq_bytes)
     }
 
-    for (i, node) in dfa_graph.states.iter().enumerate() {
-        for (k, v) in &node.transitions {
-            let chars: Vec<u8> = v.iter().cloned().collect();
-            rev_graph.get_mut(k).unwrap().insert(i, chars);
+    /// Plans the classification of the used classes with the fewest estimated constraints: either every used
+    /// class is computed directly, or one of them is computed as `1` minus the other classes, including the
+    /// class of the bytes used by no transition.
+    fn new(byte_classes: &ByteClasses, used_classes: &[usize]) -> Self {
+        let unused_classes = (0..byte_classes.num_classes)
+            .filter(|class| !used_classes.contains(class))
+            .collect::<Vec<_>>();
+        let cost = |(thresholds, eq_bytes): &(BTreeSet<u16>, BTreeSet<u8>)| {
+            thresholds.len() * LESS_EQ_THAN_CONSTRAINTS + eq_bytes.len() * IS_EQUAL_CONSTRAINTS
+        };
+        let (complement, (thresholds, eq_bytes)) = std::iter::once(None)
+            .chain(used_classes.iter().copied().map(Some))
+            .map(|complement| {
+                let classes = match complement {
+                    None => used_classes.to_vec(),
+                    Some(complement) => used_classes
+                        .iter()
+                        .chain(unused_classes.iter())
+                        .copied()
+                        .filter(|&class| class != complement)
+                        .collect(),
+                };
+                (complement, Self::checks(byte_classes, &classes))
+            })
+            .min_by_key(|(_, checks)| cost(checks))
+            .expect("the direct classification is always planned");
+
+        let class_terms = |class: usize| {
+            byte_classes
+                .segments
+                .iter()
+                .filter(|(min, _)| byte_classes.class_of[*min as usize] == class)
+                .map(|&(min, max)| {
+                    if eq_bytes.contains(&min) {
+                        ClassTerm::Eq((min..=max).collect())
+                    } else {
+                        ClassTerm::Range(min as u16, max as u16 + 1)
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let class_exprs = used_classes
+            .iter()
+            .map(|&class| {
+                if Some(class) == complement {
+                    ClassExpr {
+                        complement: true,
+                        terms: (0..byte_classes.num_classes)
+                            .filter(|&other| other != class)
+                            .flat_map(class_terms)
+                            .collect(),
+                    }
+                } else {
+                    ClassExpr {
+                        complement: false,
+                        terms: class_terms(class),
+                    }
+                }
+            })
+            .collect();
+
+        Self {
+            thresholds: thresholds.into_iter().collect(),
+            eq_bytes: eq_bytes.into_iter().collect(),
+            class_exprs,
         }
+    }
 
-        if node.state_type == "accept" {
-            accept_nodes.insert(i);
+    /// Returns the linear expression of the `byte_class` signal of the `class_i`-th used class.
+    fn class_expr(&self, class_i: usize) -> String {
+        let ge = |threshold: u16| match threshold {
+            0 => "1".to_string(),
+            256 => "0".to_string(),
+            _ => format!(
+                "byte_ge[{}][i]",
+                self.thresholds
+                    .iter()
+                    .position(|&t| t == threshold)
+                    .unwrap()
+            ),
+        };
+        let ClassExpr { complement, terms } = &self.class_exprs[class_i];
+        let sum = terms
+            .iter()
+            .map(|term| match term {
+                ClassTerm::Eq(bytes) => bytes
+                    .iter()
+                    .map(|byte| {
+                        let eq_idx = self.eq_bytes.iter().position(|b| b == byte).unwrap();
+                        format!("byte_eq[{}][i]", eq_idx)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" + "),
+                ClassTerm::Range(min, end) => match (ge(*min), ge(*end)) {
+                    (lower, upper) if upper == "0" => lower,
+                    (lower, upper) => format!("({} - {})", lower, upper),
+                },
+            })
+            .collect::<Vec<_>>()
+            .join(" + ");
+        if *complement {
+            format!("1 - ({})", sum)
+        } else {
+            sum
         }
     }
 
-    if accept_nodes.is_empty() {
-        return Err(CompilerError::AcceptNodesError(
-            "Accept node must exist".to_string(),
-        ));
+    /// Evaluates the `byte_class` signal of the `class_i`-th used class for the input byte from the `byte_ge` and
+    /// `byte_eq` signals.
+    #[cfg(test)]
+    fn class_value(&self, class_i: usize, byte: u8) -> i64 {
+        let ge = |threshold: u16| match threshold {
+            0 => 1,
+            256 => 0,
+            _ => {
+                assert!(self.thresholds.contains(&threshold));
+                (threshold <= byte as u16) as i64
+            }
+        };
+        let ClassExpr { complement, terms } = &self.class_exprs[class_i];
+        let sum = terms
+            .iter()
+            .map(|term| match term {
+                ClassTerm::Eq(bytes) => bytes
+                    .iter()
+                    .filter(|eq_byte| {
+                        assert!(self.eq_bytes.contains(eq_byte));
+                        **eq_byte == byte
+                    })
+                    .count() as i64,
+                ClassTerm::Range(min, end) => ge(*min) - ge(*end),
+            })
+            .sum::<i64>();
+        if *complement {
+            1 - sum
+        } else {
+            sum
+        }
     }
+}
 
-    Ok((rev_graph, accept_nodes))
+/// The state signals read by the accept and reveal logic.
+enum StateSignals<'a> {
+    /// The `states` signals of [`StateEncoding::Vector`].
+    Vector,
+    /// The `is_state` signals of [`StateEncoding::Index`].
+    Index(&'a IndexEncoding),
+    /// The projection of a product automaton onto one of its regexes, whose signals and the signals of its accept
+    /// and reveal logic are prefixed with the given string.
+    Projection(&'a str),
 }
 
-/// Optimizes character ranges by grouping consecutive characters and identifying individual characters.
-///
-/// This function takes a slice of u8 values (representing ASCII characters) and groups them into
-/// ranges where possible, while also identifying individual characters that don't fit into ranges.
-///
-/// # Arguments
-///
-/// * `k` - A slice of u8 values representing ASCII characters.
-///
-/// # Returns
-///
-/// A tuple containing:
-/// * A Vec of (u8, u8) tuples representing optimized character ranges (min, max).
-/// * A BTreeSet of u8 values representing individual characters not included in ranges.
-///
-/// # Note
-///
-/// Ranges are only created for sequences of 16 or more consecutive characters.
-fn optimize_char_ranges(k: &[u8]) -> (Vec<(u8, u8)>, BTreeSet<u8>) {
-    let mut min_maxes = vec![];
-    let mut vals = k.iter().cloned().collect::<BTreeSet<u8>>();
+impl<'a> StateSignals<'a> {
+    /// Returns the StateSignals of a circuit with the given IndexEncoding, if any.
+    fn of(encoding: Option<&'a IndexEncoding>) -> Self {
+        match encoding {
+            Some(encoding) => StateSignals::Index(encoding),
+            None => StateSignals::Vector,
+        }
+    }
 
-    if k.is_empty() {
-        return (min_maxes, vals);
+    /// Returns the prefix of the signals of the accept and reveal logic.
+    fn prefix(&self) -> &str {
+        match self {
+            StateSignals::Projection(prefix) => prefix,
+            _ => "",
+        }
     }
 
-    let mut cur_min = k[0];
-    let mut cur_max = k[0];
+    /// Returns the signal that is `1` if `state` is active at the position `idx`.
+    fn state(&self, state: usize, idx: &str) -> String {
+        match self {
+            StateSignals::Vector => format!("states[{}][{}]", idx, state),
+            StateSignals::Index(encoding) => encoding.state_signal(state, idx),
+            StateSignals::Projection(prefix) => format!("{}states[{}][{}]", prefix, idx, state),
+        }
+    }
 
-    for &val in &k[1..] {
-        if cur_max == val {
-            continue;
-        } else if cur_max + 1 == val {
-            cur_max = val;
+    /// Returns the signal that is `1` if a non-zero state is active after reading `in[idx]`.
+    fn state_changed(&self, idx: &str) -> String {
+        match self {
+            StateSignals::Vector => format!("state_changed[{}].out", idx),
+            _ => format!("{}state_changed[{}]", self.prefix(), idx),
+        }
+    }
+}
+
+/// Returns the byte classes used by the transitions in the order of their first use, i.e., the order of the
+/// `byte_class` signals.
+fn used_classes(byte_classes: &ByteClasses) -> Vec<usize> {
+    let mut used_classes = vec![];
+    for &class in byte_classes.transition_classes.iter().flatten() {
+        if !used_classes.contains(&class) {
+            used_classes.push(class);
+        }
+    }
+    used_classes
+}
+
+/// The state transition function of [`StateEncoding::Index`] over the byte classes.
+struct IndexEncoding {
+    /// The byte classes used by the transitions, i.e., the `byte_class` signals.
+    used_classes: Vec<usize>,
+    classification: ByteClassification,
+    /// The states with an `is_state` signal: the states with outgoing transitions, the accept states and the
+    /// states of the reveal masks.
+    indicators: Vec<usize>,
+    /// `next[state][class_i]` is the non-zero state reached from `state` by a byte of the `class_i`-th used class.
+    next: Vec<Vec<Option<usize>>>,
+}
+
+impl IndexEncoding {
+    fn new(circuit: &RegexCircuit) -> Self {
+        let byte_classes = circuit.byte_classes();
+        let used_classes = used_classes(&byte_classes);
+        let classification = ByteClassification::new(&byte_classes, &used_classes);
+
+        let mut next = vec![vec![None; used_classes.len()]; circuit.num_states];
+        for (transition, classes) in circuit
+            .transitions
+            .iter()
+            .zip(&byte_classes.transition_classes)
+        {
+            if transition.to == 0 {
+                continue;
+            }
+            for class in classes {
+                let class_i = used_classes.iter().position(|c| c == class).unwrap();
+                next[transition.from][class_i] = Some(transition.to);
+            }
+        }
+
+        let indicators = (1..circuit.num_states)
+            .filter(|&state| {
+                next[state].iter().any(Option::is_some)
+                    || circuit.accept_states.contains(&state)
+                    || circuit.reveals.iter().any(|reveal| {
+                        reveal
+                            .transitions
+                            .iter()
+                            .any(|&(from, to)| from == state || to == state)
+                    })
+            })
+            .collect();
+
+        Self {
+            used_classes,
+            classification,
+            indicators,
+            next,
+        }
+    }
+
+    /// Returns the state after reading a byte of the `class_i`-th used class in `state`: the transitions from
+    /// the zero state are taken only if no transition from `state` is.
+    fn next_state(&self, state: usize, class_i: usize) -> usize {
+        self.next[state][class_i]
+            .or(self.next[0][class_i])
+            .unwrap_or(0)
+    }
+
+    /// Returns the signal that is `1` if `state` is active at the position `idx`.
+    fn state_signal(&self, state: usize, idx: &str) -> String {
+        if state == 0 {
+            return "1".to_string();
+        }
+        let indicator_i = self
+            .indicators
+            .iter()
+            .position(|&indicator| indicator == state)
+            .expect("every state used by the accept and reveal logic has an indicator");
+        format!("is_state[{}][{}]", indicator_i, idx)
+    }
+
+    /// Returns the linear expression `sum_k coeffs[k] * is_state[k][i]` plus `constant`.
+    fn linear_expr(&self, constant: i64, coeffs: impl Fn(usize) -> i64) -> String {
+        let mut expr = if constant != 0 {
+            constant.to_string()
         } else {
-            if cur_max - cur_min >= 16 {
-                min_maxes.push((cur_min, cur_max));
+            String::new()
+        };
+        for (indicator_i, &state) in self.indicators.iter().enumerate() {
+            let coeff = coeffs(state);
+            if coeff == 0 {
+                continue;
             }
-            cur_min = val;
-            cur_max = val;
+            let term = match coeff.abs() {
+                1 => format!("is_state[{}][i]", indicator_i),
+                abs => format!("{} * is_state[{}][i]", abs, indicator_i),
+            };
+            expr = match (expr.is_empty(), coeff < 0) {
+                (true, false) => term,
+                (true, true) => format!("-{}", term),
+                (false, false) => format!("{} + {}", expr, term),
+                (false, true) => format!("{} - {}", expr, term),
+            };
+        }
+        if expr.is_empty() {
+            "0".to_string()
+        } else {
+            expr
         }
     }
 
-    if cur_max - cur_min >= 16 {
-        min_maxes.push((cur_min, cur_max));
+    /// Returns the expression of the next state index for a byte of the `class_i`-th used class, i.e., the
+    /// next state from the zero state plus the differences of the next states from the indicated states.
+    fn next_state_expr(&self, class_i: usize) -> String {
+        let from_zero = self.next_state(0, class_i) as i64;
+        self.linear_expr(from_zero, |state| {
+            self.next_state(state, class_i) as i64 - from_zero
+        })
+    }
+
+    /// Returns the expression that is `1` if a transition from the active non-zero state is taken for a byte of
+    /// the `class_i`-th used class, or `None` if there is no such transition.
+    fn leaves_nonzero_expr(&self, class_i: usize) -> Option<String> {
+        (1..self.next.len())
+            .any(|state| self.next[state][class_i].is_some())
+            .then(|| self.linear_expr(0, |state| self.next[state][class_i].is_some() as i64))
     }
+}
 
-    for (min, max) in &min_maxes {
-        for code in *min..=*max {
-            vals.remove(&code);
+/// Generates the state transition logic of [`StateEncoding::Index`] for the Circom circuit.
+///
+/// # Arguments
+///
+/// * `circuit` - A reference to the RegexCircuit.
+/// * `encoding` - A reference to the IndexEncoding of the circuit.
+///
+/// # Returns
+///
+/// A tuple containing:
+/// * The CircomStats of the components used.
+/// * A Vec of Strings containing the generated Circom code lines.
+fn generate_index_state_transition_logic(
+    circuit: &RegexCircuit,
+    encoding: &IndexEncoding,
+) -> (CircomStats, Vec<String>) {
+    let mut lines = vec!["\tfor (var i = 0; i < num_bytes; i++) {".to_string()];
+    if circuit.has_end_anchor {
+        lines.push(
+            "\t\tpadding_start[i+1] <== IsNotZeroAcc()(padding_start[i], in[i]);".to_string(),
+        );
+    }
+    add_byte_classification(&mut lines, &encoding.classification);
+    for (indicator_i, state) in encoding.indicators.iter().enumerate() {
+        lines.push(format!(
+            "\t\tis_state[{}][i] <== IsEqual()([state[i], {}]);",
+            indicator_i, state
+        ));
+    }
+    for class_i in 0..encoding.used_classes.len() {
+        lines.push(format!(
+            "\t\tclass_next[{}][i] <== byte_class[{}][i] * ({});",
+            class_i,
+            class_i,
+            encoding.next_state_expr(class_i)
+        ));
+    }
+    lines.push(format!(
+        "\t\tstate[i+1] <== {};",
+        if encoding.used_classes.is_empty() {
+            "0".to_string()
+        } else {
+            (0..encoding.used_classes.len())
+                .map(|class_i| format!("class_next[{}][i]", class_i))
+                .collect::<Vec<_>>()
+                .join(" + ")
         }
+    ));
+    lines.push("\t}".to_string());
+    // The accept and reveal logic also read the state after the last byte.
+    for (indicator_i, state) in encoding.indicators.iter().enumerate() {
+        lines.push(format!(
+            "\tis_state[{}][num_bytes] <== IsEqual()([state[num_bytes], {}]);",
+            indicator_i, state
+        ));
     }
 
-    (min_maxes, vals)
+    let stats = CircomStats {
+        byte_classes: encoding.used_classes.len(),
+        class_comparators: encoding.classification.thresholds.len(),
+        class_eqs: encoding.classification.eq_bytes.len(),
+        state_indicators: encoding.indicators.len(),
+        class_products: encoding.used_classes.len(),
+        state_signals: 1 + encoding.indicators.len(),
+        ..Default::default()
+    };
+    (stats, lines)
 }
 
-/// Adds a range check for character comparisons in the Circom circuit.
+/// Adds the classification of the input byte into its byte class to the Circom circuit.
 ///
-/// This function either reuses an existing range check or creates a new one,
-/// adding the necessary Circom code lines and updating the relevant counters.
+/// Each check of the input byte is computed once per position, and the indicator of each class is a linear
+/// combination of the checks.
 ///
 /// # Arguments
 ///
 /// * `lines` - A mutable reference to a Vec of Strings containing Circom code lines.
-/// * `range_checks` - A mutable reference to a 2D Vec storing existing range checks.
-/// * `eq_outputs` - A mutable reference to a Vec storing equality check outputs.
-/// * `min` - The minimum value of the range.
-/// * `max` - The maximum value of the range.
-/// * `lt_i` - A mutable reference to the current LessThan component index.
-/// * `and_i` - A mutable reference to the current AND component index.
-fn add_range_check(
-    lines: &mut Vec<String>,
-    range_checks: &mut Vec<Vec<Option<(usize, usize)>>>,
-    eq_outputs: &mut Vec<(&str, usize)>,
-    min: u8,
-    max: u8,
-    lt_i: &mut usize,
-    and_i: &mut usize,
-) {
-    if let Some((_, and_i)) = range_checks[min as usize][max as usize] {
-        eq_outputs.push(("and", and_i));
-    } else {
-        lines.push(format!("\t\tlt[{}][i] = LessEqThan(8);", *lt_i));
-        lines.push(format!("\t\tlt[{}][i].in[0] <== {};", *lt_i, min));
-        lines.push(format!("\t\tlt[{}][i].in[1] <== in[i];", *lt_i));
-        lines.push(format!("\t\tlt[{}][i] = LessEqThan(8);", *lt_i + 1));
-        lines.push(format!("\t\tlt[{}][i].in[0] <== in[i];", *lt_i + 1));
-        lines.push(format!("\t\tlt[{}][i].in[1] <== {};", *lt_i + 1, max));
-        lines.push(format!("\t\tand[{}][i] = AND();", *and_i));
+/// * `classification` - A reference to the planned ByteClassification.
+fn add_byte_classification(lines: &mut Vec<String>, classification: &ByteClassification) {
+    for (ge_i, threshold) in classification.thresholds.iter().enumerate() {
         lines.push(format!(
-            "\t\tand[{}][i].a <== lt[{}][i].out;",
-            *and_i, *lt_i
+            "\t\tbyte_ge[{}][i] <== LessEqThan(8)([{}, in[i]]);",
+            ge_i, threshold
         ));
+    }
+    for (eq_i, byte) in classification.eq_bytes.iter().enumerate() {
         lines.push(format!(
-            "\t\tand[{}][i].b <== lt[{}][i].out;",
-            *and_i,
-            *lt_i + 1
+            "\t\tbyte_eq[{}][i] <== IsEqual()([in[i], {}]);",
+            eq_i, byte
+        ));
+    }
+    for class_i in 0..classification.class_exprs.len() {
+        lines.push(format!(
+            "\t\tbyte_class[{}][i] <== {};",
+            class_i,
+            classification.class_expr(class_i)
         ));
-
-        eq_outputs.push(("and", *and_i));
-        range_checks[min as usize][max as usize] = Some((*lt_i, *and_i));
-        *lt_i += 2;
-        *and_i += 1;
     }
 }
 
-/// Adds an equality check for a specific character code in the Circom circuit.
+/// Adds a state transition enabled by the sum of mutually exclusive conditions to the Circom circuit.
 ///
-/// This function either reuses an existing equality check or creates a new one,
-/// adding the necessary Circom code lines and updating the relevant counter.
+/// At most one of the conditions holds, so their sum enables the transition without a MultiOR gate.
 ///
 /// # Arguments
 ///
 /// * `lines` - A mutable reference to a Vec of Strings containing Circom code lines.
-/// * `eq_checks` - A mutable reference to a Vec storing existing equality checks.
-/// * `code` - The ASCII code of the character to check for equality.
-/// * `eq_i` - A mutable reference to the current equality component index.
-///
-/// # Returns
-///
-/// The index of the equality check component used or created.
-fn add_eq_check(
+/// * `zero_starting_and_idxes` - A mutable reference to a BTreeMap storing AND indices for zero-starting states.
+/// * `i` - The current state index.
+/// * `prev_i` - The previous state index.
+/// * `conditions` - The signals of the conditions, e.g., the `byte_class` signals of the transition.
+/// * `and_i` - A mutable reference to the current AND gate index.
+fn add_summed_state_transition(
     lines: &mut Vec<String>,
-    eq_checks: &mut Vec<Option<usize>>,
-    code: u8,
-    eq_i: &mut usize,
-) -> usize {
-    if let Some(index) = eq_checks[code as usize] {
-        index
-    } else {
-        lines.push(format!("\t\teq[{}][i] = IsEqual();", *eq_i));
-        lines.push(format!("\t\teq[{}][i].in[0] <== in[i];", *eq_i));
-        lines.push(format!("\t\teq[{}][i].in[1] <== {};", *eq_i, code));
-        eq_checks[code as usize] = Some(*eq_i);
-        let result = *eq_i;
-        *eq_i += 1;
-        result
+    zero_starting_and_idxes: &mut BTreeMap<usize, Vec<usize>>,
+    i: usize,
+    prev_i: usize,
+    conditions: &[String],
+    and_i: &mut usize,
+) {
+    lines.push(format!("\t\tand[{}][i] = AND();", and_i));
+    lines.push(format!(
+        "\t\tand[{}][i].a <== states[i][{}];",
+        and_i, prev_i
+    ));
+    if !conditions.is_empty() {
+        lines.push(format!(
+            "\t\tand[{}][i].b <== {};",
+            and_i,
+            conditions.join(" + ")
+        ));
+        if prev_i == 0 {
+            zero_starting_and_idxes.get_mut(&i).unwrap().push(*and_i);
+        }
     }
+    *and_i += 1;
 }
 
 /// Adds a state transition to the Circom circuit.
@@ -208,19 +781,15 @@ fn add_eq_check(
 /// # Arguments
 ///
 /// * `lines` - A mutable reference to a Vec of Strings containing Circom code lines.
-/// * `zero_starting_and_idxes` - A mutable reference to a BTreeMap storing AND indices for zero-starting states.
-/// * `i` - The current state index.
 /// * `prev_i` - The previous state index.
-/// * `eq_outputs` - A Vec of tuples containing equality output types and indices.
+/// * `eq_outputs` - A slice of the output signals of the byte predicates of the transition.
 /// * `and_i` - A mutable reference to the current AND gate index.
 /// * `multi_or_checks1` - A mutable reference to a BTreeMap storing MultiOR checks.
 /// * `multi_or_i` - A mutable reference to the current MultiOR gate index.
 fn add_state_transition(
     lines: &mut Vec<String>,
-    zero_starting_and_idxes: &mut BTreeMap<usize, Vec<usize>>,
-    i: usize,
     prev_i: usize,
-    eq_outputs: Vec<(&str, usize)>,
+    eq_outputs: &[String],
     and_i: &mut usize,
     multi_or_checks1: &mut BTreeMap<String, usize>,
     multi_or_i: &mut usize,
@@ -232,15 +801,9 @@ fn add_state_transition(
     ));
 
     if eq_outputs.len() == 1 {
-        lines.push(format!(
-            "\t\tand[{}][i].b <== {}[{}][i].out;",
-            and_i, eq_outputs[0].0, eq_outputs[0].1
-        ));
-        if prev_i == 0 {
-            zero_starting_and_idxes.get_mut(&i).unwrap().push(*and_i);
-        }
+        lines.push(format!("\t\tand[{}][i].b <== {};", and_i, eq_outputs[0]));
     } else if eq_outputs.len() > 1 {
-        let eq_outputs_key = serde_json::to_string(&eq_outputs).unwrap();
+        let eq_outputs_key = serde_json::to_string(eq_outputs).unwrap();
         if let Some(&multi_or_index) = multi_or_checks1.get(&eq_outputs_key) {
             lines.push(format!(
                 "\t\tand[{}][i].b <== multi_or[{}][i].out;",
@@ -252,10 +815,10 @@ fn add_state_transition(
                 *multi_or_i,
                 eq_outputs.len()
             ));
-            for (output_i, (eq_type, eq_i)) in eq_outputs.iter().enumerate() {
+            for (output_i, eq_output) in eq_outputs.iter().enumerate() {
                 lines.push(format!(
-                    "\t\tmulti_or[{}][i].in[{}] <== {}[{}][i].out;",
-                    *multi_or_i, output_i, eq_type, eq_i
+                    "\t\tmulti_or[{}][i].in[{}] <== {};",
+                    *multi_or_i, output_i, eq_output
                 ));
             }
             lines.push(format!(
@@ -265,9 +828,6 @@ fn add_state_transition(
             multi_or_checks1.insert(eq_outputs_key, *multi_or_i);
             *multi_or_i += 1;
         }
-        if prev_i == 0 {
-            zero_starting_and_idxes.get_mut(&i).unwrap().push(*and_i);
-        }
     }
 
     *and_i += 1;
@@ -308,6 +868,8 @@ fn add_multi_or_gate(
 /// * `zero_starting_states` - A mutable reference to a Vec of zero-starting state indices.
 /// * `multi_or_checks2` - A mutable reference to a BTreeMap storing MultiOR checks.
 /// * `multi_or_i` - A mutable reference to the current MultiOR gate index.
+/// * `one_hot` - Whether to sum the outputs, which come from distinct previous states and are thus mutually
+///   exclusive, instead of creating a MultiOR gate.
 fn add_state_update(
     lines: &mut Vec<String>,
     i: usize,
@@ -315,6 +877,7 @@ fn add_state_update(
     zero_starting_states: &[usize],
     multi_or_checks2: &mut BTreeMap<String, usize>,
     multi_or_i: &mut usize,
+    one_hot: bool,
 ) {
     let is_zero_starting = zero_starting_states.contains(&i);
     let state_var = if is_zero_starting {
@@ -329,6 +892,14 @@ fn add_state_update(
             "\t\t{state_var}[i+1][{i}] <== and[{}][i].out;",
             outputs[0]
         )),
+        _ if one_hot => lines.push(format!(
+            "\t\t{state_var}[i+1][{i}] <== {};",
+            outputs
+                .iter()
+                .map(|and_i| format!("and[{}][i].out", and_i))
+                .collect::<Vec<_>>()
+                .join(" + ")
+        )),
         _ => {
             let outputs_key = serde_json::to_string(&outputs).expect("Failed to serialize outputs");
             if let Some(&multi_or_index) = multi_or_checks2.get(&outputs_key) {
@@ -354,23 +925,35 @@ fn add_state_update(
 /// * `lines` - A mutable reference to a Vec of Strings containing Circom code lines.
 /// * `state_len` - The total number of states in the DFA.
 /// * `zero_starting_states` - A reference to a Vec of indices of zero-starting states.
+/// * `one_hot` - Whether to subtract the sum of the states, at most one of which is active, from `1` instead of
+///   creating a MultiNOR gate.
 fn add_from_zero_enabled(
     lines: &mut Vec<String>,
     state_len: usize,
     zero_starting_states: &Vec<usize>,
+    one_hot: bool,
 ) {
-    lines.push(format!(
-        "\t\tfrom_zero_enabled[i] <== MultiNOR({})([{}]);",
-        state_len - 1,
-        (1..state_len)
-            .map(|i| (if zero_starting_states.contains(&i) {
+    let states = (1..state_len)
+        .map(|i| {
+            if zero_starting_states.contains(&i) {
                 format!("states_tmp[i+1][{}]", i)
             } else {
                 format!("states[i+1][{}]", i)
-            }))
-            .collect::<Vec<_>>()
-            .join(", ")
-    ));
+            }
+        })
+        .collect::<Vec<_>>();
+    if one_hot {
+        lines.push(format!(
+            "\t\tfrom_zero_enabled[i] <== 1 - ({});",
+            states.join(" + ")
+        ));
+    } else {
+        lines.push(format!(
+            "\t\tfrom_zero_enabled[i] <== MultiNOR({})([{}]);",
+            state_len - 1,
+            states.join(", ")
+        ));
+    }
 }
 
 /// Adds updates for zero-starting states to the Circom circuit.
@@ -383,14 +966,30 @@ fn add_from_zero_enabled(
 ///
 /// * `lines` - A mutable reference to a Vec of Strings containing Circom code lines.
 /// * `zero_starting_and_idxes` - A reference to a BTreeMap mapping state indices to their corresponding AND gate indices.
+/// * `one_hot` - Whether to sum the temporary state and the transition from the zero state, which is enabled
+///   only if no temporary state is active, instead of creating a MultiOR gate.
+///
+/// # Returns
+///
+/// The number of MultiOR gates created.
 fn add_zero_starting_state_updates(
     lines: &mut Vec<String>,
     zero_starting_and_idxes: &BTreeMap<usize, Vec<usize>>,
-) {
+    one_hot: bool,
+) -> usize {
+    let mut num_multi_ors = 0;
     for (i, vec) in zero_starting_and_idxes {
         if vec.is_empty() {
             continue;
         }
+        if one_hot && vec.len() == 1 {
+            lines.push(format!(
+                "\t\tstates[i+1][{}] <== states_tmp[i+1][{}] + from_zero_enabled[i] * and[{}][i].out;",
+                i, i, vec[0]
+            ));
+            continue;
+        }
+        num_multi_ors += 1;
         lines.push(format!(
             "\t\tstates[i+1][{}] <== MultiOR({})([states_tmp[i+1][{}], {}]);",
             i,
@@ -402,6 +1001,7 @@ fn add_zero_starting_state_updates(
                 .join(", ")
         ));
     }
+    num_multi_ors
 }
 
 /// Adds state change detection logic to the Circom circuit.
@@ -430,116 +1030,305 @@ fn add_state_changed_updates(lines: &mut Vec<String>, state_len: usize) {
 ///
 /// # Arguments
 ///
-/// * `rev_graph` - A reference to the reverse graph of the DFA.
-/// * `state_len` - The total number of states in the DFA.
-/// * `end_anchor` - A boolean indicating whether an end anchor is present.
+/// * `circuit` - A reference to the RegexCircuit.
+/// * `options` - A reference to the CircomOptions.
+/// * `shared_predicates` - The predicates of the `pred` input signals if they are computed by a shared template.
 ///
 /// # Returns
 ///
 /// A tuple containing:
-/// * The number of equality checks used.
-/// * The number of less-than checks used.
-/// * The number of AND gates used.
-/// * The number of multi-OR gates used.
+/// * The CircomStats of the components used.
 /// * A Vec of Strings containing the generated Circom code lines.
 fn generate_state_transition_logic(
-    rev_graph: &BTreeMap<usize, BTreeMap<usize, Vec<u8>>>,
-    state_len: usize,
-    end_anchor: bool,
-) -> (usize, usize, usize, usize, Vec<String>) {
+    circuit: &RegexCircuit,
+    options: &CircomOptions,
+    shared_predicates: Option<&[BytePredicate]>,
+) -> (CircomStats, Vec<String>) {
+    if options.state_encoding == StateEncoding::Index {
+        return generate_index_state_transition_logic(circuit, &IndexEncoding::new(circuit));
+    }
+
+    let state_len = circuit.num_states;
     let mut eq_i = 0;
     let mut lt_i = 0;
     let mut and_i = 0;
     let mut multi_or_i = 0;
 
-    let mut range_checks = vec![vec![None; 256]; 256];
-    let mut eq_checks = vec![None; 256];
+    let mut predicate_outputs = vec![None; circuit.predicates.len()];
     let mut multi_or_checks1 = BTreeMap::<String, usize>::new();
     let mut multi_or_checks2 = BTreeMap::<String, usize>::new();
     let mut zero_starting_states = vec![];
     let mut zero_starting_and_idxes = BTreeMap::<usize, Vec<usize>>::new();
 
     let mut lines = vec![];
+    let mut stats = CircomStats::default();
+
+    let byte_classes = options.alphabet_classes.then(|| circuit.byte_classes());
+    let mut class_signal_idxes = BTreeMap::<usize, usize>::new();
+    let mut classification = None;
+    if let Some(byte_classes) = &byte_classes {
+        let used_classes = used_classes(byte_classes);
+        class_signal_idxes.extend(
+            used_classes
+                .iter()
+                .enumerate()
+                .map(|(class_i, &class)| (class, class_i)),
+        );
+        let planned = ByteClassification::new(byte_classes, &used_classes);
+        stats.byte_classes = used_classes.len();
+        stats.class_comparators = planned.thresholds.len();
+        stats.class_eqs = planned.eq_bytes.len();
+        classification = Some(planned);
+    }
 
     lines.push("\tfor (var i = 0; i < num_bytes; i++) {".to_string());
+    // OneHotSum outputs the sum of the non-zero states and constrains it to be 0 or 1.
     lines.push(format!(
-        "\t\tstate_changed[i] = MultiOR({});",
+        "\t\tstate_changed[i] = {}({});",
+        if options.one_hot {
+            "OneHotSum"
+        } else {
+            "MultiOR"
+        },
         state_len - 1
     ));
     lines.push("\t\tstates[i][0] <== 1;".to_string());
 
-    if end_anchor {
+    if circuit.has_end_anchor {
         lines.push(
             "\t\tpadding_start[i+1] <== IsNotZeroAcc()(padding_start[i], in[i]);".to_string(),
         );
     }
 
+    if let Some(classification) = &classification {
+        add_byte_classification(&mut lines, classification);
+    }
+
     for i in 1..state_len {
         let mut outputs = vec![];
         zero_starting_and_idxes.insert(i, vec![]);
 
-        for (prev_i, chars) in rev_graph.get(&i).unwrap_or(&BTreeMap::new()) {
-            if *prev_i == 0 {
+        for (transition_idx, transition) in circuit
+            .transitions
+            .iter()
+            .enumerate()
+            .filter(|(_, transition)| transition.to == i)
+        {
+            let prev_i = transition.from;
+            if prev_i == 0 {
                 zero_starting_states.push(i);
             }
-            let mut k = chars.clone();
-            k.retain(|&x| x != 0);
-            k.sort();
 
-            let mut eq_outputs = vec![];
-
-            let (min_maxes, individual_chars) = optimize_char_ranges(&k);
-
-            for (min, max) in min_maxes {
-                add_range_check(
+            if let Some(byte_classes) = &byte_classes {
+                let class_signals = byte_classes.transition_classes[transition_idx]
+                    .iter()
+                    .map(|class| format!("byte_class[{}][i]", class_signal_idxes[class]))
+                    .collect::<Vec<_>>();
+                add_summed_state_transition(
                     &mut lines,
-                    &mut range_checks,
-                    &mut eq_outputs,
-                    min,
-                    max,
-                    &mut lt_i,
+                    &mut zero_starting_and_idxes,
+                    i,
+                    prev_i,
+                    &class_signals,
                     &mut and_i,
                 );
+                if prev_i != 0 {
+                    outputs.push(and_i - 1);
+                }
+                continue;
             }
 
-            for &code in &individual_chars {
-                let eq_index = add_eq_check(&mut lines, &mut eq_checks, code, &mut eq_i);
-                eq_outputs.push(("eq", eq_index));
+            let eq_outputs = transition
+                .predicates
+                .iter()
+                .map(|&predicate_idx| {
+                    let predicate = circuit.predicates[predicate_idx];
+                    if let Some(shared_predicates) = shared_predicates {
+                        let shared_idx = shared_predicates
+                            .iter()
+                            .position(|&shared| shared == predicate)
+                            .expect("the shared predicates contain the predicates of the circuit");
+                        return format!("pred[{}][i]", shared_idx);
+                    }
+                    let (eq_type, eq_i) = add_predicate_check(
+                        &mut lines,
+                        &mut predicate_outputs,
+                        predicate_idx,
+                        predicate,
+                        &mut eq_i,
+                        &mut lt_i,
+                        &mut and_i,
+                    );
+                    format!("{}[{}][i].out", eq_type, eq_i)
+                })
+                .collect::<Vec<_>>();
+
+            if options.one_hot
+                && eq_outputs.len() > 1
+                && circuit.predicates_are_exclusive(&transition.predicates)
+            {
+                add_summed_state_transition(
+                    &mut lines,
+                    &mut zero_starting_and_idxes,
+                    i,
+                    prev_i,
+                    &eq_outputs,
+                    &mut and_i,
+                );
+                stats.sums += 1;
+                if prev_i != 0 {
+                    outputs.push(and_i - 1);
+                }
+                continue;
             }
 
+            if prev_i == 0 && !eq_outputs.is_empty() {
+                zero_starting_and_idxes.get_mut(&i).unwrap().push(and_i);
+            }
             add_state_transition(
                 &mut lines,
-                &mut zero_starting_and_idxes,
-                i,
-                *prev_i,
-                eq_outputs,
+                prev_i,
+                &eq_outputs,
                 &mut and_i,
                 &mut multi_or_checks1,
                 &mut multi_or_i,
             );
 
-            if *prev_i != 0 {
+            if prev_i != 0 {
                 outputs.push(and_i - 1);
             }
         }
 
+        if options.one_hot && outputs.len() > 1 {
+            stats.sums += 1;
+        }
         add_state_update(
             &mut lines,
             i,
             outputs,
-            &mut zero_starting_states,
+            &zero_starting_states,
             &mut multi_or_checks2,
             &mut multi_or_i,
+            options.one_hot,
         );
     }
 
-    add_from_zero_enabled(&mut lines, state_len, &zero_starting_states);
-    add_zero_starting_state_updates(&mut lines, &zero_starting_and_idxes);
+    add_from_zero_enabled(
+        &mut lines,
+        state_len,
+        &zero_starting_states,
+        options.one_hot,
+    );
+    let zero_starting_multi_ors =
+        add_zero_starting_state_updates(&mut lines, &zero_starting_and_idxes, options.one_hot);
     add_state_changed_updates(&mut lines, state_len);
 
     lines.push("\t}".to_string());
 
-    (eq_i, lt_i, and_i, multi_or_i, lines)
+    stats.eq = eq_i;
+    stats.lt = lt_i;
+    stats.and = and_i;
+    stats.multi_or = multi_or_i;
+    // states and states_tmp.
+    stats.state_signals = 2 * state_len;
+    if options.one_hot {
+        stats.sums += zero_starting_and_idxes
+            .values()
+            .filter(|and_idxes| !and_idxes.is_empty())
+            .count()
+            - zero_starting_multi_ors
+            + 1;
+        stats.inline_multi_or = zero_starting_multi_ors;
+        stats.one_hot_checks = 1;
+    } else {
+        // The MultiNOR of from_zero_enabled and the MultiOR of state_changed.
+        stats.inline_multi_or = zero_starting_multi_ors + 2;
+    }
+    (stats, lines)
+}
+
+/// Generates the declaration of the `msg` signals, which are unpacked from the `msg_packed` input of field
+/// elements if `packed_msg` is true.
+fn generate_msg_input(packed_msg: bool) -> Vec<String> {
+    if !packed_msg {
+        return vec!["\tsignal input msg[msg_bytes];".to_string()];
+    }
+    vec![
+        "\tsignal input msg_packed[(msg_bytes+30)\\31];".to_string(),
+        "\tsignal msg[msg_bytes];".to_string(),
+        "\tmsg <== UnpackBytes(msg_bytes)(msg_packed);".to_string(),
+    ]
+}
+
+/// The include of the helper templates used by every generated template.
+const REGEX_HELPERS_INCLUDE: &str =
+    "include \"@zk-email/zk-regex-circom/circuits/regex_helpers.circom\";";
+/// The include of `CommitBytes` and the Poseidon hash, which only a template with a committed part uses.
+const COMMIT_HELPERS_INCLUDE: &str =
+    "include \"@zk-email/zk-regex-circom/circuits/commit_helpers.circom\";";
+
+/// Adds the include of `commit_helpers.circom` after the include of `regex_helpers.circom` of a generated file if
+/// one of its templates commits to a substring, so that the other files do not depend on the Poseidon hash.
+///
+/// # Arguments
+///
+/// * `circom` - The generated Circom code.
+/// * `committed` - Whether a template of the file commits to a substring.
+///
+/// # Returns
+///
+/// The generated Circom code with the include.
+fn include_commit_helpers(circom: String, committed: bool) -> String {
+    if !committed {
+        return circom;
+    }
+    circom.replacen(
+        REGEX_HELPERS_INCLUDE,
+        &format!("{REGEX_HELPERS_INCLUDE}\n{COMMIT_HELPERS_INCLUDE}"),
+        1,
+    )
+}
+
+/// Generates the header of a template that reads the `msg` input, i.e., the pragma, the include, the template
+/// signature and the range-checked `in` signals with `in[0] = 255`.
+///
+/// # Arguments
+///
+/// * `template_name` - The name of the Circom template.
+/// * `regex_str` - The regex string written in the comment above the template.
+/// * `packed_msg` - Whether `msg` is unpacked from the `msg_packed` input.
+///
+/// # Returns
+///
+/// A Vec of Strings containing the generated Circom lines.
+fn generate_msg_header(template_name: &str, regex_str: &str, packed_msg: bool) -> Vec<String> {
+    [
+        vec![
+            "pragma circom 2.1.5;\n".to_string(),
+            format!("{REGEX_HELPERS_INCLUDE}\n"),
+            format!(
+                "// regex: {}",
+                regex_str.replace('\n', "\\n").replace('\r', "\\r")
+            ),
+            format!("template {}(msg_bytes) {{", template_name),
+        ],
+        generate_msg_input(packed_msg),
+        vec![
+            "\tsignal output out;".to_string(),
+            "".to_string(),
+            "\tvar num_bytes = msg_bytes+1;".to_string(),
+            "\tsignal in[num_bytes];".to_string(),
+            "\tsignal in_range_checks[msg_bytes];".to_string(),
+            "\tin[0]<==255;".to_string(),
+            "\tfor (var i = 0; i < msg_bytes; i++) {".to_string(),
+            "\t\tin_range_checks[i] <== LessThan(8)([msg[i], 255]);".to_string(),
+            "\t\tin_range_checks[i] === 1;".to_string(),
+            "\t\tin[i+1] <== msg[i];".to_string(),
+            "\t}".to_string(),
+            "".to_string(),
+        ],
+    ]
+    .concat()
 }
 
 /// Generates the declarations for the Circom circuit.
@@ -552,11 +1341,11 @@ fn generate_state_transition_logic(
 /// * `template_name` - The name of the Circom template.
 /// * `regex_str` - The regular expression string.
 /// * `state_len` - The total number of states in the DFA.
-/// * `eq_i` - The number of equality components.
-/// * `lt_i` - The number of less-than components.
-/// * `and_i` - The number of AND components.
-/// * `multi_or_i` - The number of multi-OR components.
+/// * `stats` - The CircomStats of the components used.
 /// * `end_anchor` - A boolean indicating whether an end anchor is present.
+/// * `options` - A reference to the CircomOptions, whose state encoding and `packed_msg` are used.
+/// * `num_shared_predicates` - The number of `pred` input signals if the predicates are computed by a shared
+///   template, which also outputs the range-checked `in` signals.
 ///
 /// # Returns
 ///
@@ -565,59 +1354,89 @@ fn generate_declarations(
     template_name: &str,
     regex_str: &str,
     state_len: usize,
-    eq_i: usize,
-    lt_i: usize,
-    and_i: usize,
-    multi_or_i: usize,
+    stats: &CircomStats,
     end_anchor: bool,
+    options: &CircomOptions,
+    num_shared_predicates: Option<usize>,
 ) -> Vec<String> {
-    let mut declarations = vec![
-        "pragma circom 2.1.5;\n".to_string(),
-        "include \"@zk-email/zk-regex-circom/circuits/regex_helpers.circom\";\n".to_string(),
-        format!(
-            "// regex: {}",
-            regex_str.replace('\n', "\\n").replace('\r', "\\r")
-        ),
-        format!("template {}(msg_bytes) {{", template_name),
-        "\tsignal input msg[msg_bytes];".to_string(),
-        "\tsignal output out;".to_string(),
-        "".to_string(),
-        "\tvar num_bytes = msg_bytes+1;".to_string(),
-        "\tsignal in[num_bytes];".to_string(),
-        "\tsignal in_range_checks[msg_bytes];".to_string(),
-        "\tin[0]<==255;".to_string(),
-        "\tfor (var i = 0; i < msg_bytes; i++) {".to_string(),
-        "\t\tin_range_checks[i] <== LessThan(8)([msg[i], 255]);".to_string(),
-        "\t\tin_range_checks[i] === 1;".to_string(),
-        "\t\tin[i+1] <== msg[i];".to_string(),
-        "\t}".to_string(),
-        "".to_string(),
-    ];
+    let mut declarations = match num_shared_predicates {
+        Some(num_shared_predicates) => vec![
+            format!(
+                "// regex: {}",
+                regex_str.replace('\n', "\\n").replace('\r', "\\r")
+            ),
+            format!("template {}(msg_bytes) {{", template_name),
+            "\tvar num_bytes = msg_bytes+1;".to_string(),
+            "\tsignal input in[num_bytes];".to_string(),
+            format!("\tsignal input pred[{}][num_bytes];", num_shared_predicates),
+            "\tsignal output out;".to_string(),
+            "".to_string(),
+        ],
+        None => generate_msg_header(template_name, regex_str, options.packed_msg),
+    };
 
-    if eq_i > 0 {
-        declarations.push(format!("\tcomponent eq[{}][num_bytes];", eq_i));
+    if stats.eq > 0 {
+        declarations.push(format!("\tcomponent eq[{}][num_bytes];", stats.eq));
     }
 
-    if lt_i > 0 {
-        declarations.push(format!("\tcomponent lt[{}][num_bytes];", lt_i));
+    if stats.lt > 0 {
+        declarations.push(format!("\tcomponent lt[{}][num_bytes];", stats.lt));
     }
 
-    if and_i > 0 {
-        declarations.push(format!("\tcomponent and[{}][num_bytes];", and_i));
+    if stats.and > 0 {
+        declarations.push(format!("\tcomponent and[{}][num_bytes];", stats.and));
     }
 
-    if multi_or_i > 0 {
-        declarations.push(format!("\tcomponent multi_or[{}][num_bytes];", multi_or_i));
+    if stats.multi_or > 0 {
+        declarations.push(format!(
+            "\tcomponent multi_or[{}][num_bytes];",
+            stats.multi_or
+        ));
     }
 
-    declarations.extend([
-        format!("\tsignal states[num_bytes+1][{state_len}];"),
-        format!("\tsignal states_tmp[num_bytes+1][{state_len}];"),
-        "\tsignal from_zero_enabled[num_bytes+1];".to_string(),
-        "\tfrom_zero_enabled[num_bytes] <== 0;".to_string(),
-        "\tcomponent state_changed[num_bytes];".to_string(),
-        "".to_string(),
-    ]);
+    if stats.class_comparators > 0 {
+        declarations.push(format!(
+            "\tsignal byte_ge[{}][num_bytes];",
+            stats.class_comparators
+        ));
+    }
+
+    if stats.class_eqs > 0 {
+        declarations.push(format!("\tsignal byte_eq[{}][num_bytes];", stats.class_eqs));
+    }
+
+    if stats.byte_classes > 0 {
+        declarations.push(format!(
+            "\tsignal byte_class[{}][num_bytes];",
+            stats.byte_classes
+        ));
+    }
+
+    if options.state_encoding == StateEncoding::Index {
+        declarations.push("\tsignal state[num_bytes+1];".to_string());
+        if stats.state_indicators > 0 {
+            declarations.push(format!(
+                "\tsignal is_state[{}][num_bytes+1];",
+                stats.state_indicators
+            ));
+        }
+        if stats.class_products > 0 {
+            declarations.push(format!(
+                "\tsignal class_next[{}][num_bytes];",
+                stats.class_products
+            ));
+        }
+        declarations.push("".to_string());
+    } else {
+        declarations.extend([
+            format!("\tsignal states[num_bytes+1][{state_len}];"),
+            format!("\tsignal states_tmp[num_bytes+1][{state_len}];"),
+            "\tsignal from_zero_enabled[num_bytes+1];".to_string(),
+            "\tfrom_zero_enabled[num_bytes] <== 0;".to_string(),
+            "\tcomponent state_changed[num_bytes];".to_string(),
+            "".to_string(),
+        ]);
+    }
 
     if end_anchor {
         declarations.extend([
@@ -636,11 +1455,15 @@ fn generate_declarations(
 /// # Arguments
 ///
 /// * `state_len` - The total number of states in the DFA.
+/// * `index_encoding` - Whether the states use [`StateEncoding::Index`].
 ///
 /// # Returns
 ///
 /// A Vec of Strings containing the generated initialization code.
-fn generate_init_code(state_len: usize) -> Vec<String> {
+fn generate_init_code(state_len: usize, index_encoding: bool) -> Vec<String> {
+    if index_encoding {
+        return vec!["\tstate[0] <== 0;".to_string(), "".to_string()];
+    }
     vec![
         format!("\tfor (var i = 1; i < {state_len}; i++) {{"),
         "\t\tstates[0][i] <== 0;".to_string(),
@@ -658,6 +1481,7 @@ fn generate_init_code(state_len: usize) -> Vec<String> {
 ///
 /// * `accept_nodes` - A BTreeSet of accepting state indices.
 /// * `end_anchor` - A boolean indicating whether an end anchor is present.
+/// * `signals` - The StateSignals read by the logic.
 ///
 /// # Returns
 ///
@@ -667,6 +1491,7 @@ fn generate_init_code(state_len: usize) -> Vec<String> {
 fn generate_accept_logic(
     accept_nodes: BTreeSet<usize>,
     end_anchor: bool,
+    signals: &StateSignals,
 ) -> Result<Vec<String>, CompilerError> {
     let mut accept_lines = vec![];
 
@@ -683,32 +1508,35 @@ fn generate_accept_logic(
     }
 
     let accept_node = *accept_nodes.iter().next().unwrap();
+    let p = signals.prefix();
 
     accept_lines.push("".to_string());
-    accept_lines.push("\tcomponent is_accepted = MultiOR(num_bytes+1);".to_string());
+    accept_lines.push(format!(
+        "\tcomponent {p}is_accepted = MultiOR(num_bytes+1);"
+    ));
     accept_lines.push("\tfor (var i = 0; i <= num_bytes; i++) {".to_string());
     accept_lines.push(format!(
-        "\t\tis_accepted.in[i] <== states[i][{}];",
-        accept_node
+        "\t\t{p}is_accepted.in[i] <== {};",
+        signals.state(accept_node, "i")
     ));
     accept_lines.push("\t}".to_string());
 
     if end_anchor {
-        accept_lines.push("\tsignal end_anchor_check[num_bytes+1][2];".to_string());
-        accept_lines.push("\tend_anchor_check[0][1] <== 0;".to_string());
+        accept_lines.push(format!("\tsignal {p}end_anchor_check[num_bytes+1][2];"));
+        accept_lines.push(format!("\t{p}end_anchor_check[0][1] <== 0;"));
         accept_lines.push("\tfor (var i = 0; i < num_bytes; i++) {".to_string());
+        accept_lines.push(format!(
+            "\t\t{p}end_anchor_check[i+1][0] <== IsEqual()([i, padding_start[num_bytes]]);"
+        ));
         accept_lines.push(
-            "\t\tend_anchor_check[i+1][0] <== IsEqual()([i, padding_start[num_bytes]]);"
-                .to_string(),
-        );
-        accept_lines.push(
-            format!("\t\tend_anchor_check[i+1][1] <== end_anchor_check[i][1] + states[i][{}] * end_anchor_check[i+1][0];", accept_node)
+            format!("\t\t{p}end_anchor_check[i+1][1] <== {p}end_anchor_check[i][1] + {} * {p}end_anchor_check[i+1][0];", signals.state(accept_node, "i"))
         );
         accept_lines.push("\t}".to_string());
-        accept_lines
-            .push("\tout <== is_accepted.out * end_anchor_check[num_bytes][1];".to_string());
+        accept_lines.push(format!(
+            "\t{p}out <== {p}is_accepted.out * {p}end_anchor_check[num_bytes][1];"
+        ));
     } else {
-        accept_lines.push("\tout <== is_accepted.out;".to_string());
+        accept_lines.push(format!("\t{p}out <== {p}is_accepted.out;"));
     }
 
     Ok(accept_lines)
@@ -721,41 +1549,52 @@ fn generate_accept_logic(
 ///
 /// # Arguments
 ///
-/// * `dfa_graph` - A reference to the DFA graph.
+/// * `circuit` - A reference to the RegexCircuit.
 /// * `template_name` - The name of the Circom template.
-/// * `regex_str` - The regular expression string.
-/// * `end_anchor` - A boolean indicating whether an end anchor is present.
+/// * `options` - A reference to the CircomOptions.
+/// * `shared_predicates` - The predicates of the `pred` input signals if they are computed by a shared template.
 ///
 /// # Returns
 ///
 /// A String containing the complete Circom circuit code.
 fn gen_circom_allstr(
-    dfa_graph: &DFAGraph,
+    circuit: &RegexCircuit,
     template_name: &str,
-    regex_str: &str,
-    end_anchor: bool,
+    options: &CircomOptions,
+    shared_predicates: Option<&[BytePredicate]>,
 ) -> Result<String, CompilerError> {
-    let state_len = dfa_graph.states.len();
+    let state_len = circuit.num_states;
 
-    let (rev_graph, accept_nodes) = build_reverse_graph(state_len, dfa_graph)?;
+    if circuit.accept_states.is_empty() {
+        return Err(CompilerError::AcceptNodesError(
+            "Accept node must exist".to_string(),
+        ));
+    }
 
-    let (eq_i, lt_i, and_i, multi_or_i, lines) =
-        generate_state_transition_logic(&rev_graph, state_len, end_anchor);
+    let encoding =
+        (options.state_encoding == StateEncoding::Index).then(|| IndexEncoding::new(circuit));
+    let (stats, lines) = match &encoding {
+        Some(encoding) => generate_index_state_transition_logic(circuit, encoding),
+        None => generate_state_transition_logic(circuit, options, shared_predicates),
+    };
 
     let declarations = generate_declarations(
         template_name,
-        regex_str,
+        &circuit.regex,
         state_len,
-        eq_i,
-        lt_i,
-        and_i,
-        multi_or_i,
-        end_anchor,
+        &stats,
+        circuit.has_end_anchor,
+        options,
+        shared_predicates.map(<[BytePredicate]>::len),
     );
 
-    let init_code = generate_init_code(state_len);
+    let init_code = generate_init_code(state_len, encoding.is_some());
 
-    let accept_lines = generate_accept_logic(accept_nodes, end_anchor)?;
+    let accept_lines = generate_accept_logic(
+        circuit.accept_states.clone(),
+        circuit.has_end_anchor,
+        &StateSignals::of(encoding.as_ref()),
+    )?;
 
     let final_code = [declarations, init_code, lines, accept_lines].concat();
 
@@ -769,24 +1608,304 @@ fn gen_circom_allstr(
 /// # Arguments
 ///
 /// * `accepted_state` - The index of the accepted state.
+/// * `signals` - The StateSignals read by the logic.
 ///
 /// # Returns
 ///
 /// A String containing the generated Circom code for consecutive logic.
-fn write_consecutive_logic(accepted_state: usize) -> String {
+fn write_consecutive_logic(accepted_state: usize, signals: &StateSignals) -> String {
+    let accepted = signals.state(accepted_state, "num_bytes-i");
+    let state_changed = signals.state_changed("msg_bytes-i");
+    let p = signals.prefix();
     let mut logic = String::new();
     logic += "\n";
-    logic += "\tsignal is_consecutive[msg_bytes+1][3];\n";
-    logic += "\tis_consecutive[msg_bytes][2] <== 0;\n";
+    logic += &format!("\tsignal {p}is_consecutive[msg_bytes+1][3];\n");
+    logic += &format!("\t{p}is_consecutive[msg_bytes][2] <== 0;\n");
     logic += "\tfor (var i = 0; i < msg_bytes; i++) {\n";
     logic += &format!(
-        "\t\tis_consecutive[msg_bytes-1-i][0] <== states[num_bytes-i][{accepted_state}] * (1 - is_consecutive[msg_bytes-i][2]) + is_consecutive[msg_bytes-i][2];\n"
+        "\t\t{p}is_consecutive[msg_bytes-1-i][0] <== {accepted} * (1 - {p}is_consecutive[msg_bytes-i][2]) + {p}is_consecutive[msg_bytes-i][2];\n"
     );
-    logic +=
-        "\t\tis_consecutive[msg_bytes-1-i][1] <== state_changed[msg_bytes-i].out * is_consecutive[msg_bytes-1-i][0];\n";
     logic += &format!(
-        "\t\tis_consecutive[msg_bytes-1-i][2] <== ORAnd()([(1 - from_zero_enabled[msg_bytes-i+1]), states[num_bytes-i][{accepted_state}], is_consecutive[msg_bytes-1-i][1]]);\n"
+        "\t\t{p}is_consecutive[msg_bytes-1-i][1] <== {state_changed} * {p}is_consecutive[msg_bytes-1-i][0];\n"
+    );
+    logic += &format!(
+        "\t\t{p}is_consecutive[msg_bytes-1-i][2] <== ORAnd()([(1 - {p}from_zero_enabled[msg_bytes-i+1]), {accepted}, {p}is_consecutive[msg_bytes-1-i][1]]);\n"
+    );
+    logic += "\t}\n";
+    logic
+}
+
+/// Writes the `in_match` signals of a leftmost match policy, which are `1` at the bytes that may belong to the
+/// first run of the DFA that reaches the accept state.
+///
+/// `accepted_before[i]` is `1` if the accept state is reached before `msg[i]`. With
+/// [`MatchPolicy::LeftmostShortest`], the match ends at the first accept state, so `in_match[i]` is its negation.
+/// With [`MatchPolicy::LeftmostLongest`], `in_match[i]` also stays `1` while the run continues from a non-zero
+/// state, i.e., `from_zero_enabled` is `0`.
+///
+/// # Arguments
+///
+/// * `accepted_state` - The index of the accepted state.
+/// * `policy` - The leftmost MatchPolicy.
+/// * `signals` - The StateSignals read by the logic.
+///
+/// # Returns
+///
+/// A String containing the generated Circom code for the `in_match` signals.
+fn write_leftmost_logic(
+    accepted_state: usize,
+    policy: MatchPolicy,
+    signals: &StateSignals,
+) -> String {
+    let p = signals.prefix();
+    let first_accepted = signals.state(accepted_state, "1");
+    let accepted = signals.state(accepted_state, "i+1");
+    let in_match = |i: &str| {
+        match policy {
+        MatchPolicy::LeftmostLongest => format!(
+            "\t{p}in_match[{i}] <== 1 - {p}accepted_before[{i}] + {p}accepted_before[{i}] * {p}continues_match[{i}];\n"
+        ),
+        _ => format!("\t{p}in_match[{i}] <== 1 - {p}accepted_before[{i}];\n"),
+    }
+    };
+    let mut logic = String::new();
+    logic += "\n";
+    logic += &format!("\tsignal {p}accepted_before[msg_bytes];\n");
+    if policy == MatchPolicy::LeftmostLongest {
+        logic += &format!("\tsignal {p}continues_match[msg_bytes];\n");
+    }
+    logic += &format!("\tsignal {p}in_match[msg_bytes];\n");
+    logic += &format!("\t{p}accepted_before[0] <== {first_accepted};\n");
+    if policy == MatchPolicy::LeftmostLongest {
+        logic += &format!("\t{p}continues_match[0] <== 1 - {p}from_zero_enabled[1];\n");
+    }
+    logic += &in_match("0");
+    logic += "\tfor (var i = 1; i < msg_bytes; i++) {\n";
+    logic += &format!(
+        "\t\t{p}accepted_before[i] <== {p}accepted_before[i-1] + {accepted} - {p}accepted_before[i-1] * {accepted};\n"
+    );
+    if policy == MatchPolicy::LeftmostLongest {
+        logic += &format!(
+            "\t\t{p}continues_match[i] <== {p}in_match[i-1] * (1 - {p}from_zero_enabled[i+1]);\n"
+        );
+    }
+    logic += &format!("\t{}", in_match("i"));
+    logic += "\t}\n";
+    logic
+}
+
+/// Writes the constraints of [`Occurrence::First`] and [`Occurrence::Unique`], which run the complete
+/// [`SearchDfa`] of the regex on `in` with one signal per state and position. Unlike the DFA, which restarts from
+/// the state `0` only when a run fails, it finds every position where an occurrence of the regex ends.
+///
+/// The first position where the SearchDfa accepts must have the accept state of the DFA active, so the first run
+/// of the DFA that reaches the accept state is the first occurrence. With [`Occurrence::Unique`], the SearchDfa
+/// must not accept where `in_match` is `0`, i.e., no occurrence ends after the first one.
+///
+/// # Arguments
+///
+/// * `search` - A reference to the SearchDfa of the regex.
+/// * `accepted_state` - The index of the accepted state.
+/// * `occurrence` - The Occurrence, which is not [`Occurrence::Any`].
+/// * `signals` - The StateSignals read by the logic.
+///
+/// # Returns
+///
+/// A String containing the generated Circom code for the constraints.
+fn write_occurrence_logic(
+    search: &SearchDfa,
+    accepted_state: usize,
+    occurrence: Occurrence,
+    signals: &StateSignals,
+) -> String {
+    let p = signals.prefix();
+    let mut ranges = BTreeMap::new();
+    let mut transitions = vec![];
+    for (transition_idx, (from, _, transition_ranges)) in search.transitions.iter().enumerate() {
+        let preds = transition_ranges
+            .iter()
+            .map(|&range| {
+                let num_ranges = ranges.len();
+                let range_idx = *ranges.entry(range).or_insert(num_ranges);
+                format!("{p}search_in_range[{range_idx}][i]")
+            })
+            .collect::<Vec<_>>();
+        transitions.push(format!(
+            "\t\t{p}search_transitions[{transition_idx}][i] <== {p}search_states[i][{from}] * ({});\n",
+            preds.join(" + ")
+        ));
+    }
+    let mut ranges = ranges.into_iter().collect::<Vec<_>>();
+    ranges.sort_by_key(|&(_, range_idx)| range_idx);
+
+    let mut logic = String::new();
+    logic += "\n";
+    logic += &format!(
+        "\tsignal {p}search_in_range[{}][num_bytes];\n",
+        ranges.len()
+    );
+    logic += &format!(
+        "\tsignal {p}search_transitions[{}][num_bytes];\n",
+        search.transitions.len()
+    );
+    logic += &format!(
+        "\tsignal {p}search_states[num_bytes+1][{}];\n",
+        search.num_states
+    );
+    logic += &format!("\tsignal {p}search_accepted[num_bytes+1];\n");
+    logic += &format!("\tsignal {p}search_first[msg_bytes];\n");
+    logic += &format!("\t{p}search_states[0][0] <== 1;\n");
+    for state in 1..search.num_states {
+        logic += &format!("\t{p}search_states[0][{state}] <== 0;\n");
+    }
+    logic += &format!("\t{p}search_accepted[0] <== 0;\n");
+    logic += "\tfor (var i = 0; i < num_bytes; i++) {\n";
+    for ((min, max), range_idx) in ranges {
+        logic += &format!(
+            "\t\t{p}search_in_range[{range_idx}][i] <== {};\n",
+            range_check_expr(min, max)
+        );
+    }
+    logic += &transitions.concat();
+    for state in 0..search.num_states {
+        let incoming = search
+            .transitions
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, to, _))| *to == state)
+            .map(|(transition_idx, _)| format!("{p}search_transitions[{transition_idx}][i]"))
+            .collect::<Vec<_>>();
+        logic += &format!(
+            "\t\t{p}search_states[i+1][{state}] <== {};\n",
+            if incoming.is_empty() {
+                "0".to_string()
+            } else {
+                incoming.join(" + ")
+            }
+        );
+    }
+    let accepted = search
+        .accept_states
+        .iter()
+        .map(|state| format!("{p}search_states[i+1][{state}]"))
+        .collect::<Vec<_>>();
+    logic += &format!(
+        "\t\t{p}search_accepted[i+1] <== {};\n",
+        if accepted.is_empty() {
+            "0".to_string()
+        } else {
+            accepted.join(" + ")
+        }
+    );
+    logic += "\t}\n";
+    logic += &format!(
+        "\t{p}search_accepted[1] * (1 - {}) === 0;\n",
+        signals.state(accepted_state, "1")
+    );
+    logic += "\tfor (var i = 0; i < msg_bytes; i++) {\n";
+    logic += &format!(
+        "\t\t{p}search_first[i] <== {p}search_accepted[i+2] * (1 - {p}accepted_before[i]);\n"
+    );
+    logic += &format!(
+        "\t\t{p}search_first[i] * (1 - {}) === 0;\n",
+        signals.state(accepted_state, "i+2")
+    );
+    if occurrence == Occurrence::Unique {
+        logic += &format!("\t\t{p}search_accepted[i+2] * (1 - {p}in_match[i]) === 0;\n");
+    }
+    logic += "\t}\n";
+    logic
+}
+
+/// Writes the `from_zero_enabled` and `state_changed` signals used by the substring logic of a circuit with
+/// [`StateEncoding::Index`].
+///
+/// # Arguments
+///
+/// * `encoding` - A reference to the IndexEncoding of the circuit.
+///
+/// # Returns
+///
+/// A String containing the generated Circom code for the signals.
+fn write_index_substr_signals(encoding: &IndexEncoding) -> String {
+    let from_nonzero = (0..encoding.used_classes.len())
+        .filter_map(|class_i| Some((class_i, encoding.leaves_nonzero_expr(class_i)?)))
+        .collect::<Vec<_>>();
+
+    let mut logic = String::new();
+    logic += "\n";
+    logic += "\tsignal from_zero_enabled[num_bytes+1];\n";
+    if !from_nonzero.is_empty() {
+        logic += &format!(
+            "\tsignal from_nonzero[{}][num_bytes];\n",
+            from_nonzero.len()
+        );
+    }
+    logic += "\tsignal state_changed[num_bytes];\n";
+    logic += "\tfrom_zero_enabled[num_bytes] <== 0;\n";
+    logic += "\tfor (var i = 0; i < num_bytes; i++) {\n";
+    for (from_nonzero_i, (class_i, expr)) in from_nonzero.iter().enumerate() {
+        logic += &format!(
+            "\t\tfrom_nonzero[{from_nonzero_i}][i] <== byte_class[{class_i}][i] * ({expr});\n"
+        );
+    }
+    logic += &format!(
+        "\t\tfrom_zero_enabled[i] <== 1 - ({});\n",
+        if from_nonzero.is_empty() {
+            "0".to_string()
+        } else {
+            (0..from_nonzero.len())
+                .map(|from_nonzero_i| format!("from_nonzero[{from_nonzero_i}][i]"))
+                .collect::<Vec<_>>()
+                .join(" + ")
+        }
+    );
+    logic += "\t\tstate_changed[i] <== 1 - IsZero()(state[i+1]);\n";
+    logic += "\t}\n";
+    logic
+}
+
+/// Writes the `from_zero_enabled` and `state_changed` signals used by the substring logic of a circuit with a
+/// [`SearchMode`] other than [`SearchMode::Anywhere`], whose single run of the DFA never restarts.
+///
+/// `from_zero_enabled[i]` is `1` if the run takes a transition from the state `0` when reading `in[i]`, i.e., only
+/// where the run starts, so that the substring transitions from the state `0` are read there and no byte before
+/// the start continues the run.
+///
+/// # Arguments
+///
+/// * `num_states` - The number of states of the DFA.
+/// * `zero_transitions` - The indexes into the `transitions` signal of the transitions from the state `0`.
+///
+/// # Returns
+///
+/// A String containing the generated Circom code for the signals.
+fn write_anchored_substr_signals(num_states: usize, zero_transitions: &[usize]) -> String {
+    let mut logic = String::new();
+    logic += "\n";
+    logic += "\tsignal from_zero_enabled[num_bytes+1];\n";
+    logic += "\tcomponent state_changed[num_bytes];\n";
+    logic += "\tfrom_zero_enabled[num_bytes] <== 0;\n";
+    logic += "\tfor (var i = 0; i < num_bytes; i++) {\n";
+    logic += &format!(
+        "\t\tfrom_zero_enabled[i] <== {};\n",
+        if zero_transitions.is_empty() {
+            "0".to_string()
+        } else {
+            zero_transitions
+                .iter()
+                .map(|transition_idx| format!("transitions[{}][i]", transition_idx))
+                .collect::<Vec<_>>()
+                .join(" + ")
+        }
     );
+    logic += &format!("\t\tstate_changed[i] = MultiOR({});\n", num_states - 1);
+    for state in 1..num_states {
+        logic += &format!(
+            "\t\tstate_changed[i].in[{}] <== states[i+1][{state}];\n",
+            state - 1
+        );
+    }
     logic += "\t}\n";
     logic
 }
@@ -799,20 +1918,26 @@ fn write_consecutive_logic(accepted_state: usize) -> String {
 ///
 /// * `idx` - The index of the current substring.
 /// * `ranges` - A slice of references to tuples representing state transitions.
+/// * `signals` - The StateSignals read by the logic.
 ///
 /// # Returns
 ///
 /// A String containing the generated Circom code for previous states.
-fn write_prev_states(idx: usize, ranges: &[&(usize, usize)]) -> String {
+fn write_prev_states(idx: usize, ranges: &[&(usize, usize)], signals: &StateSignals) -> String {
+    let p = signals.prefix();
     let mut prev_states = String::new();
     for (trans_idx, &(cur, _)) in ranges.iter().enumerate() {
+        let cur_state = signals.state(*cur, "i+1");
         if *cur == 0 {
-            prev_states += &format!(
-                "\t\tprev_states{idx}[{trans_idx}][i] <== from_zero_enabled[i+1] * states[i+1][{cur}];\n"
-            );
+            let enabled = if cur_state == "1" {
+                format!("{p}from_zero_enabled[i+1]")
+            } else {
+                format!("{p}from_zero_enabled[i+1] * {cur_state}")
+            };
+            prev_states += &format!("\t\t{p}prev_states{idx}[{trans_idx}][i] <== {enabled};\n");
         } else {
             prev_states += &format!(
-                "\t\tprev_states{idx}[{trans_idx}][i] <== (1 - from_zero_enabled[i+1]) * states[i+1][{cur}];\n"
+                "\t\t{p}prev_states{idx}[{trans_idx}][i] <== (1 - {p}from_zero_enabled[i+1]) * {cur_state};\n"
             );
         }
     }
@@ -827,22 +1952,27 @@ fn write_prev_states(idx: usize, ranges: &[&(usize, usize)]) -> String {
 ///
 /// * `idx` - The index of the current substring.
 /// * `ranges` - A slice of references to tuples representing state transitions.
+/// * `signals` - The StateSignals read by the logic.
 ///
 /// # Returns
 ///
 /// A String containing the generated Circom code for substring logic.
-fn write_is_substr(idx: usize, ranges: &[&(usize, usize)]) -> String {
+fn write_is_substr(idx: usize, ranges: &[&(usize, usize)], signals: &StateSignals) -> String {
+    let p = signals.prefix();
     let multi_or_inputs = ranges
         .iter()
         .enumerate()
         .map(|(trans_idx, (_, next))| {
-            format!("prev_states{idx}[{trans_idx}][i] * states[i+2][{next}]")
+            format!(
+                "{p}prev_states{idx}[{trans_idx}][i] * {}",
+                signals.state(*next, "i+2")
+            )
         })
         .collect::<Vec<_>>()
         .join(", ");
 
     format!(
-        "\t\tis_substr{idx}[i] <== MultiOR({})([{multi_or_inputs}]);\n",
+        "\t\t{p}is_substr{idx}[i] <== MultiOR({})([{multi_or_inputs}]);\n",
         ranges.len()
     )
 }
@@ -854,16 +1984,27 @@ fn write_is_substr(idx: usize, ranges: &[&(usize, usize)]) -> String {
 /// # Arguments
 ///
 /// * `idx` - The index of the current substring.
+/// * `p` - The prefix of the signals.
+/// * `leftmost` - Whether the reveal is restricted by the `in_match` signals of a leftmost match policy.
+/// * `committed` - Whether the substring is committed, which omits the `reveal{idx}` output.
 ///
 /// # Returns
 ///
 /// A String containing the generated Circom code for reveal logic.
-fn write_is_reveal_and_reveal(idx: usize) -> String {
+fn write_is_reveal_and_reveal(idx: usize, p: &str, leftmost: bool, committed: bool) -> String {
     let mut reveal = String::new();
-    reveal += &format!(
-        "\t\tis_reveal{idx}[i] <== MultiAND(3)([out, is_substr{idx}[i], is_consecutive[i][2]]);\n"
-    );
-    reveal += &format!("\t\treveal{idx}[i] <== in[i+1] * is_reveal{idx}[i];\n");
+    if leftmost {
+        reveal += &format!(
+            "\t\t{p}is_reveal{idx}[i] <== MultiAND(4)([{p}out, {p}is_substr{idx}[i], {p}is_consecutive[i][2], {p}in_match[i]]);\n"
+        );
+    } else {
+        reveal += &format!(
+            "\t\t{p}is_reveal{idx}[i] <== MultiAND(3)([{p}out, {p}is_substr{idx}[i], {p}is_consecutive[i][2]]);\n"
+        );
+    }
+    if !committed {
+        reveal += &format!("\t\t{p}reveal{idx}[i] <== in[i+1] * {p}is_reveal{idx}[i];\n");
+    }
     reveal
 }
 
@@ -875,16 +2016,31 @@ fn write_is_reveal_and_reveal(idx: usize) -> String {
 ///
 /// * `idx` - The index of the current substring.
 /// * `ranges` - A slice of tuples representing state transitions.
+/// * `signals` - The StateSignals read by the logic.
+/// * `leftmost` - Whether the reveal is restricted by the `in_match` signals of a leftmost match policy.
+/// * `committed` - Whether the substring is committed, which omits the `reveal{idx}` output.
 ///
 /// # Returns
 ///
 /// A String containing the generated Circom code for the complete substring logic.
-fn write_substr_logic(idx: usize, ranges: &[(usize, usize)]) -> String {
+fn write_substr_logic(
+    idx: usize,
+    ranges: &[(usize, usize)],
+    signals: &StateSignals,
+    leftmost: bool,
+    committed: bool,
+) -> String {
+    let p = signals.prefix();
     let mut logic = String::new();
-    logic += &format!("\tsignal prev_states{idx}[{}][msg_bytes];\n", ranges.len());
-    logic += &format!("\tsignal is_substr{idx}[msg_bytes];\n");
-    logic += &format!("\tsignal is_reveal{idx}[msg_bytes];\n");
-    logic += &format!("\tsignal output reveal{idx}[msg_bytes];\n");
+    logic += &format!(
+        "\tsignal {p}prev_states{idx}[{}][msg_bytes];\n",
+        ranges.len()
+    );
+    logic += &format!("\tsignal {p}is_substr{idx}[msg_bytes];\n");
+    logic += &format!("\tsignal {p}is_reveal{idx}[msg_bytes];\n");
+    if !committed {
+        logic += &format!("\tsignal output {p}reveal{idx}[msg_bytes];\n");
+    }
     logic += "\tfor (var i = 0; i < msg_bytes; i++) {\n";
 
     let sorted_ranges = sort_ranges(ranges);
@@ -893,9 +2049,9 @@ fn write_substr_logic(idx: usize, ranges: &[(usize, usize)]) -> String {
         sorted_ranges
     );
 
-    logic += &write_prev_states(idx, &sorted_ranges);
-    logic += &write_is_substr(idx, &sorted_ranges);
-    logic += &write_is_reveal_and_reveal(idx);
+    logic += &write_prev_states(idx, &sorted_ranges, signals);
+    logic += &write_is_substr(idx, &sorted_ranges, signals);
+    logic += &write_is_reveal_and_reveal(idx, p, leftmost, committed);
 
     logic += "\t}\n";
     logic
@@ -922,93 +2078,1998 @@ fn sort_ranges(ranges: &[(usize, usize)]) -> Vec<&(usize, usize)> {
 ///
 /// # Arguments
 ///
-/// * `regex_dfa` - A reference to the RegexAndDFA struct containing the DFA and substring information.
+/// * `circuit` - A reference to the RegexCircuit containing the reveal masks.
+/// * `options` - A reference to the CircomOptions.
 ///
 /// # Returns
 ///
 /// A Result containing the generated Circom code as a String, or a CompilerError.
-fn add_substrs_constraints(regex_dfa: &RegexAndDFA) -> Result<String, CompilerError> {
-    let accepted_state =
-        get_accepted_state(&regex_dfa.dfa).ok_or(CompilerError::NoAcceptedState)?;
+fn add_substrs_constraints(
+    circuit: &RegexCircuit,
+    options: &CircomOptions,
+) -> Result<String, CompilerError> {
+    let encoding =
+        (options.state_encoding == StateEncoding::Index).then(|| IndexEncoding::new(circuit));
     let mut circom = String::new();
 
-    circom += &write_consecutive_logic(accepted_state);
-
-    circom += &format!(
-        "\t// substrings calculated: {:?}\n",
-        regex_dfa.substrings.substring_ranges
-    );
-
-    for (idx, ranges) in regex_dfa.substrings.substring_ranges.iter().enumerate() {
-        circom += &write_substr_logic(idx, &ranges.iter().copied().collect::<Vec<_>>());
+    if let Some(encoding) = &encoding {
+        circom += &write_index_substr_signals(encoding);
     }
+    circom += &write_reveal_logic(circuit, &StateSignals::of(encoding.as_ref()), options)?;
 
     circom += "}";
     Ok(circom)
 }
 
-/// Generates a Circom template file for the given regex and DFA.
+/// Writes the `reveal{idx}_found`, `reveal{idx}_start`, `reveal{idx}_len` and `reveal{idx}_packed` outputs of a
+/// reveal with a `max_len`, which describe its first revealed substring.
 ///
-/// This function creates a Circom file containing the circuit logic for the regex matcher.
+/// The substring starts at a revealed substring transition from one of the start states of its boundaries:
+/// `reveal{idx}_starts_here[i]` is `1` at the first such byte, and `reveal{idx}_in_first[i]` is `1` while the
+/// revealed bytes continue from it in the same run of the DFA, so that a substring revealed right after it by a run
+/// restarting from the state `0` is not appended to it. `reveal{idx}_found` is `1` if a substring is revealed.
+/// `reveal{idx}_packed[j]` selects the byte `j` positions after the start with one product per position, so the
+/// outputs cost `msg_bytes * max_len` constraints, and the length is constrained to be at most `max_len`.
+///
+/// If the substring is committed, these signals are not outputs, and `reveal{idx}_commitment` is output instead:
+/// the Poseidon hash of the packed bytes and the `reveal{idx}_salt` input, computed by `CommitBytes`.
 ///
 /// # Arguments
 ///
-/// * `regex_and_dfa` - A reference to the RegexAndDFA struct containing the regex and DFA information.
-/// * `circom_path` - The path where the generated Circom file should be saved.
-/// * `template_name` - The name of the Circom template.
-/// * `gen_substrs` - A boolean indicating whether to generate substring constraints.
+/// * `idx` - The index of the current substring.
+/// * `max_len` - The length of the packed output.
+/// * `p` - The prefix of the signals.
+/// * `start_transitions` - The signals that are `1` if `msg[i]` is read by a substring transition from a start
+///   state.
+/// * `restart` - The signal that is `1` if the run of the DFA restarts from the state `0` at `msg[i]`, or `None` if
+///   the DFA never restarts.
+/// * `committed` - Whether the substring is committed instead of revealed.
+/// * `packed_reveals` - Whether to also output `reveal{idx}_fields`, the packed bytes in field elements, if the
+///   substring is not committed.
 ///
 /// # Returns
 ///
-/// A Result indicating success or a CompilerError.
-pub(crate) fn gen_circom_template(
-    regex_and_dfa: &RegexAndDFA,
-    circom_path: &Path,
-    template_name: &str,
-    gen_substrs: bool,
-) -> Result<(), CompilerError> {
-    let circom = gen_circom_allstr(
-        &regex_and_dfa.dfa,
-        template_name,
-        &regex_and_dfa.regex_pattern,
-        regex_and_dfa.has_end_anchor,
-    )?;
+/// A String containing the generated Circom code for the outputs.
+fn write_packed_reveal_logic(
+    idx: usize,
+    max_len: usize,
+    p: &str,
+    start_transitions: &[String],
+    restart: Option<&str>,
+    committed: bool,
+    packed_reveals: bool,
+) -> String {
+    let r = format!("{p}reveal{idx}");
+    let output = if committed { "" } else { "output " };
+    let from_start = match start_transitions {
+        [] => "0".to_string(),
+        [transition] => transition.clone(),
+        _ => format!(
+            "MultiOR({})([{}])",
+            start_transitions.len(),
+            start_transitions.join(", ")
+        ),
+    };
+    let mut logic = String::new();
+    logic += &format!("\tsignal {r}_from_start[msg_bytes];\n");
+    logic += &format!("\tsignal {r}_starts_at[msg_bytes];\n");
+    logic += &format!("\tsignal {r}_starts_here[msg_bytes];\n");
+    logic += &format!("\tsignal {r}_continues[msg_bytes];\n");
+    logic += &format!("\tsignal {r}_in_first[msg_bytes];\n");
+    logic += &format!("\tsignal {r}_bytes[msg_bytes+{max_len}];\n");
+    logic += &format!("\tsignal {r}_terms[{max_len}][msg_bytes];\n");
+    logic += &format!("\tsignal {r}_len_check;\n");
+    logic += &format!("\tsignal {output}{r}_found;\n");
+    logic += &format!("\tsignal {output}{r}_start;\n");
+    logic += &format!("\tsignal {output}{r}_len;\n");
+    logic += &format!("\tsignal {output}{r}_packed[{max_len}];\n");
+    logic += "\tfor (var i = 0; i < msg_bytes; i++) {\n";
+    logic += &format!("\t\t{r}_from_start[i] <== {from_start};\n");
+    logic += &format!("\t\t{r}_starts_at[i] <== {p}is_reveal{idx}[i] * {r}_from_start[i];\n");
+    logic += "\t}\n";
+    logic += &format!("\t{r}_starts_here[0] <== {r}_starts_at[0];\n");
+    logic += &format!("\t{r}_continues[0] <== 0;\n");
+    logic += &format!("\t{r}_in_first[0] <== {r}_starts_here[0];\n");
+    logic += &format!("\tvar {r}_seen = {r}_starts_here[0];\n");
+    logic += "\tfor (var i = 1; i < msg_bytes; i++) {\n";
+    logic += &format!("\t\t{r}_starts_here[i] <== {r}_starts_at[i] * (1 - {r}_seen);\n");
+    logic += &format!("\t\t{r}_continues[i] <== {r}_in_first[i-1] * {p}is_reveal{idx}[i];\n");
+    logic += &match restart {
+        Some(restart) => format!(
+            "\t\t{r}_in_first[i] <== {r}_starts_here[i] + {r}_continues[i] * (1 - {restart});\n"
+        ),
+        None => format!("\t\t{r}_in_first[i] <== {r}_starts_here[i] + {r}_continues[i];\n"),
+    };
+    logic += &format!("\t\t{r}_seen += {r}_starts_here[i];\n");
+    logic += "\t}\n";
+    logic += &format!("\t{r}_found <== {r}_seen;\n");
+    logic += &format!("\tvar {r}_start_sum = 0;\n");
+    logic += &format!("\tvar {r}_len_sum = 0;\n");
+    logic += "\tfor (var i = 0; i < msg_bytes; i++) {\n";
+    logic += &format!("\t\t{r}_start_sum += i * {r}_starts_here[i];\n");
+    logic += &format!("\t\t{r}_len_sum += {r}_in_first[i];\n");
+    logic += &format!("\t\t{r}_bytes[i] <== in[i+1] * {r}_in_first[i];\n");
+    logic += "\t}\n";
+    logic += &format!("\tfor (var i = msg_bytes; i < msg_bytes+{max_len}; i++) {{\n");
+    logic += &format!("\t\t{r}_bytes[i] <== 0;\n");
+    logic += "\t}\n";
+    logic += &format!("\t{r}_start <== {r}_start_sum;\n");
+    logic += &format!("\t{r}_len <== {r}_len_sum;\n");
+    logic += &format!("\t{r}_len_check <== LessEqThan(32)([{r}_len, {max_len}]);\n");
+    logic += &format!("\t{r}_len_check === 1;\n");
+    logic += &format!("\tfor (var j = 0; j < {max_len}; j++) {{\n");
+    logic += &format!("\t\tvar {r}_packed_sum = 0;\n");
+    logic += "\t\tfor (var i = 0; i < msg_bytes; i++) {\n";
+    logic += &format!("\t\t\t{r}_terms[j][i] <== {r}_starts_here[i] * {r}_bytes[i+j];\n");
+    logic += &format!("\t\t\t{r}_packed_sum += {r}_terms[j][i];\n");
+    logic += "\t\t}\n";
+    logic += &format!("\t\t{r}_packed[j] <== {r}_packed_sum;\n");
+    logic += "\t}\n";
+    if committed {
+        logic += &format!("\tsignal input {r}_salt;\n");
+        logic += &format!("\tsignal output {r}_commitment;\n");
+        logic += &format!("\t{r}_commitment <== CommitBytes({max_len})({r}_packed, {r}_salt);\n");
+    } else if packed_reveals {
+        let num_fields = max_len.div_ceil(MAX_BYTES_IN_FIELD);
+        logic += &format!("\tsignal output {r}_fields[{num_fields}];\n");
+        logic += &format!("\t{r}_fields <== PackBytes({max_len})({r}_packed);\n");
+    }
+    logic
+}
 
-    let mut file = File::create(circom_path)?;
-    file.write_all(circom.as_bytes())?;
+/// Writes the constraints that two substrings with a `max_len` are equal, i.e., that they have the same length and
+/// the same packed bytes up to the smaller `max_len`, beyond which both are zero.
+///
+/// # Arguments
+///
+/// * `a` - The prefix of the signals, the circuit and the index of the first substring.
+/// * `b` - The prefix of the signals, the circuit and the index of the second substring.
+///
+/// # Returns
+///
+/// A Result containing the generated Circom code as a String, or a CompilerError if a substring has no `max_len`.
+fn write_equal_logic(
+    a: (&str, &RegexCircuit, usize),
+    b: (&str, &RegexCircuit, usize),
+) -> Result<String, CompilerError> {
+    let max_len = |(_, circuit, idx): (&str, &RegexCircuit, usize)| {
+        circuit
+            .reveals
+            .get(idx)
+            .and_then(|reveal| reveal.max_len)
+            .ok_or_else(|| {
+                CompilerError::GenericError(format!(
+                    "The substring {} constrained to be equal has no max_len",
+                    idx
+                ))
+            })
+    };
+    let max_len = max_len(a)?.min(max_len(b)?);
+    let ra = format!("{}reveal{}", a.0, a.2);
+    let rb = format!("{}reveal{}", b.0, b.2);
+    let mut logic = format!("\t// {ra} == {rb}\n");
+    logic += &format!("\t{ra}_len === {rb}_len;\n");
+    logic += &format!("\tfor (var j = 0; j < {max_len}; j++) {{\n");
+    logic += &format!("\t\t{ra}_packed[j] === {rb}_packed[j];\n");
+    logic += "\t}\n";
+    Ok(logic)
+}
 
-    if gen_substrs {
-        let substrs = add_substrs_constraints(regex_and_dfa)?;
-        file.write_all(substrs.as_bytes())?;
+/// Checks that the equality constraints, if any, have the substring constraints, which compute the compared
+/// signals.
+fn check_equalities(gen_substrs: bool, has_equalities: bool) -> Result<(), CompilerError> {
+    if has_equalities && !gen_substrs {
+        return Err(CompilerError::GenericError(
+            "The equality constraints require the substring constraints".to_string(),
+        ));
     }
-
-    file.flush()?;
     Ok(())
 }
 
-/// Generates a Circom circuit as a string for the given regex and DFA.
+/// Writes the `reveal{idx}_value` output of a substring with the uint output, which constrains the packed bytes
+/// within its length to ASCII digits, or also a single decimal point followed by at most `decimals` digits if
+/// `decimals` is positive, and computes its value scaled by `10 ** decimals`.
 ///
-/// This function creates a string containing the Circom circuit logic for the regex matcher.
+/// # Arguments
+///
+/// * `idx` - The index of the current substring.
+/// * `max_len` - The length of the packed bytes.
+/// * `decimals` - The number of decimals.
+/// * `p` - The prefix of the signals.
+///
+/// # Returns
+///
+/// A String containing the generated Circom code for the output.
+fn write_uint_logic(idx: usize, max_len: usize, decimals: usize, p: &str) -> String {
+    let r = format!("{p}reveal{idx}");
+    let mut logic = String::new();
+    logic += &format!("\tsignal {r}_in_part[{max_len}];\n");
+    logic += &format!("\tsignal {r}_is_digit[{max_len}];\n");
+    logic += &format!("\tsignal {r}_digit[{max_len}];\n");
+    logic += &format!("\tsignal {r}_step[{max_len}];\n");
+    logic += &format!("\tsignal {r}_acc[{max_len}+1];\n");
+    if decimals > 0 {
+        logic += &format!("\tsignal {r}_is_dot[{max_len}];\n");
+        logic += &format!("\tsignal {r}_dot[{max_len}];\n");
+        logic += &format!("\tsignal {r}_after_dot[{max_len}+1];\n");
+        logic += &format!("\tsignal {r}_decimal[{max_len}];\n");
+        logic += &format!("\tsignal {r}_num_decimals;\n");
+        logic += &format!("\tsignal {r}_decimals_check;\n");
+        logic += &format!("\tsignal {r}_is_scale[{}];\n", decimals + 1);
+    }
+    logic += &format!("\tsignal output {r}_value;\n");
+    logic += &format!("\t{r}_acc[0] <== 0;\n");
+    if decimals > 0 {
+        logic += &format!("\t{r}_after_dot[0] <== 0;\n");
+        logic += &format!("\tvar {r}_decimals_sum = 0;\n");
+    }
+    logic += &format!("\tfor (var j = 0; j < {max_len}; j++) {{\n");
+    logic += &format!("\t\t{r}_in_part[j] <== LessThan(8)([j, {r}_len]);\n");
+    logic += &format!(
+        "\t\t{r}_is_digit[j] <== AND()(LessEqThan(8)([48, {r}_packed[j]]), LessEqThan(8)([{r}_packed[j], 57]));\n"
+    );
+    logic += &format!("\t\t{r}_digit[j] <== {r}_in_part[j] * {r}_is_digit[j];\n");
+    if decimals > 0 {
+        logic += &format!("\t\t{r}_is_dot[j] <== IsEqual()([{r}_packed[j], 46]);\n");
+        logic += &format!("\t\t{r}_in_part[j] * (1 - {r}_is_digit[j] - {r}_is_dot[j]) === 0;\n");
+        logic += &format!("\t\t{r}_dot[j] <== {r}_in_part[j] * {r}_is_dot[j];\n");
+        logic += &format!("\t\t{r}_after_dot[j+1] <== {r}_after_dot[j] + {r}_dot[j];\n");
+        logic += &format!("\t\t{r}_decimal[j] <== {r}_digit[j] * {r}_after_dot[j];\n");
+        logic += &format!("\t\t{r}_decimals_sum += {r}_decimal[j];\n");
+    } else {
+        logic += &format!("\t\t{r}_in_part[j] === {r}_digit[j];\n");
+    }
+    logic +=
+        &format!("\t\t{r}_step[j] <== {r}_digit[j] * (9 * {r}_acc[j] + {r}_packed[j] - 48);\n");
+    logic += &format!("\t\t{r}_acc[j+1] <== {r}_acc[j] + {r}_step[j];\n");
+    logic += "\t}\n";
+    if decimals == 0 {
+        logic += &format!("\t{r}_value <== {r}_acc[{max_len}];\n");
+        return logic;
+    }
+    // At most one decimal point, followed by at most `decimals` digits, which determine the scale of the value.
+    logic += &format!("\t{r}_after_dot[{max_len}] * ({r}_after_dot[{max_len}] - 1) === 0;\n");
+    logic += &format!("\t{r}_num_decimals <== {r}_decimals_sum;\n");
+    logic += &format!("\t{r}_decimals_check <== LessEqThan(8)([{r}_num_decimals, {decimals}]);\n");
+    logic += &format!("\t{r}_decimals_check === 1;\n");
+    let mut scale = vec![];
+    for k in 0..=decimals {
+        logic += &format!("\t{r}_is_scale[{k}] <== IsEqual()([{r}_num_decimals, {k}]);\n");
+        scale.push(format!("{r}_is_scale[{k}] * 1{}", "0".repeat(decimals - k)));
+    }
+    logic += &format!(
+        "\t{r}_value <== {r}_acc[{max_len}] * ({});\n",
+        scale.join(" + ")
+    );
+    logic
+}
+
+/// Writes the consecutive logic and the substring logic of every reveal of the circuit.
 ///
 /// # Arguments
 ///
-/// * `regex_and_dfa` - A reference to the RegexAndDFA struct containing the regex and DFA information.
-/// * `template_name` - The name of the Circom template.
+/// * `circuit` - A reference to the RegexCircuit containing the reveal masks.
+/// * `signals` - The StateSignals read by the logic.
+/// * `options` - A reference to the CircomOptions. The first occurrence of the greedy match policy is the first
+///   occurrence of [`MatchPolicy::LeftmostLongest`].
 ///
 /// # Returns
 ///
 /// A Result containing the generated Circom code as a String, or a CompilerError.
-pub(crate) fn gen_circom_string(
-    regex_and_dfa: &RegexAndDFA,
-    template_name: &str,
+fn write_reveal_logic(
+    circuit: &RegexCircuit,
+    signals: &StateSignals,
+    options: &CircomOptions,
 ) -> Result<String, CompilerError> {
-    let circom = gen_circom_allstr(
-        &regex_and_dfa.dfa,
-        template_name,
-        &regex_and_dfa.regex_pattern,
-        regex_and_dfa.has_end_anchor,
-    )?;
-    let substrs = add_substrs_constraints(regex_and_dfa)?;
-    let result = circom + &substrs;
-    Ok(result)
+    let occurrence = options.occurrence;
+    let accepted_state = *circuit
+        .accept_states
+        .first()
+        .ok_or(CompilerError::NoAcceptedState)?;
+    let mut circom = write_consecutive_logic(accepted_state, signals);
+    let policy = match circuit.match_policy {
+        MatchPolicy::Greedy if occurrence != Occurrence::Any => MatchPolicy::LeftmostLongest,
+        policy => policy,
+    };
+    let leftmost = !policy.is_greedy();
+    if leftmost {
+        circom += &write_leftmost_logic(accepted_state, policy, signals);
+    }
+    if occurrence != Occurrence::Any {
+        circom += &write_occurrence_logic(
+            &SearchDfa::new(circuit)?,
+            accepted_state,
+            occurrence,
+            signals,
+        );
+    }
+
+    circom += &format!(
+        "\t// substrings calculated: {:?}\n",
+        circuit
+            .reveals
+            .iter()
+            .map(|reveal| &reveal.transitions)
+            .collect::<Vec<_>>()
+    );
+
+    for (idx, reveal) in circuit.reveals.iter().enumerate() {
+        circom += &write_substr_logic(
+            idx,
+            &reveal.transitions.iter().copied().collect::<Vec<_>>(),
+            signals,
+            leftmost,
+            reveal.committed,
+        );
+        if let Some(max_len) = reveal.max_len {
+            let p = signals.prefix();
+            let ranges = reveal.transitions.iter().collect::<Vec<_>>();
+            let start_transitions = reveal
+                .start_transitions()?
+                .into_iter()
+                .map(|trans_idx| {
+                    format!(
+                        "{p}prev_states{idx}[{trans_idx}][i] * {}",
+                        signals.state(ranges[trans_idx].1, "i+2")
+                    )
+                })
+                .collect::<Vec<_>>();
+            circom += &write_packed_reveal_logic(
+                idx,
+                max_len,
+                p,
+                &start_transitions,
+                Some(&format!("{p}from_zero_enabled[i+1]")),
+                reveal.committed,
+                options.packed_reveals,
+            );
+            if let Some(decimals) = reveal.uint_decimals {
+                circom += &write_uint_logic(idx, max_len, decimals, signals.prefix());
+            }
+        }
+    }
+    for &(a, b) in &circuit.equalities {
+        let p = signals.prefix();
+        circom += &write_equal_logic((p, circuit, a), (p, circuit, b))?;
+    }
+
+    Ok(circom)
+}
+
+/// Checks that the packed reveals of the options, if any, have the substring constraints and a reveal with a
+/// `max_len` to pack, which `has_max_len` tells.
+fn check_packed_reveals(
+    options: &CircomOptions,
+    gen_substrs: bool,
+    has_max_len: bool,
+) -> Result<(), CompilerError> {
+    if options.packed_reveals && !(gen_substrs && has_max_len) {
+        return Err(CompilerError::GenericError(
+            "The packed reveals require the substring constraints and a public part with a max_len"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The Circom backend, which generates a Circom template from a RegexCircuit.
+pub struct CircomBackend<'a> {
+    /// The name of the Circom template.
+    pub template_name: &'a str,
+    /// Whether to generate the substring constraints and the `reveal{idx}` outputs.
+    pub gen_substrs: bool,
+    pub options: CircomOptions,
+    /// The byte predicates computed by a shared template, whose `pred` signals the template takes as inputs
+    /// along with `in` instead of `msg`. They must contain the predicates of the circuit.
+    pub shared_predicates: Option<&'a [BytePredicate]>,
+}
+
+impl CircomBackend<'_> {
+    /// Returns the CircomStats of the circuit generated with the options of the backend.
+    pub fn stats(&self, circuit: &RegexCircuit) -> CircomStats {
+        generate_state_transition_logic(circuit, &self.options, self.shared_predicates).0
+    }
+}
+
+impl Backend for CircomBackend<'_> {
+    type Output = String;
+
+    fn generate(&self, circuit: &RegexCircuit) -> Result<String, CompilerError> {
+        check_packed_reveals(
+            &self.options,
+            self.gen_substrs,
+            circuit
+                .reveals
+                .iter()
+                .any(|reveal| reveal.max_len.is_some()),
+        )?;
+        check_equalities(self.gen_substrs, !circuit.equalities.is_empty())?;
+        // With shared predicates, the file of the templates includes the helpers.
+        let committed = self.gen_substrs
+            && self.shared_predicates.is_none()
+            && circuit.reveals.iter().any(|reveal| reveal.committed);
+        if self.options.negate {
+            if self.shared_predicates.is_some()
+                || self.options.alphabet_classes
+                || self.options.one_hot
+                || self.options.state_encoding != StateEncoding::Vector
+                || self.options.search_mode != SearchMode::Anywhere
+                || self.options.occurrence != Occurrence::Any
+                || self.options.packed_reveals
+                || !circuit.equalities.is_empty()
+            {
+                return Err(CompilerError::GenericError(
+                    "The negative match mode cannot be combined with the other Circom options"
+                        .to_string(),
+                ));
+            }
+            return Ok(gen_circom_negated(
+                &NegatedDfa::new(circuit)?,
+                &circuit.regex,
+                self.template_name,
+                self.options.packed_msg,
+            ));
+        }
+        if self.options.search_mode != SearchMode::Anywhere {
+            if self.shared_predicates.is_some()
+                || self.options.alphabet_classes
+                || self.options.one_hot
+                || self.options.state_encoding != StateEncoding::Vector
+                || self.options.occurrence != Occurrence::Any
+            {
+                return Err(CompilerError::GenericError(
+                    "The anchored search modes cannot be combined with the other Circom options"
+                        .to_string(),
+                ));
+            }
+            return Ok(include_commit_helpers(
+                gen_circom_anchored(circuit, self.template_name, self.gen_substrs, &self.options)?,
+                committed,
+            ));
+        }
+        if self.shared_predicates.is_some()
+            && (self.options.alphabet_classes
+                || self.options.state_encoding != StateEncoding::Vector)
+        {
+            return Err(CompilerError::GenericError(
+                "Shared predicates require the vector state encoding without alphabet classes"
+                    .to_string(),
+            ));
+        }
+        if self.options.occurrence != Occurrence::Any && !self.gen_substrs {
+            return Err(CompilerError::GenericError(
+                "The occurrence constraints require the substring constraints".to_string(),
+            ));
+        }
+        let mut circom = gen_circom_allstr(
+            circuit,
+            self.template_name,
+            &self.options,
+            self.shared_predicates,
+        )?;
+        if self.gen_substrs {
+            circom += &add_substrs_constraints(circuit, &self.options)?;
+        }
+        Ok(include_commit_helpers(circom, committed))
+    }
+}
+
+/// Generates a Circom template file for the given regex and DFA.
+///
+/// This function creates a Circom file containing the circuit logic for the regex matcher.
+///
+/// # Arguments
+///
+/// * `regex_and_dfa` - A reference to the RegexAndDFA struct containing the regex and DFA information.
+/// * `circom_path` - The path where the generated Circom file should be saved.
+/// * `template_name` - The name of the Circom template.
+/// * `gen_substrs` - A boolean indicating whether to generate substring constraints.
+/// * `options` - A reference to the CircomOptions.
+///
+/// # Returns
+///
+/// A Result indicating success or a CompilerError.
+pub(crate) fn gen_circom_template(
+    regex_and_dfa: &RegexAndDFA,
+    circom_path: &Path,
+    template_name: &str,
+    gen_substrs: bool,
+    options: &CircomOptions,
+) -> Result<(), CompilerError> {
+    let circom = CircomBackend {
+        template_name,
+        gen_substrs,
+        options: *options,
+        shared_predicates: None,
+    }
+    .generate(&RegexCircuit::new(regex_and_dfa))?;
+
+    let mut file = File::create(circom_path)?;
+    file.write_all(circom.as_bytes())?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Generates a Circom circuit as a string for the given regex and DFA.
+///
+/// This function creates a string containing the Circom circuit logic for the regex matcher.
+///
+/// # Arguments
+///
+/// * `regex_and_dfa` - A reference to the RegexAndDFA struct containing the regex and DFA information.
+/// * `template_name` - The name of the Circom template.
+///
+/// # Returns
+///
+/// A Result containing the generated Circom code as a String, or a CompilerError.
+pub(crate) fn gen_circom_string(
+    regex_and_dfa: &RegexAndDFA,
+    template_name: &str,
+) -> Result<String, CompilerError> {
+    gen_circom_string_with_options(regex_and_dfa, template_name, &CircomOptions::default())
+}
+
+/// Generates a Circom circuit with the substring constraints as a string for the given regex and DFA with the
+/// given options.
+///
+/// # Arguments
+///
+/// * `regex_and_dfa` - A reference to the RegexAndDFA struct containing the regex and DFA information.
+/// * `template_name` - The name of the Circom template.
+/// * `options` - A reference to the CircomOptions.
+///
+/// # Returns
+///
+/// A Result containing the generated Circom code as a String, or a CompilerError.
+pub(crate) fn gen_circom_string_with_options(
+    regex_and_dfa: &RegexAndDFA,
+    template_name: &str,
+    options: &CircomOptions,
+) -> Result<String, CompilerError> {
+    CircomBackend {
+        template_name,
+        gen_substrs: !options.negate,
+        options: *options,
+        shared_predicates: None,
+    }
+    .generate(&RegexCircuit::new(regex_and_dfa))
+}
+
+/// Generates the template that computes the range-checked `in` signals and the byte predicates of several
+/// regexes over the same message once per position.
+///
+/// # Arguments
+///
+/// * `template_name` - The name of the Circom template.
+/// * `predicates` - The byte predicates of the `pred` output signals.
+/// * `packed_msg` - Whether `msg` is unpacked from the `msg_packed` input.
+///
+/// # Returns
+///
+/// A String containing the generated Circom template.
+fn gen_shared_predicates_template(
+    template_name: &str,
+    predicates: &[BytePredicate],
+    packed_msg: bool,
+) -> String {
+    let mut lines = vec![format!("template {}(msg_bytes) {{", template_name)];
+    lines.extend(generate_msg_input(packed_msg));
+    lines.extend([
+        "\tvar num_bytes = msg_bytes+1;".to_string(),
+        "\tsignal output in[num_bytes];".to_string(),
+        format!("\tsignal output pred[{}][num_bytes];", predicates.len()),
+        "".to_string(),
+        "\tsignal in_range_checks[msg_bytes];".to_string(),
+        "\tin[0]<==255;".to_string(),
+        "\tfor (var i = 0; i < msg_bytes; i++) {".to_string(),
+        "\t\tin_range_checks[i] <== LessThan(8)([msg[i], 255]);".to_string(),
+        "\t\tin_range_checks[i] === 1;".to_string(),
+        "\t\tin[i+1] <== msg[i];".to_string(),
+        "\t}".to_string(),
+        "".to_string(),
+        "\tfor (var i = 0; i < num_bytes; i++) {".to_string(),
+    ]);
+    for (pred_i, predicate) in predicates.iter().enumerate() {
+        lines.push(match predicate {
+            BytePredicate::Eq(byte) => {
+                format!("\t\tpred[{pred_i}][i] <== IsEqual()([in[i], {byte}]);")
+            }
+            BytePredicate::Range(min, max) => format!(
+                "\t\tpred[{pred_i}][i] <== AND()(LessEqThan(8)([{min}, in[i]]), LessEqThan(8)([in[i], {max}]));"
+            ),
+        });
+    }
+    lines.push("\t}".to_string());
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+/// Returns the distinct byte predicates of the circuits in the order of their first use, which are the `pred`
+/// outputs of the shared predicate template.
+fn collect_shared_predicates(circuits: &[(String, RegexCircuit)]) -> Vec<BytePredicate> {
+    let mut predicates = vec![];
+    for predicate in circuits
+        .iter()
+        .flat_map(|(_, circuit)| circuit.predicates.iter())
+    {
+        if !predicates.contains(predicate) {
+            predicates.push(*predicate);
+        }
+    }
+    predicates
+}
+
+/// Generates the Circom templates of several regexes over the same message, whose byte predicates are computed
+/// once per position by a shared template.
+///
+/// Each regex template takes the `in` and `pred` outputs of the shared template as inputs instead of `msg`.
+///
+/// # Arguments
+///
+/// * `circuits` - The template names and circuits of the regexes.
+/// * `predicates_template_name` - The name of the shared predicate template.
+/// * `gen_substrs` - A boolean indicating whether to generate substring constraints.
+/// * `options` - A reference to the CircomOptions.
+///
+/// # Returns
+///
+/// A Result containing the generated Circom code as a String, or a CompilerError.
+pub(crate) fn gen_circom_shared(
+    circuits: &[(String, RegexCircuit)],
+    predicates_template_name: &str,
+    gen_substrs: bool,
+    options: &CircomOptions,
+) -> Result<String, CompilerError> {
+    let predicates = collect_shared_predicates(circuits);
+    let mut templates = vec![
+        format!("pragma circom 2.1.5;\n\n{REGEX_HELPERS_INCLUDE}"),
+        gen_shared_predicates_template(predicates_template_name, &predicates, options.packed_msg),
+    ];
+    for (template_name, circuit) in circuits {
+        let mut template = CircomBackend {
+            template_name,
+            gen_substrs,
+            options: *options,
+            shared_predicates: Some(&predicates),
+        }
+        .generate(circuit)?;
+        // Only the substring constraints close the template.
+        if !gen_substrs {
+            template += "\n}";
+        }
+        templates.push(template);
+    }
+    let committed = gen_substrs
+        && circuits
+            .iter()
+            .flat_map(|(_, circuit)| &circuit.reveals)
+            .any(|reveal| reveal.committed);
+    Ok(include_commit_helpers(templates.join("\n\n"), committed))
+}
+
+/// Returns the prefix of the signals of a regex in a combined template, i.e., its name with every character that
+/// is not allowed in a Circom identifier replaced by `_`, followed by `_`.
+fn signal_prefix(name: &str) -> String {
+    let mut prefix = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if !prefix.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        prefix.insert(0, 'r');
+    }
+    prefix + "_"
+}
+
+/// Returns the sum of the `states[idx]` signals of the given product states, or `0` if there is none.
+fn states_sum(states: &[usize], idx: &str) -> String {
+    if states.is_empty() {
+        return "0".to_string();
+    }
+    states
+        .iter()
+        .map(|state| format!("states[{}][{}]", idx, state))
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+/// Writes the signals of a regex of a product automaton as linear combinations of the product states.
+///
+/// `{p}states[i][k]` is the sum of the product states whose state of the regex is `k`. If `gen_substrs` is true,
+/// `{p}from_zero_enabled` and `{p}state_changed` are also written for the reveal logic.
+///
+/// # Arguments
+///
+/// * `product` - A reference to the ProductAutomaton.
+/// * `regex_idx` - The index of the regex.
+/// * `p` - The prefix of the signals of the regex.
+/// * `gen_substrs` - A boolean indicating whether to generate the signals of the substring constraints.
+///
+/// # Returns
+///
+/// A String containing the generated Circom code.
+fn write_projection_signals(
+    product: &ProductAutomaton,
+    regex_idx: usize,
+    p: &str,
+    gen_substrs: bool,
+) -> String {
+    let state_len = product.circuits[regex_idx].num_states;
+    let mut circom = String::new();
+    circom += &format!("\tsignal {p}states[num_bytes+1][{state_len}];\n");
+    circom += "\tfor (var i = 0; i <= num_bytes; i++) {\n";
+    circom += &format!("\t\t{p}states[i][0] <== 1;\n");
+    for state in 1..state_len {
+        let sources = product.states_where(regex_idx, |component, _| component == state);
+        circom += &format!(
+            "\t\t{p}states[i][{state}] <== {};\n",
+            states_sum(&sources, "i")
+        );
+    }
+    circom += "\t}\n";
+
+    if gen_substrs {
+        let continued = product.states_where(regex_idx, |_, continued| continued);
+        let changed = product.states_where(regex_idx, |component, _| component != 0);
+        circom += &format!("\tsignal {p}from_zero_enabled[num_bytes+1];\n");
+        circom += &format!("\tsignal {p}state_changed[num_bytes];\n");
+        circom += &format!("\t{p}from_zero_enabled[num_bytes] <== 0;\n");
+        circom += "\tfor (var i = 0; i < num_bytes; i++) {\n";
+        circom += &format!(
+            "\t\t{p}from_zero_enabled[i] <== 1 - ({});\n",
+            states_sum(&continued, "i+1")
+        );
+        circom += &format!(
+            "\t\t{p}state_changed[i] <== {};\n",
+            states_sum(&changed, "i+1")
+        );
+        circom += "\t}\n";
+    }
+    circom
+}
+
+/// Generates a single Circom template that matches several regexes with the circuit of their product automaton.
+///
+/// The template outputs `{name}_out` and the `{name}_reveal{idx}` signals of each regex, whose signals are
+/// prefixed with its name, and `out`, which combines the `{name}_out` signals according to `combine`.
+///
+/// # Arguments
+///
+/// * `product` - A reference to the ProductAutomaton of the regexes.
+/// * `template_name` - The name of the Circom template.
+/// * `gen_substrs` - A boolean indicating whether to generate substring constraints.
+/// * `combine` - How `out` is computed from the outputs of the regexes.
+/// * `equalities` - The pairs of substrings of different regexes constrained to be equal, each given by the index
+///   of its regex and its index in the regex.
+/// * `options` - A reference to the CircomOptions, which must use [`StateEncoding::Vector`].
+///
+/// # Returns
+///
+/// A Result containing the Circom code as a String, or a CompilerError.
+pub(crate) fn gen_circom_product(
+    product: &ProductAutomaton,
+    template_name: &str,
+    gen_substrs: bool,
+    combine: CombineMode,
+    equalities: &[CrossEquality],
+    options: &CircomOptions,
+) -> Result<String, CompilerError> {
+    if options.state_encoding != StateEncoding::Vector {
+        return Err(CompilerError::GenericError(
+            "Combined templates require the vector state encoding".to_string(),
+        ));
+    }
+    if options.negate {
+        return Err(CompilerError::GenericError(
+            "Combined templates do not support the negative match mode".to_string(),
+        ));
+    }
+    if options.search_mode != SearchMode::Anywhere {
+        return Err(CompilerError::GenericError(
+            "Combined templates do not support the anchored search modes".to_string(),
+        ));
+    }
+    if options.occurrence != Occurrence::Any && !gen_substrs {
+        return Err(CompilerError::GenericError(
+            "The occurrence constraints require the substring constraints".to_string(),
+        ));
+    }
+    check_packed_reveals(
+        options,
+        gen_substrs,
+        product
+            .circuits
+            .iter()
+            .flat_map(|circuit| &circuit.reveals)
+            .any(|reveal| reveal.max_len.is_some()),
+    )?;
+    check_equalities(
+        gen_substrs,
+        !equalities.is_empty()
+            || product
+                .circuits
+                .iter()
+                .any(|circuit| !circuit.equalities.is_empty()),
+    )?;
+    let prefixes = product
+        .names
+        .iter()
+        .map(|name| signal_prefix(name))
+        .collect::<Vec<_>>();
+    if prefixes.iter().collect::<BTreeSet<_>>().len() != prefixes.len() {
+        return Err(CompilerError::GenericError(format!(
+            "The signal prefixes of the regexes are not unique: {:?}",
+            prefixes
+        )));
+    }
+
+    let circuit = RegexCircuit::new(&product.regex_and_dfa);
+    let (stats, lines) = generate_state_transition_logic(&circuit, options, None);
+    let mut declarations = generate_declarations(
+        template_name,
+        &circuit.regex,
+        circuit.num_states,
+        &stats,
+        circuit.has_end_anchor,
+        options,
+        None,
+    );
+    for p in &prefixes {
+        declarations.push(format!("\tsignal output {p}out;"));
+    }
+    declarations.push("".to_string());
+    let init_code = generate_init_code(circuit.num_states, false);
+    let mut circom = [declarations, init_code, lines].concat().join("\n");
+
+    for (regex_idx, (p, regex_circuit)) in prefixes.iter().zip(&product.circuits).enumerate() {
+        circom += &format!(
+            "\n\n\t// {}: {}\n",
+            product.names[regex_idx],
+            regex_circuit
+                .regex
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+        );
+        circom += &write_projection_signals(product, regex_idx, p, gen_substrs);
+        let signals = StateSignals::Projection(p);
+        circom += &generate_accept_logic(
+            regex_circuit.accept_states.clone(),
+            regex_circuit.has_end_anchor,
+            &signals,
+        )?
+        .join("\n");
+        circom += "\n";
+        if gen_substrs {
+            circom += &write_reveal_logic(regex_circuit, &signals, options)?;
+        }
+    }
+    if gen_substrs && !equalities.is_empty() {
+        circom += "\n";
+        for &((regex_a, a), (regex_b, b)) in equalities {
+            circom += &write_equal_logic(
+                (&prefixes[regex_a], &product.circuits[regex_a], a),
+                (&prefixes[regex_b], &product.circuits[regex_b], b),
+            )?;
+        }
+    }
+
+    let outs = prefixes
+        .iter()
+        .map(|p| format!("{p}out"))
+        .collect::<Vec<_>>();
+    let combinator = match combine {
+        CombineMode::All => "MultiAND",
+        CombineMode::Any => "MultiOR",
+    };
+    circom += &format!(
+        "\n\tout <== {combinator}({})([{}]);\n}}",
+        outs.len(),
+        outs.join(", ")
+    );
+    let committed = gen_substrs
+        && product
+            .circuits
+            .iter()
+            .flat_map(|circuit| &circuit.reveals)
+            .any(|reveal| reveal.committed);
+    Ok(include_commit_helpers(circom, committed))
+}
+
+/// Returns the Circom expression of the check that `in[i]` is in the inclusive range `[min, max]`.
+fn range_check_expr(min: u8, max: u8) -> String {
+    match (min, max) {
+        (0, 255) => "1".to_string(),
+        _ if min == max => format!("IsEqual()([in[i], {}])", min),
+        (0, _) => format!("LessEqThan(8)([in[i], {}])", max),
+        (_, 255) => format!("LessEqThan(8)([{}, in[i]])", min),
+        _ => format!(
+            "AND()(LessEqThan(8)([{}, in[i]]), LessEqThan(8)([in[i], {}]))",
+            min, max
+        ),
+    }
+}
+
+/// Generates the Circom template of the negative match mode.
+///
+/// The template runs the complete NegatedDfa on `in` with one signal per state and position. Since the DFA is
+/// complete and deterministic, exactly one state is active at each position, so the dead state is the complement
+/// of the live states and `out` is `1` if and only if the dead state is not reached.
+///
+/// # Arguments
+///
+/// * `negated` - A reference to the NegatedDfa of the regex.
+/// * `regex_str` - The regex string written in the comment above the template.
+/// * `template_name` - The name of the Circom template.
+/// * `packed_msg` - Whether `msg` is unpacked from the `msg_packed` input.
+///
+/// # Returns
+///
+/// A String containing the Circom code.
+fn gen_circom_negated(
+    negated: &NegatedDfa,
+    regex_str: &str,
+    template_name: &str,
+    packed_msg: bool,
+) -> String {
+    let dead_state = negated.dead_state();
+    let mut ranges = BTreeMap::new();
+    let mut body = vec![];
+    for (transition_idx, (from, _, transition_ranges)) in negated.transitions.iter().enumerate() {
+        let preds = transition_ranges
+            .iter()
+            .map(|&range| {
+                let num_ranges = ranges.len();
+                let range_idx = *ranges.entry(range).or_insert(num_ranges);
+                format!("byte_in_range[{}][i]", range_idx)
+            })
+            .collect::<Vec<_>>();
+        body.push(format!(
+            "\t\ttransitions[{}][i] <== states[i][{}] * ({});",
+            transition_idx,
+            from,
+            preds.join(" + ")
+        ));
+    }
+    for state in 0..dead_state {
+        let incoming = negated
+            .transitions
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, to, _))| *to == state)
+            .map(|(transition_idx, _)| format!("transitions[{}][i]", transition_idx))
+            .collect::<Vec<_>>();
+        body.push(format!(
+            "\t\tstates[i+1][{}] <== {};",
+            state,
+            if incoming.is_empty() {
+                "0".to_string()
+            } else {
+                incoming.join(" + ")
+            }
+        ));
+    }
+    body.push(format!(
+        "\t\tstates[i+1][{}] <== 1{};",
+        dead_state,
+        (0..dead_state)
+            .map(|state| format!(" - states[i+1][{}]", state))
+            .collect::<String>()
+    ));
+
+    let mut lines = generate_msg_header(template_name, regex_str, packed_msg);
+    if !ranges.is_empty() {
+        lines.push(format!(
+            "\tsignal byte_in_range[{}][num_bytes];",
+            ranges.len()
+        ));
+    }
+    if !negated.transitions.is_empty() {
+        lines.push(format!(
+            "\tsignal transitions[{}][num_bytes];",
+            negated.transitions.len()
+        ));
+    }
+    lines.push(format!(
+        "\tsignal states[num_bytes+1][{}];",
+        negated.num_states
+    ));
+    lines.push("".to_string());
+    lines.push("\tstates[0][0] <== 1;".to_string());
+    lines.extend((1..negated.num_states).map(|state| format!("\tstates[0][{}] <== 0;", state)));
+    lines.push("\tfor (var i = 0; i < num_bytes; i++) {".to_string());
+    let mut ranges = ranges.into_iter().collect::<Vec<_>>();
+    ranges.sort_by_key(|&(_, range_idx)| range_idx);
+    lines.extend(ranges.into_iter().map(|((min, max), range_idx)| {
+        format!(
+            "\t\tbyte_in_range[{}][i] <== {};",
+            range_idx,
+            range_check_expr(min, max)
+        )
+    }));
+    lines.extend(body);
+    lines.push("\t}".to_string());
+    lines.push("".to_string());
+    lines.push(format!("\tout <== 1 - states[num_bytes][{}];", dead_state));
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+/// Generates the Circom template of the anchored search modes.
+///
+/// The template runs the DFA once on `in` with one signal per state and position. The states of
+/// [`RegexCircuit::anchored_run`] are entered at the position selected by `is_start`: the state reached from `0` by
+/// the `255` prepended to `msg`, and `0` for the first byte only. The DFA never restarts from `0`, so at most one
+/// transition is taken at each position.
+/// The reveal outputs are those of the other search modes, computed from the signals of
+/// [`write_anchored_substr_signals`]: a byte is revealed if it is read by a reveal transition before the last
+/// position where the accept state is active, or before the first one with [`MatchPolicy::LeftmostShortest`].
+///
+/// # Arguments
+///
+/// * `circuit` - A reference to the RegexCircuit.
+/// * `template_name` - The name of the Circom template.
+/// * `gen_substrs` - A boolean indicating whether to generate the reveal outputs.
+/// * `options` - A reference to the CircomOptions, whose search mode is not [`SearchMode::Anywhere`].
+///
+/// # Returns
+///
+/// A Result containing the Circom code as a String, or a CompilerError.
+fn gen_circom_anchored(
+    circuit: &RegexCircuit,
+    template_name: &str,
+    gen_substrs: bool,
+    options: &CircomOptions,
+) -> Result<String, CompilerError> {
+    let search_mode = options.search_mode;
+    let accept_lines = generate_accept_logic(
+        circuit.accept_states.clone(),
+        circuit.has_end_anchor,
+        &StateSignals::Vector,
+    )?;
+    let AnchoredRun {
+        init_state,
+        transitions,
+    } = circuit.anchored_run()?;
+    let mut signal_idxes = BTreeMap::new();
+    for transition in &transitions {
+        for &range in &transition.ranges {
+            let num_signals = signal_idxes.len();
+            signal_idxes.entry(range).or_insert(num_signals);
+        }
+    }
+    let start_states = if transitions.iter().any(|transition| transition.from == 0) {
+        BTreeSet::from([0, init_state])
+    } else {
+        BTreeSet::from([init_state])
+    };
+
+    let mut lines = generate_msg_header(template_name, &circuit.regex, options.packed_msg);
+    if search_mode == SearchMode::AtIndex {
+        lines.push("\tsignal input start_index;".to_string());
+    }
+    lines.push("\tsignal is_start[num_bytes];".to_string());
+    if !signal_idxes.is_empty() {
+        lines.push(format!(
+            "\tsignal byte_in_range[{}][num_bytes];",
+            signal_idxes.len()
+        ));
+    }
+    if !transitions.is_empty() {
+        lines.push(format!(
+            "\tsignal transitions[{}][num_bytes];",
+            transitions.len()
+        ));
+    }
+    lines.push(format!(
+        "\tsignal states[num_bytes+1][{}];",
+        circuit.num_states
+    ));
+    if circuit.has_end_anchor {
+        lines.push("\tsignal padding_start[num_bytes+1];".to_string());
+        lines.push("\tpadding_start[0] <== 0;".to_string());
+    }
+    lines.push("".to_string());
+    lines.extend((0..circuit.num_states).map(|state| format!("\tstates[0][{}] <== 0;", state)));
+    if search_mode == SearchMode::Anchored {
+        lines.push("\tis_start[0] <== 1;".to_string());
+        lines.push("\tfor (var i = 1; i < num_bytes; i++) {".to_string());
+        lines.push("\t\tis_start[i] <== 0;".to_string());
+        lines.push("\t}".to_string());
+    } else {
+        lines.push("\tvar num_starts = 0;".to_string());
+    }
+    lines.push("\tfor (var i = 0; i < num_bytes; i++) {".to_string());
+    if circuit.has_end_anchor {
+        lines.push(
+            "\t\tpadding_start[i+1] <== IsNotZeroAcc()(padding_start[i], in[i]);".to_string(),
+        );
+    }
+    if search_mode == SearchMode::AtIndex {
+        lines.push("\t\tis_start[i] <== IsEqual()([i, start_index]);".to_string());
+        lines.push("\t\tnum_starts += is_start[i];".to_string());
+    }
+    let mut ranges = signal_idxes.iter().collect::<Vec<_>>();
+    ranges.sort_by_key(|&(_, signal_idx)| signal_idx);
+    for (&(min, max), signal_idx) in ranges {
+        lines.push(format!(
+            "\t\tbyte_in_range[{}][i] <== {};",
+            signal_idx,
+            range_check_expr(min, max)
+        ));
+    }
+    for (transition_idx, transition) in transitions.iter().enumerate() {
+        let preds = transition
+            .ranges
+            .iter()
+            .map(|range| format!("byte_in_range[{}][i]", signal_idxes[range]))
+            .collect::<Vec<_>>();
+        lines.push(format!(
+            "\t\ttransitions[{}][i] <== states[i][{}] * ({});",
+            transition_idx,
+            transition.from,
+            preds.join(" + ")
+        ));
+    }
+    for state in 0..circuit.num_states {
+        let incoming = transitions
+            .iter()
+            .enumerate()
+            .filter(|(_, transition)| transition.to == state)
+            .map(|(transition_idx, _)| format!("transitions[{}][i]", transition_idx))
+            .chain(
+                start_states
+                    .contains(&state)
+                    .then(|| "is_start[i]".to_string()),
+            )
+            .collect::<Vec<_>>();
+        lines.push(format!(
+            "\t\tstates[i+1][{}] <== {};",
+            state,
+            if incoming.is_empty() {
+                "0".to_string()
+            } else {
+                incoming.join(" + ")
+            }
+        ));
+    }
+    lines.push("\t}".to_string());
+    if search_mode == SearchMode::AtIndex {
+        lines.push("\tnum_starts === 1;".to_string());
+    }
+    lines.extend(accept_lines);
+
+    if gen_substrs {
+        lines.extend(
+            write_anchored_substr_signals(
+                circuit.num_states,
+                &transitions
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, transition)| transition.from == 0)
+                    .map(|(transition_idx, _)| transition_idx)
+                    .collect::<Vec<_>>(),
+            )
+            .lines()
+            .map(str::to_string),
+        );
+        lines.extend(
+            write_reveal_logic(circuit, &StateSignals::Vector, options)?
+                .lines()
+                .map(str::to_string),
+        );
+    }
+    lines.push("}".to_string());
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        regex::get_regex_and_dfa, registry_pattern_config, structs::PartOutput,
+        trace::trace_circuit,
+    };
+    use zk_regex_apis::{commitment::MAX_COMMITTED_BYTES, numeric::MAX_UINT_DIGITS};
+
+    /// Returns the first index of a signal such as `byte_ge[3][i]`.
+    fn signal_idx(token: &str) -> usize {
+        token[token.find('[').unwrap() + 1..token.find(']').unwrap()]
+            .parse()
+            .unwrap()
+    }
+
+    /// Generates the template `Test` of a circuit.
+    fn generate(
+        circuit: &RegexCircuit,
+        gen_substrs: bool,
+        options: CircomOptions,
+    ) -> Result<String, CompilerError> {
+        CircomBackend {
+            template_name: "Test",
+            gen_substrs,
+            options,
+            shared_predicates: None,
+        }
+        .generate(circuit)
+    }
+
+    fn builtin_circuits() -> Vec<(String, RegexAndDFA)> {
+        let registry = zk_regex_apis::registry::PatternRegistry::builtin();
+        registry
+            .names()
+            .map(|name| {
+                let mut config = registry_pattern_config(&registry, name).unwrap();
+                (name.to_string(), get_regex_and_dfa(&mut config).unwrap())
+            })
+            .collect()
+    }
+
+    /// The subject regex, whose public part has the given `max_len`.
+    fn subject_config(max_len: Option<usize>) -> crate::DecomposedRegexConfig {
+        let mut config = crate::DecomposedRegexConfig::from_parts(&[
+            ("(\r\n|^)subject:", false),
+            ("[a-z]+", true),
+            ("\r\n", false),
+        ]);
+        config.parts[1].max_len = max_len;
+        config
+    }
+
+    #[test]
+    fn test_circom_options_json() {
+        let options: CircomOptions = serde_json::from_str(
+            r#"{"searchMode": "at-index", "one_hot": true, "occurrence": "unique"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            options,
+            CircomOptions {
+                search_mode: SearchMode::AtIndex,
+                one_hot: true,
+                occurrence: Occurrence::Unique,
+                ..Default::default()
+            }
+        );
+        let json = serde_json::to_string(&options).unwrap();
+        assert_eq!(
+            serde_json::from_str::<CircomOptions>(&json).unwrap(),
+            options
+        );
+        assert!(serde_json::from_str::<CircomOptions>(r#"{"searchMode": "anywhere"}"#).is_ok());
+        assert!(serde_json::from_str::<CircomOptions>(r#"{"stateEncoding": "binary"}"#).is_err());
+    }
+
+    #[test]
+    fn test_byte_classification() {
+        for (name, regex_and_dfa) in builtin_circuits() {
+            let circuit = RegexCircuit::new(&regex_and_dfa);
+            let byte_classes = circuit.byte_classes();
+            let used_classes = used_classes(&byte_classes);
+            let classification = ByteClassification::new(&byte_classes, &used_classes);
+            assert_eq!(
+                classification.class_exprs.len(),
+                used_classes.len(),
+                "{}",
+                name
+            );
+            // The comparisons with `0` and `256` are constant.
+            assert!(
+                classification
+                    .thresholds
+                    .iter()
+                    .all(|&threshold| 0 < threshold && threshold < 256),
+                "{}",
+                name
+            );
+            // Every expression reads the declared checks.
+            for class_i in 0..used_classes.len() {
+                let expr = classification.class_expr(class_i);
+                for token in expr.split(|c: char| " ()*+-".contains(c)) {
+                    if token.starts_with("byte_ge") {
+                        assert!(signal_idx(token) < classification.thresholds.len());
+                    } else if token.starts_with("byte_eq") {
+                        assert!(signal_idx(token) < classification.eq_bytes.len());
+                    } else if !token.is_empty() {
+                        token.parse::<i64>().unwrap();
+                    }
+                }
+            }
+            // Every byte is in the used class of the transitions containing it, and the byte `0` is in none.
+            for byte in 0..=255u8 {
+                for (class_i, &class) in used_classes.iter().enumerate() {
+                    assert_eq!(
+                        classification.class_value(class_i, byte),
+                        (byte_classes.class_of[byte as usize] == class) as i64,
+                        "{} {} {}",
+                        name,
+                        class,
+                        byte
+                    );
+                }
+                for (transition, classes) in circuit
+                    .transitions
+                    .iter()
+                    .zip(&byte_classes.transition_classes)
+                {
+                    let in_ranges = byte != 0
+                        && transition
+                            .ranges
+                            .iter()
+                            .any(|&(min, max)| min <= byte && byte <= max);
+                    assert_eq!(
+                        classes.contains(&byte_classes.class_of[byte as usize]),
+                        in_ranges,
+                        "{} {}",
+                        name,
+                        byte
+                    );
+                }
+            }
+        }
+    }
+
+    /// Returns whether the byte predicate holds for the byte.
+    fn predicate_holds(predicate: BytePredicate, byte: u8) -> bool {
+        match predicate {
+            BytePredicate::Eq(eq_byte) => eq_byte == byte,
+            BytePredicate::Range(min, max) => min <= byte && byte <= max,
+        }
+    }
+
+    #[test]
+    fn test_one_hot_sums() {
+        let mut msgs = vec![
+            b"from:suegamisora@gmail.com\r\nsubject:hello zk\r\nto:a@b.com\r\n".to_vec(),
+            b"from:from:a@b@c.com\r\n\r\nsubject:\r\nsubject:x\r\n".to_vec(),
+            (1..=254).collect::<Vec<u8>>(),
+        ];
+        for msg in &mut msgs {
+            msg.resize(256, 0);
+        }
+        for (name, regex_and_dfa) in builtin_circuits() {
+            let circuit = RegexCircuit::new(&regex_and_dfa);
+            // The conditions summed by a transition hold for disjoint bytes, whose union is the transition.
+            for transition in circuit
+                .transitions
+                .iter()
+                .filter(|transition| circuit.predicates_are_exclusive(&transition.predicates))
+            {
+                for byte in 1..=255u8 {
+                    let sum = transition
+                        .predicates
+                        .iter()
+                        .filter(|&&predicate_idx| {
+                            predicate_holds(circuit.predicates[predicate_idx], byte)
+                        })
+                        .count();
+                    let in_ranges = transition
+                        .ranges
+                        .iter()
+                        .any(|&(min, max)| min <= byte && byte <= max);
+                    assert_eq!(sum, in_ranges as usize, "{} {}", name, byte);
+                }
+            }
+            // The summed states and transitions are one-hot, so the sums are equal to the ORs.
+            for msg in &msgs {
+                assert!(
+                    trace_circuit(&regex_and_dfa, msg).unwrap().one_hot,
+                    "{}",
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_shared_predicates() {
+        let circuits = builtin_circuits()
+            .into_iter()
+            .map(|(name, regex_and_dfa)| (name, RegexCircuit::new(&regex_and_dfa)))
+            .collect::<Vec<_>>();
+        let shared = collect_shared_predicates(&circuits);
+        assert_eq!(shared.iter().collect::<BTreeSet<_>>().len(), shared.len());
+        // The `pred` signals read by each template enable the bytes of its transitions.
+        for (name, circuit) in &circuits {
+            for transition in &circuit.transitions {
+                let preds = transition
+                    .predicates
+                    .iter()
+                    .map(|&predicate_idx| {
+                        shared
+                            .iter()
+                            .position(|&shared| shared == circuit.predicates[predicate_idx])
+                            .unwrap()
+                    })
+                    .collect::<Vec<_>>();
+                for byte in 1..=255u8 {
+                    assert_eq!(
+                        preds
+                            .iter()
+                            .any(|&pred_i| predicate_holds(shared[pred_i], byte)),
+                        transition
+                            .ranges
+                            .iter()
+                            .any(|&(min, max)| min <= byte && byte <= max),
+                        "{} {}",
+                        name,
+                        byte
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_encoding() {
+        for (name, regex_and_dfa) in builtin_circuits() {
+            let circuit = RegexCircuit::new(&regex_and_dfa);
+            let encoding = IndexEncoding::new(&circuit);
+
+            // The expressions read the declared indicators.
+            for class_i in 0..encoding.used_classes.len() {
+                let exprs = std::iter::once(encoding.next_state_expr(class_i))
+                    .chain(encoding.leaves_nonzero_expr(class_i))
+                    .collect::<Vec<_>>();
+                for token in exprs
+                    .iter()
+                    .flat_map(|expr| expr.split(|c: char| " *+-".contains(c)))
+                    .filter(|token| token.starts_with("is_state"))
+                {
+                    assert!(signal_idx(token) < encoding.indicators.len(), "{}", name);
+                }
+            }
+
+            // The state index follows the active states of the circuit with the state vectors.
+            let byte_classes = circuit.byte_classes();
+            let mut msg = b"from:suegamisora@gmail.com\r\nsubject:hello zk\r\n".to_vec();
+            msg.resize(64, 0);
+            let trace = trace_circuit(&regex_and_dfa, &msg).unwrap();
+            let mut state = 0;
+            for (i, byte) in std::iter::once(255).chain(msg).enumerate() {
+                let class = byte_classes.class_of[byte as usize];
+                let class_i = encoding.used_classes.iter().position(|&c| c == class);
+                let from_nonzero = class_i
+                    .is_some_and(|class_i| state != 0 && encoding.next[state][class_i].is_some());
+                if i > 0 {
+                    assert_eq!(
+                        !from_nonzero,
+                        trace.bytes[i - 1].from_zero_enabled,
+                        "{}",
+                        name
+                    );
+                }
+                state = class_i.map_or(0, |class_i| encoding.next_state(state, class_i));
+                let active_states = if i == 0 {
+                    &trace.initial_states
+                } else {
+                    &trace.bytes[i - 1].active_states
+                };
+                assert_eq!(
+                    active_states,
+                    &(state != 0)
+                        .then_some(state)
+                        .into_iter()
+                        .collect::<Vec<_>>(),
+                    "{}",
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_gen_circom_negated() {
+        let options = CircomOptions {
+            negate: true,
+            ..Default::default()
+        };
+        for (name, regex_and_dfa) in builtin_circuits() {
+            let circuit = RegexCircuit::new(&regex_and_dfa);
+            let negated = NegatedDfa::new(&circuit).unwrap();
+            let circom = generate(&circuit, true, options).unwrap();
+            assert!(!circom.contains("reveal"));
+            assert!(circom.contains(&format!(
+                "\tsignal states[num_bytes+1][{}];\n",
+                negated.num_states
+            )));
+            assert!(circom.contains("\tstates[0][0] <== 1;\n"));
+            // The complete DFA updates every state at every position.
+            for state in 0..negated.num_states {
+                assert_eq!(
+                    circom
+                        .matches(&format!("\t\tstates[i+1][{}] <== ", state))
+                        .count(),
+                    1,
+                    "{} state {}",
+                    name,
+                    state
+                );
+            }
+            assert!(circom.contains(&format!(
+                "\tout <== 1 - states[num_bytes][{}];\n",
+                negated.dead_state()
+            )));
+        }
+
+        assert!(generate(
+            &RegexCircuit::new(&builtin_circuits()[0].1),
+            false,
+            CircomOptions {
+                one_hot: true,
+                ..options
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_leftmost_reveal_logic() {
+        let msg = b"x=abc;x=de;x=f\0\0";
+        for (policy, signals, expected) in [
+            (
+                MatchPolicy::LeftmostLongest,
+                &["accepted_before", "continues_match", "in_match"][..],
+                (2, 5),
+            ),
+            (
+                MatchPolicy::LeftmostShortest,
+                &["accepted_before", "in_match"],
+                (2, 3),
+            ),
+        ] {
+            let mut config =
+                crate::DecomposedRegexConfig::from_parts(&[("x=", false), ("[a-z]+", true)])
+                    .with_match_policy(policy);
+            let regex_and_dfa = get_regex_and_dfa(&mut config).unwrap();
+            let circuit = RegexCircuit::new(&regex_and_dfa);
+            let circom =
+                write_reveal_logic(&circuit, &StateSignals::Vector, &CircomOptions::default())
+                    .unwrap();
+            for signal in signals {
+                assert!(
+                    circom.contains(&format!("\t\t{}[i] <== ", signal)),
+                    "{:?} {}",
+                    policy,
+                    signal
+                );
+            }
+            assert!(circom.contains("is_substr0[i], is_consecutive[i][2], in_match[i]]);\n"));
+            assert_eq!(
+                trace_circuit(&regex_and_dfa, msg).unwrap().reveals,
+                [[expected]],
+                "{:?}",
+                policy
+            );
+        }
+    }
+
+    #[test]
+    fn test_gen_circom_anchored() {
+        let generate_in_mode = |circuit: &RegexCircuit, search_mode| {
+            generate(
+                circuit,
+                true,
+                CircomOptions {
+                    search_mode,
+                    ..Default::default()
+                },
+            )
+        };
+        for (name, regex_and_dfa) in builtin_circuits() {
+            let circuit = RegexCircuit::new(&regex_and_dfa);
+            let anchored = generate_in_mode(&circuit, SearchMode::Anchored).unwrap();
+            let at_index = generate_in_mode(&circuit, SearchMode::AtIndex).unwrap();
+            assert!(!anchored.contains("start_index"), "{}", name);
+            assert!(
+                at_index.contains("\tsignal input start_index;\n"),
+                "{}",
+                name
+            );
+            for circom in [&anchored, &at_index] {
+                // The single run never restarts from the state `0`.
+                assert!(!circom.contains("\t\tfrom_zero_enabled[i] <== states[i][0];\n"));
+                assert!(circom.contains("\tfrom_zero_enabled[num_bytes] <== 0;\n"));
+                for idx in 0..circuit.reveals.len() {
+                    assert!(
+                        circom.contains(&format!("\tsignal output reveal{}[msg_bytes];\n", idx)),
+                        "{}",
+                        name
+                    );
+                }
+            }
+        }
+
+        // `\r\n` is read from the state `0` at the start, as `^` is read from the initial state.
+        let circuit = RegexCircuit::new(
+            &get_regex_and_dfa(&mut crate::DecomposedRegexConfig::from_parts(&[
+                ("(\r\n|^)from:", false),
+                ("[a-z]+", true),
+            ]))
+            .unwrap(),
+        );
+        let anchored = generate_in_mode(&circuit, SearchMode::Anchored).unwrap();
+        assert!(anchored.contains("\t\tstates[i+1][0] <== is_start[i];\n"));
+        let init_state = circuit.anchored_run().unwrap().init_state;
+        assert!(anchored.lines().any(|line| {
+            line.starts_with(&format!("\t\tstates[i+1][{}] <== ", init_state))
+                && line.ends_with("is_start[i];")
+        }));
+
+        let circuit = RegexCircuit::new(&builtin_circuits()[0].1);
+        for options in [
+            CircomOptions {
+                search_mode: SearchMode::Anchored,
+                negate: true,
+                ..Default::default()
+            },
+            CircomOptions {
+                search_mode: SearchMode::AtIndex,
+                one_hot: true,
+                ..Default::default()
+            },
+        ] {
+            assert!(generate(&circuit, true, options).is_err());
+        }
+    }
+
+    #[test]
+    fn test_occurrence_constraints() {
+        let circuit = RegexCircuit::new(&get_regex_and_dfa(&mut subject_config(None)).unwrap());
+        for options in [
+            CircomOptions::default(),
+            CircomOptions {
+                one_hot: true,
+                ..Default::default()
+            },
+            CircomOptions {
+                state_encoding: StateEncoding::Index,
+                ..Default::default()
+            },
+        ] {
+            for occurrence in [Occurrence::First, Occurrence::Unique] {
+                let circom = generate(
+                    &circuit,
+                    true,
+                    CircomOptions {
+                        occurrence,
+                        ..options
+                    },
+                )
+                .unwrap();
+                assert!(circom.contains("search_first"));
+                assert_eq!(
+                    circom.contains("search_accepted[i+2] * (1 - in_match[i]) === 0"),
+                    occurrence == Occurrence::Unique
+                );
+            }
+        }
+
+        // The DFA misses the occurrence starting inside a failed run, which the SearchDfa finds.
+        let abac = get_regex_and_dfa(&mut crate::DecomposedRegexConfig::from_parts(&[(
+            "abac", true,
+        )]))
+        .unwrap();
+        let search = SearchDfa::new(&RegexCircuit::new(&abac)).unwrap();
+        let msg = b"ababac;abac";
+        assert_eq!(search.match_ends(msg), [5, 10]);
+        assert_eq!(trace_circuit(&abac, msg).unwrap().reveals, [[(7, 11)]]);
+
+        assert!(generate(
+            &circuit,
+            false,
+            CircomOptions {
+                occurrence: Occurrence::First,
+                ..Default::default()
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_packed_reveal_outputs() {
+        let config = |max_len, is_public| {
+            let mut config = subject_config(max_len);
+            config.parts[1].is_public = is_public;
+            config
+        };
+        let regex_and_dfa = get_regex_and_dfa(&mut config(Some(4), true)).unwrap();
+        let circuit = RegexCircuit::new(&regex_and_dfa);
+        assert_eq!(circuit.reveals[0].max_len, Some(4));
+        // The substring starts from the state after `subject:`.
+        assert_eq!(circuit.reveals[0].start_transitions().unwrap().len(), 1);
+        for search_mode in [SearchMode::Anywhere, SearchMode::Anchored] {
+            let circom = generate(
+                &circuit,
+                true,
+                CircomOptions {
+                    search_mode,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            for output in ["found", "start", "len", "packed[4]"] {
+                assert!(circom.contains(&format!("signal output reveal0_{output};")));
+            }
+        }
+
+        assert!(get_regex_and_dfa(&mut config(Some(4), false)).is_err());
+        assert!(get_regex_and_dfa(&mut config(Some(0), true)).is_err());
+        let mut regex_and_dfa = regex_and_dfa;
+        regex_and_dfa.substrings.substring_boundaries = None;
+        assert!(generate(
+            &RegexCircuit::new(&regex_and_dfa),
+            true,
+            CircomOptions::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_packed_msg_and_reveals() {
+        let circuit = RegexCircuit::new(&get_regex_and_dfa(&mut subject_config(Some(4))).unwrap());
+        let msg_input = "\tsignal input msg[msg_bytes];";
+        let packed_input = generate_msg_input(true).join("\n");
+        for options in [
+            CircomOptions::default(),
+            CircomOptions {
+                search_mode: SearchMode::AtIndex,
+                ..Default::default()
+            },
+            CircomOptions {
+                negate: true,
+                ..Default::default()
+            },
+        ] {
+            let circom = generate(&circuit, !options.negate, options).unwrap();
+            let packed = generate(
+                &circuit,
+                !options.negate,
+                CircomOptions {
+                    packed_msg: true,
+                    ..options
+                },
+            )
+            .unwrap();
+            assert_eq!(packed, circom.replace(msg_input, &packed_input));
+        }
+        let shared = gen_circom_shared(
+            &[("Test".to_string(), circuit.clone())],
+            "Predicates",
+            true,
+            &CircomOptions {
+                packed_msg: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(shared.contains(&packed_input) && !shared.contains(msg_input));
+
+        let circom = generate(
+            &circuit,
+            true,
+            CircomOptions {
+                packed_reveals: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(circom.contains("\tsignal output reveal0_fields[1];"));
+        assert!(circom.contains("reveal0_fields <== PackBytes(4)(reveal0_packed);"));
+
+        assert!(generate(
+            &circuit,
+            false,
+            CircomOptions {
+                packed_reveals: true,
+                ..Default::default()
+            },
+        )
+        .is_err());
+        let circuit = RegexCircuit::new(&get_regex_and_dfa(&mut subject_config(None)).unwrap());
+        assert!(generate(
+            &circuit,
+            true,
+            CircomOptions {
+                packed_reveals: true,
+                ..Default::default()
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_committed_reveals() {
+        let config = |max_len, is_public| {
+            let mut config = subject_config(max_len);
+            config.parts[1].is_public = is_public;
+            config.parts[1].committed = is_public;
+            config
+        };
+        let circuit = RegexCircuit::new(&get_regex_and_dfa(&mut config(Some(4), true)).unwrap());
+        for search_mode in [SearchMode::Anywhere, SearchMode::Anchored] {
+            let circom = generate(
+                &circuit,
+                true,
+                CircomOptions {
+                    search_mode,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            // The commitment is the only output of the part.
+            assert_eq!(
+                circom
+                    .lines()
+                    .filter(|line| line.contains("signal output reveal0"))
+                    .collect::<Vec<_>>(),
+                ["\tsignal output reveal0_commitment;"]
+            );
+            assert!(circom.contains("\tsignal input reveal0_salt;\n"));
+            assert!(circom
+                .contains("reveal0_commitment <== CommitBytes(4)(reveal0_packed, reveal0_salt);"));
+            assert!(circom.contains(COMMIT_HELPERS_INCLUDE));
+        }
+        let uncommitted = RegexCircuit::new(&get_regex_and_dfa(&mut config(None, false)).unwrap());
+        let circom = generate(&uncommitted, true, CircomOptions::default()).unwrap();
+        assert!(circom.contains(REGEX_HELPERS_INCLUDE) && !circom.contains(COMMIT_HELPERS_INCLUDE));
+        let shared = gen_circom_shared(
+            &[
+                ("Committed".to_string(), circuit.clone()),
+                ("Uncommitted".to_string(), uncommitted),
+            ],
+            "Predicates",
+            true,
+            &CircomOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(shared.matches(COMMIT_HELPERS_INCLUDE).count(), 1);
+
+        for (max_len, is_public) in [
+            (None, true),
+            (Some(4), false),
+            (Some(MAX_COMMITTED_BYTES + 1), true),
+        ] {
+            let mut config = config(max_len, is_public);
+            config.parts[1].committed = true;
+            assert!(get_regex_and_dfa(&mut config).is_err());
+        }
+    }
+
+    #[test]
+    fn test_equal_reveals() {
+        let config = |constraints: &str| {
+            let mut config = crate::DecomposedRegexConfig::from_parts(&[
+                ("from:[a-z]+@", false),
+                ("[a-z]+", true),
+                ("\\.com;id:[a-z]+@", false),
+                ("[a-z]+", true),
+                ("\\.com", false),
+            ])
+            .with_constraints(serde_json::from_str(constraints).unwrap());
+            config.parts[1].max_len = Some(6);
+            config.parts[3].max_len = Some(4);
+            config
+        };
+        let circuit =
+            RegexCircuit::new(&get_regex_and_dfa(&mut config(r#"[{"equal": [1, 3]}]"#)).unwrap());
+        assert_eq!(circuit.equalities, [(0, 1)]);
+        for search_mode in [SearchMode::Anywhere, SearchMode::Anchored] {
+            let circom = generate(
+                &circuit,
+                true,
+                CircomOptions {
+                    search_mode,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert!(circom.contains("\treveal0_len === reveal1_len;\n"));
+            assert!(circom.contains("\tfor (var j = 0; j < 4; j++) {\n"));
+            assert!(circom.contains("\t\treveal0_packed[j] === reveal1_packed[j];\n"));
+        }
+        assert!(generate(&circuit, false, CircomOptions::default()).is_err());
+
+        for constraints in [
+            r#"[{"equal": [0, 3]}]"#,
+            r#"[{"equal": [1, 5]}]"#,
+            r#"[{"equal": [1, {"regex": "id", "part": 1}]}]"#,
+        ] {
+            assert!(get_regex_and_dfa(&mut config(constraints)).is_err());
+        }
+        let mut no_max_len = config(r#"[{"equal": [1, 3]}]"#);
+        no_max_len.parts[3].max_len = None;
+        assert!(get_regex_and_dfa(&mut no_max_len).is_err());
+
+        // The parts of two regexes compiled into a combined template.
+        let address = |prefix: &str| {
+            let mut config = crate::DecomposedRegexConfig::from_parts(&[
+                (prefix, false),
+                ("[a-z]+", true),
+                ("\\.com", false),
+            ]);
+            config.parts[1].max_len = Some(4);
+            config
+        };
+        let mut regexes = vec![
+            (
+                "from".to_string(),
+                address("from:[a-z]+@").with_constraints(
+                    serde_json::from_str(r#"[{"equal": [1, {"regex": "id", "part": 1}]}]"#)
+                        .unwrap(),
+                ),
+            ),
+            ("id".to_string(), address("id:[a-z]+@")),
+        ];
+        let combined = crate::gen_combined_circom_string(
+            &mut regexes.clone(),
+            "Test",
+            crate::CombineMode::All,
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(combined.contains("\tfrom_reveal0_len === id_reveal0_len;\n"));
+        assert!(combined.contains("\t\tfrom_reveal0_packed[j] === id_reveal0_packed[j];\n"));
+
+        regexes.truncate(1);
+        assert!(crate::gen_combined_circom_string(
+            &mut regexes,
+            "Test",
+            crate::CombineMode::All,
+            None,
+            None,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_uint_reveals() {
+        let config = |output, max_len, decimals| {
+            let mut config = crate::DecomposedRegexConfig::from_parts(&[
+                ("(\r\n|^)amount:", false),
+                ("[0-9a-z.]+", true),
+                ("\r\n", false),
+            ]);
+            config.parts[1].max_len = max_len;
+            config.parts[1].output = output;
+            config.parts[1].decimals = decimals;
+            config
+        };
+        for decimals in [0, 2] {
+            let circuit = RegexCircuit::new(
+                &get_regex_and_dfa(&mut config(
+                    PartOutput::Uint,
+                    Some(6),
+                    Some(decimals).filter(|&decimals| decimals > 0),
+                ))
+                .unwrap(),
+            );
+            for search_mode in [SearchMode::Anywhere, SearchMode::Anchored] {
+                let circom = generate(
+                    &circuit,
+                    true,
+                    CircomOptions {
+                        search_mode,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+                assert!(circom.contains("\tsignal output reveal0_value;\n"));
+                assert!(circom.contains("\tsignal output reveal0[msg_bytes];\n"));
+            }
+        }
+
+        for (output, max_len, decimals) in [
+            (PartOutput::Uint, None, None),
+            (PartOutput::Uint, Some(MAX_UINT_DIGITS), Some(1)),
+            (PartOutput::Bytes, Some(6), Some(2)),
+        ] {
+            assert!(get_regex_and_dfa(&mut config(output, max_len, decimals)).is_err());
+        }
+        let mut committed = config(PartOutput::Uint, Some(6), None);
+        committed.parts[1].committed = true;
+        assert!(get_regex_and_dfa(&mut committed).is_err());
+        let mut private = config(PartOutput::Uint, Some(6), None);
+        private.parts[1].is_public = false;
+        assert!(get_regex_and_dfa(&mut private).is_err());
+    }
 }
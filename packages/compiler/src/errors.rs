@@ -14,7 +14,7 @@ pub enum CompilerError {
     BuildError {
         regex: String,
         #[source]
-        source: regex_automata::dfa::dense::BuildError,
+        source: Box<regex_automata::dfa::dense::BuildError>,
     },
     #[error("Error in Regex: {0}")]
     RegexError(#[from] regex::Error),
@@ -26,4 +26,18 @@ pub enum CompilerError {
     NoAcceptedState,
     #[error("Accept Nodes Error: {0}")]
     AcceptNodesError(String),
+    #[error("Pattern Registry Error: {0}")]
+    RegistryError(#[from] zk_regex_apis::registry::RegistryError),
+    #[error("Pattern {0} is not found in the registry")]
+    PatternNotFound(String),
+    #[error("Transition ({from}, {to}) of substring {substr_idx} does not exist in the DFA")]
+    MissingSubstringTransition {
+        substr_idx: usize,
+        from: usize,
+        to: usize,
+    },
+    #[error("Invalid Halo2 table: {0}")]
+    InvalidHalo2Table(String),
+    #[error("The product automaton has more than {0} states")]
+    ProductStateLimit(usize),
 }
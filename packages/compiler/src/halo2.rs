@@ -1,34 +1,557 @@
 use crate::{
     errors::CompilerError,
-    regex::{get_accepted_state, get_max_state},
+    ir::{Backend, RegexCircuit},
     structs::RegexAndDFA,
 };
+use serde::{Deserialize, Serialize};
 use std::{
     fs::File,
     io::{BufWriter, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-/// Converts a RegexAndDFA structure to a text representation of the DFA.
+/// The version of the [`Halo2Table`] format.
+pub const HALO2_TABLE_VERSION: u32 = 1;
+
+/// The magic bytes at the beginning of the binary [`Halo2Table`] format.
+const HALO2_TABLE_MAGIC: &[u8; 4] = b"ZKRT";
+
+/// The magic bytes at the beginning of the binary [`Halo2MultiTable`] format.
+const HALO2_MULTI_TABLE_MAGIC: &[u8; 4] = b"ZKRM";
+
+/// A transition of a [`Halo2Table`] on the bytes in the inclusive `ranges`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Halo2Transition {
+    pub from: usize,
+    pub to: usize,
+    pub ranges: Vec<(u8, u8)>,
+}
+
+/// The substring table of a public part in a [`Halo2Table`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Halo2SubstrTable {
+    /// The name of the public part.
+    pub name: String,
+    /// The states where the substring starts.
+    pub starts: Vec<usize>,
+    /// The states where the substring ends.
+    pub ends: Vec<usize>,
+    /// The (from, to) state transitions of the substring.
+    pub transitions: Vec<(usize, usize)>,
+}
+
+/// The lookup tables of a regex for Halo2 chips, stored as versioned JSON or in a compact binary format.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Halo2Table {
+    pub version: u32,
+    pub regex: String,
+    pub init_state: usize,
+    pub accept_states: Vec<usize>,
+    pub max_state: usize,
+    pub transitions: Vec<Halo2Transition>,
+    pub substrs: Vec<Halo2SubstrTable>,
+}
+
+/// A regex of a [`Halo2MultiTable`], whose states are shifted by `state_offset` to be globally unique.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Halo2MultiRegex {
+    pub regex_id: usize,
+    pub name: String,
+    pub state_offset: usize,
+    pub table: Halo2Table,
+}
+
+/// The lookup tables of several regexes combined into one table keyed by `(regex_id, state, byte)`,
+/// so that a single lookup argument serves all of them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Halo2MultiTable {
+    pub version: u32,
+    pub regexes: Vec<Halo2MultiRegex>,
+}
+
+/// A cursor over the binary [`Halo2Table`] format.
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BinaryReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < len {
+            return Err("unexpected end of data".to_string());
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_usize(&mut self) -> Result<usize, String> {
+        Ok(self.read_u32()? as usize)
+    }
+
+    fn read_usizes(&mut self) -> Result<Vec<usize>, String> {
+        let len = self.read_usize()?;
+        (0..len).map(|_| self.read_usize()).collect()
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        let len = self.read_usize()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|e| e.to_string())
+    }
+
+    fn read_multi_header(&mut self) -> Result<(u32, usize), String> {
+        if self.take(HALO2_MULTI_TABLE_MAGIC.len())? != HALO2_MULTI_TABLE_MAGIC {
+            return Err("invalid magic bytes".to_string());
+        }
+        Ok((self.read_u32()?, self.read_usize()?))
+    }
+
+    fn read_multi_regex(&mut self) -> Result<(String, usize, &'a [u8]), String> {
+        let name = self.read_string()?;
+        let state_offset = self.read_usize()?;
+        let len = self.read_usize()?;
+        Ok((name, state_offset, self.take(len)?))
+    }
+
+    fn read_table(&mut self) -> Result<Halo2Table, String> {
+        if self.take(HALO2_TABLE_MAGIC.len())? != HALO2_TABLE_MAGIC {
+            return Err("invalid magic bytes".to_string());
+        }
+        let version = self.read_u32()?;
+        let regex = self.read_string()?;
+        let init_state = self.read_usize()?;
+        let accept_states = self.read_usizes()?;
+        let max_state = self.read_usize()?;
+        let num_transitions = self.read_usize()?;
+        let transitions = (0..num_transitions)
+            .map(|_| {
+                let from = self.read_usize()?;
+                let to = self.read_usize()?;
+                let num_ranges = self.read_usize()?;
+                let ranges = (0..num_ranges)
+                    .map(|_| Ok((self.read_u8()?, self.read_u8()?)))
+                    .collect::<Result<_, String>>()?;
+                Ok(Halo2Transition { from, to, ranges })
+            })
+            .collect::<Result<_, String>>()?;
+        let num_substrs = self.read_usize()?;
+        let substrs = (0..num_substrs)
+            .map(|_| {
+                let name = self.read_string()?;
+                let starts = self.read_usizes()?;
+                let ends = self.read_usizes()?;
+                let num_transitions = self.read_usize()?;
+                let transitions = (0..num_transitions)
+                    .map(|_| Ok((self.read_usize()?, self.read_usize()?)))
+                    .collect::<Result<_, String>>()?;
+                Ok(Halo2SubstrTable {
+                    name,
+                    starts,
+                    ends,
+                    transitions,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        if !self.bytes.is_empty() {
+            return Err("trailing data".to_string());
+        }
+
+        Ok(Halo2Table {
+            version,
+            regex,
+            init_state,
+            accept_states,
+            max_state,
+            transitions,
+            substrs,
+        })
+    }
+}
+
+fn check_version(version: u32) -> Result<(), CompilerError> {
+    if version != HALO2_TABLE_VERSION {
+        return Err(CompilerError::InvalidHalo2Table(format!(
+            "unsupported version {}",
+            version
+        )));
+    }
+    Ok(())
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn write_usizes(bytes: &mut Vec<u8>, values: &[usize]) {
+    write_u32(bytes, values.len());
+    for &value in values {
+        write_u32(bytes, value);
+    }
+}
+
+fn write_string(bytes: &mut Vec<u8>, value: &str) {
+    write_u32(bytes, value.len());
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+/// The Halo2 backend, which generates the lookup tables of a RegexCircuit.
+pub struct Halo2Backend<'a> {
+    /// The names of the public parts, one for each reveal mask. Defaults to `substr_{idx}`.
+    pub public_part_names: &'a [String],
+}
+
+impl Backend for Halo2Backend<'_> {
+    type Output = Halo2Table;
+
+    fn generate(&self, circuit: &RegexCircuit) -> Result<Halo2Table, CompilerError> {
+        if circuit.accept_states.is_empty() {
+            return Err(CompilerError::NoAcceptedState);
+        }
+        if !circuit.equalities.is_empty() {
+            return Err(CompilerError::GenericError(
+                "The Halo2 backend does not support the equality constraints".to_string(),
+            ));
+        }
+
+        let mut transitions = circuit
+            .transitions
+            .iter()
+            .map(|transition| Halo2Transition {
+                from: transition.from,
+                to: transition.to,
+                ranges: transition.ranges.clone(),
+            })
+            .collect::<Vec<_>>();
+        transitions.sort_by_key(|transition| (transition.from, transition.to));
+
+        let substrs = circuit
+            .reveals
+            .iter()
+            .enumerate()
+            .map(|(idx, reveal)| {
+                let (starts, ends) = reveal.boundaries.as_ref().ok_or_else(|| {
+                    CompilerError::GenericError("Substring boundaries are not computed".to_string())
+                })?;
+                Ok(Halo2SubstrTable {
+                    name: self
+                        .public_part_names
+                        .get(idx)
+                        .cloned()
+                        .unwrap_or_else(|| format!("substr_{}", idx)),
+                    starts: starts.iter().copied().collect(),
+                    ends: ends.iter().copied().collect(),
+                    transitions: reveal.transitions.iter().copied().collect(),
+                })
+            })
+            .collect::<Result<Vec<_>, CompilerError>>()?;
+
+        Ok(Halo2Table {
+            version: HALO2_TABLE_VERSION,
+            regex: circuit.regex.clone(),
+            init_state: 0,
+            accept_states: circuit.accept_states.iter().copied().collect(),
+            max_state: circuit.max_state,
+            transitions,
+            substrs,
+        })
+    }
+}
+
+impl Halo2Table {
+    /// Builds the tables of a RegexAndDFA structure.
+    ///
+    /// # Arguments
+    ///
+    /// * `regex_and_dfa` - A reference to the RegexAndDFA structure.
+    /// * `public_part_names` - The names of the public parts, one for each substring.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the Halo2Table or a CompilerError.
+    pub(crate) fn new(
+        regex_and_dfa: &RegexAndDFA,
+        public_part_names: &[String],
+    ) -> Result<Self, CompilerError> {
+        Halo2Backend { public_part_names }.generate(&RegexCircuit::new(regex_and_dfa))
+    }
+
+    /// Returns the (from, to, byte) rows of the transition lookup table.
+    pub fn lookup_rows(&self) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+        self.transitions.iter().flat_map(|transition| {
+            transition.ranges.iter().flat_map(move |&(min, max)| {
+                (min..=max).map(move |byte| (transition.from, transition.to, byte))
+            })
+        })
+    }
+
+    /// Checks that the table version is supported.
+    fn check_version(self) -> Result<Self, CompilerError> {
+        check_version(self.version)?;
+        Ok(self)
+    }
+
+    /// Shifts all state ids of the table by `offset`.
+    fn offset_states(&mut self, offset: usize) {
+        self.init_state += offset;
+        self.max_state += offset;
+        for state in self.accept_states.iter_mut() {
+            *state += offset;
+        }
+        for transition in self.transitions.iter_mut() {
+            transition.from += offset;
+            transition.to += offset;
+        }
+        for substr in self.substrs.iter_mut() {
+            for state in substr.starts.iter_mut().chain(substr.ends.iter_mut()) {
+                *state += offset;
+            }
+            for (from, to) in substr.transitions.iter_mut() {
+                *from += offset;
+                *to += offset;
+            }
+        }
+    }
+
+    /// Parses a table from its JSON format.
+    pub fn from_json(json: &str) -> Result<Self, CompilerError> {
+        serde_json::from_str::<Self>(json)?.check_version()
+    }
+
+    /// Serializes the table into its JSON format.
+    pub fn to_json(&self) -> Result<String, CompilerError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Serializes the table into its binary format: the magic bytes `ZKRT` followed by little-endian u32 fields.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = HALO2_TABLE_MAGIC.to_vec();
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        write_string(&mut bytes, &self.regex);
+        write_u32(&mut bytes, self.init_state);
+        write_usizes(&mut bytes, &self.accept_states);
+        write_u32(&mut bytes, self.max_state);
+        write_u32(&mut bytes, self.transitions.len());
+        for transition in &self.transitions {
+            write_u32(&mut bytes, transition.from);
+            write_u32(&mut bytes, transition.to);
+            write_u32(&mut bytes, transition.ranges.len());
+            for &(min, max) in &transition.ranges {
+                bytes.extend_from_slice(&[min, max]);
+            }
+        }
+        write_u32(&mut bytes, self.substrs.len());
+        for substr in &self.substrs {
+            write_string(&mut bytes, &substr.name);
+            write_usizes(&mut bytes, &substr.starts);
+            write_usizes(&mut bytes, &substr.ends);
+            write_u32(&mut bytes, substr.transitions.len());
+            for &(from, to) in &substr.transitions {
+                write_u32(&mut bytes, from);
+                write_u32(&mut bytes, to);
+            }
+        }
+        bytes
+    }
+
+    /// Parses a table from its binary format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CompilerError> {
+        BinaryReader { bytes }
+            .read_table()
+            .map_err(CompilerError::InvalidHalo2Table)?
+            .check_version()
+    }
+
+    /// Loads a table from a file in the JSON or binary format, detected from its first bytes.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CompilerError> {
+        let bytes = std::fs::read(path)?;
+        if bytes.starts_with(HALO2_TABLE_MAGIC) {
+            Self::from_bytes(&bytes)
+        } else {
+            let json = String::from_utf8(bytes)
+                .map_err(|e| CompilerError::InvalidHalo2Table(e.to_string()))?;
+            Self::from_json(&json)
+        }
+    }
+}
+
+impl Halo2MultiTable {
+    /// Combines the tables of several regexes, assigning each regex its index as `regex_id` and shifting its
+    /// states past the states of the previous regexes.
+    ///
+    /// # Arguments
+    ///
+    /// * `tables` - The names and tables of the regexes, with the states of each table starting at 0.
+    ///
+    /// # Returns
+    ///
+    /// The combined Halo2MultiTable.
+    pub fn new(tables: Vec<(String, Halo2Table)>) -> Self {
+        let mut state_offset = 0;
+        let regexes = tables
+            .into_iter()
+            .enumerate()
+            .map(|(regex_id, (name, mut table))| {
+                let num_states = table.max_state + 1;
+                table.offset_states(state_offset);
+                let regex = Halo2MultiRegex {
+                    regex_id,
+                    name,
+                    state_offset,
+                    table,
+                };
+                state_offset += num_states;
+                regex
+            })
+            .collect();
+        Self {
+            version: HALO2_TABLE_VERSION,
+            regexes,
+        }
+    }
+
+    /// Returns the (regex_id, from, byte, to) rows of the combined transition lookup table.
+    pub fn lookup_rows(&self) -> impl Iterator<Item = (usize, usize, u8, usize)> + '_ {
+        self.regexes.iter().flat_map(|regex| {
+            regex
+                .table
+                .lookup_rows()
+                .map(move |(from, to, byte)| (regex.regex_id, from, byte, to))
+        })
+    }
+
+    /// Returns the regex with the given name.
+    pub fn get(&self, name: &str) -> Option<&Halo2MultiRegex> {
+        self.regexes.iter().find(|regex| regex.name == name)
+    }
+
+    /// Parses a table from its JSON format.
+    pub fn from_json(json: &str) -> Result<Self, CompilerError> {
+        let table = serde_json::from_str::<Self>(json)?;
+        check_version(table.version)?;
+        for regex in &table.regexes {
+            check_version(regex.table.version)?;
+        }
+        Ok(table)
+    }
+
+    /// Serializes the table into its JSON format.
+    pub fn to_json(&self) -> Result<String, CompilerError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Serializes the table into its binary format: the magic bytes `ZKRM` followed by the name, state offset
+    /// and length-prefixed binary [`Halo2Table`] of each regex.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = HALO2_MULTI_TABLE_MAGIC.to_vec();
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        write_u32(&mut bytes, self.regexes.len());
+        for regex in &self.regexes {
+            write_string(&mut bytes, &regex.name);
+            write_u32(&mut bytes, regex.state_offset);
+            let table = regex.table.to_bytes();
+            write_u32(&mut bytes, table.len());
+            bytes.extend_from_slice(&table);
+        }
+        bytes
+    }
+
+    /// Parses a table from its binary format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CompilerError> {
+        let mut reader = BinaryReader { bytes };
+        let (version, num_regexes) = reader
+            .read_multi_header()
+            .map_err(CompilerError::InvalidHalo2Table)?;
+        check_version(version)?;
+        let mut regexes = vec![];
+        for regex_id in 0..num_regexes {
+            let (name, state_offset, table) = reader
+                .read_multi_regex()
+                .map_err(CompilerError::InvalidHalo2Table)?;
+            regexes.push(Halo2MultiRegex {
+                regex_id,
+                name,
+                state_offset,
+                table: Halo2Table::from_bytes(table)?,
+            });
+        }
+        if !reader.bytes.is_empty() {
+            return Err(CompilerError::InvalidHalo2Table(
+                "trailing data".to_string(),
+            ));
+        }
+        Ok(Self { version, regexes })
+    }
+
+    /// Loads a table from a file in the JSON or binary format, detected from its first bytes.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CompilerError> {
+        let bytes = std::fs::read(path)?;
+        if bytes.starts_with(HALO2_MULTI_TABLE_MAGIC) {
+            Self::from_bytes(&bytes)
+        } else {
+            let json = String::from_utf8(bytes)
+                .map_err(|e| CompilerError::InvalidHalo2Table(e.to_string()))?;
+            Self::from_json(&json)
+        }
+    }
+}
+
+/// Writes the table in `table.json` of a Halo2 output directory in the binary format to `table.bin`.
 ///
 /// # Arguments
 ///
-/// * `regex_and_dfa` - A reference to the RegexAndDFA structure.
+/// * `halo2_dir_path` - The path to the directory for Halo2 output files.
+///
+/// # Returns
+///
+/// A Result indicating success or containing a CompilerError.
+pub fn write_halo2_binary(halo2_dir_path: impl AsRef<Path>) -> Result<(), CompilerError> {
+    let halo2_dir_path = halo2_dir_path.as_ref();
+    let table = Halo2Table::load(halo2_dir_path.join("table.json"))?;
+    std::fs::write(halo2_dir_path.join("table.bin"), table.to_bytes())?;
+    Ok(())
+}
+
+/// Writes the table in `multi_table.json` of a Halo2 output directory in the binary format to `multi_table.bin`.
+///
+/// # Arguments
+///
+/// * `halo2_dir_path` - The path to the directory for Halo2 output files.
+///
+/// # Returns
+///
+/// A Result indicating success or containing a CompilerError.
+pub fn write_halo2_multi_binary(halo2_dir_path: impl AsRef<Path>) -> Result<(), CompilerError> {
+    let halo2_dir_path = halo2_dir_path.as_ref();
+    let table = Halo2MultiTable::load(halo2_dir_path.join("multi_table.json"))?;
+    std::fs::write(halo2_dir_path.join("multi_table.bin"), table.to_bytes())?;
+    Ok(())
+}
+
+/// Converts a Halo2Table to the text representation of the DFA: the initial, accept and max states, followed by
+/// one `from to byte` line per row of the lookup table.
+///
+/// # Arguments
+///
+/// * `table` - A reference to the Halo2Table.
 ///
 /// # Returns
 ///
 /// A String containing the text representation of the DFA.
-fn dfa_to_regex_def_text(regex_and_dfa: &RegexAndDFA) -> String {
-    let accepted_state = get_accepted_state(&regex_and_dfa.dfa).unwrap();
-    let max_state = get_max_state(&regex_and_dfa.dfa);
-    let mut text = format!("0\n{}\n{}\n", accepted_state, max_state);
-
-    for (i, state) in regex_and_dfa.dfa.states.iter().enumerate() {
-        for (next_state, chars) in state.transitions.iter() {
-            for &char in chars {
-                text += &format!("{} {} {}\n", i, next_state, char as u8);
-            }
-        }
+fn table_to_regex_def_text(table: &Halo2Table) -> String {
+    let mut text = format!(
+        "{}\n{}\n{}\n",
+        table.init_state, table.accept_states[0], table.max_state
+    );
+    for (from, to, byte) in table.lookup_rows() {
+        text += &format!("{} {} {}\n", from, to, byte);
     }
     text
 }
@@ -40,6 +563,8 @@ fn dfa_to_regex_def_text(regex_and_dfa: &RegexAndDFA) -> String {
 /// * `regex_and_dfa` - A reference to the RegexAndDFA structure.
 /// * `allstr_file_path` - The path where the main DFA definition will be written.
 /// * `substr_file_paths` - A slice of paths where substring definitions will be written.
+/// * `table_file_path` - The path where the versioned JSON table will be written.
+/// * `public_part_names` - The names of the public parts, written to the JSON table.
 /// * `gen_substrs` - A boolean indicating whether to generate substring files.
 ///
 /// # Returns
@@ -49,48 +574,154 @@ pub(crate) fn gen_halo2_tables(
     regex_and_dfa: &RegexAndDFA,
     allstr_file_path: &PathBuf,
     substr_file_paths: &[PathBuf],
+    table_file_path: &PathBuf,
+    public_part_names: &[String],
     gen_substrs: bool,
 ) -> Result<(), CompilerError> {
-    let regex_text = dfa_to_regex_def_text(regex_and_dfa);
-    std::fs::write(allstr_file_path, regex_text)?;
+    let mut table = Halo2Table::new(regex_and_dfa, public_part_names)?;
+    std::fs::write(allstr_file_path, table_to_regex_def_text(&table))?;
 
-    if !gen_substrs {
-        return Ok(());
+    if gen_substrs {
+        for (substr, substr_file_path) in table.substrs.iter().zip(substr_file_paths) {
+            let mut writer = BufWriter::new(File::create(substr_file_path)?);
+            writeln!(
+                writer,
+                "{}",
+                substr
+                    .starts
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )?;
+            writeln!(
+                writer,
+                "{}",
+                substr
+                    .ends
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )?;
+            for &(cur, next) in &substr.transitions {
+                writeln!(writer, "{} {}", cur, next)?;
+            }
+        }
+    } else {
+        table.substrs.clear();
     }
+    std::fs::write(table_file_path, table.to_json()?)?;
 
-    for (idx, defs) in regex_and_dfa.substrings.substring_ranges.iter().enumerate() {
-        let mut writer = BufWriter::new(File::create(&substr_file_paths[idx])?);
-        let (starts, ends) = &regex_and_dfa
-            .substrings
-            .substring_boundaries
-            .as_ref()
-            .unwrap()[idx];
+    Ok(())
+}
 
-        writeln!(
-            writer,
-            "{}",
-            starts
-                .iter()
-                .map(ToString::to_string)
-                .collect::<Vec<_>>()
-                .join(" ")
-        )?;
-        writeln!(
-            writer,
-            "{}",
-            ends.iter()
-                .map(ToString::to_string)
-                .collect::<Vec<_>>()
-                .join(" ")
-        )?;
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{regex::get_regex_and_dfa, registry_pattern_config};
 
-        let mut sorted_defs: Vec<_> = defs.iter().collect();
-        sorted_defs.sort_unstable_by_key(|&(start, end)| (*start, *end));
+    fn builtin_table(name: &str) -> Halo2Table {
+        let registry = zk_regex_apis::registry::PatternRegistry::builtin();
+        let mut config = registry_pattern_config(&registry, name).unwrap();
+        let regex_and_dfa = get_regex_and_dfa(&mut config).unwrap();
+        let public_parts = registry.get(name).unwrap().def.public_parts.clone();
+        Halo2Table::new(&regex_and_dfa, &public_parts).unwrap()
+    }
 
-        for &(cur, next) in &sorted_defs {
-            writeln!(writer, "{} {}", cur, next)?;
-        }
+    #[test]
+    fn test_halo2_table_ranges() {
+        let table = builtin_table("subject_all");
+        assert_eq!(table.version, HALO2_TABLE_VERSION);
+        assert_eq!(table.substrs.len(), 1);
+        assert_eq!(table.substrs[0].name, "subject_all");
+        assert!(!table.substrs[0].starts.is_empty());
+        assert!(!table.substrs[0].ends.is_empty());
+        // The ASCII bytes of `[^\r\n]+` are encoded as three ranges instead of 126 rows.
+        assert!(table
+            .transitions
+            .iter()
+            .any(|transition| transition.from == 10
+                && transition.ranges == vec![(0, 9), (11, 12), (14, 127)]));
+        assert!(table.lookup_rows().count() > table.transitions.len() * 10);
     }
 
-    Ok(())
+    #[test]
+    fn test_halo2_table_roundtrip() {
+        let table = builtin_table("from_addr");
+        assert_eq!(
+            Halo2Table::from_json(&table.to_json().unwrap()).unwrap(),
+            table
+        );
+        let bytes = table.to_bytes();
+        assert_eq!(Halo2Table::from_bytes(&bytes).unwrap(), table);
+        assert!(matches!(
+            Halo2Table::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(CompilerError::InvalidHalo2Table(_))
+        ));
+
+        let mut other_version = table.clone();
+        other_version.version += 1;
+        assert!(matches!(
+            Halo2Table::from_json(&other_version.to_json().unwrap()),
+            Err(CompilerError::InvalidHalo2Table(_))
+        ));
+    }
+
+    #[test]
+    fn test_halo2_multi_table() {
+        let names = ["from_addr", "subject_all", "timestamp"];
+        let tables = names.map(builtin_table);
+        let multi_table = Halo2MultiTable::new(
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .zip(tables.iter().cloned())
+                .collect(),
+        );
+
+        let mut state_offset = 0;
+        for (regex_id, table) in tables.iter().enumerate() {
+            let regex = &multi_table.regexes[regex_id];
+            assert_eq!(regex.regex_id, regex_id);
+            assert_eq!(regex.name, names[regex_id]);
+            assert_eq!(regex.state_offset, state_offset);
+            assert_eq!(regex.table.init_state, state_offset);
+            assert_eq!(
+                regex.table.substrs[0].ends,
+                table.substrs[0]
+                    .ends
+                    .iter()
+                    .map(|state| state + state_offset)
+                    .collect::<Vec<_>>()
+            );
+            state_offset += table.max_state + 1;
+        }
+
+        // The state ids are globally unique, so (regex_id, from, byte) determines the next state.
+        let rows = multi_table.lookup_rows().collect::<Vec<_>>();
+        assert_eq!(
+            rows.len(),
+            tables
+                .iter()
+                .map(|table| table.lookup_rows().count())
+                .sum::<usize>()
+        );
+        let states = rows
+            .iter()
+            .map(|&(regex_id, from, _, _)| (from, regex_id))
+            .collect::<std::collections::BTreeMap<_, _>>();
+        assert!(rows
+            .iter()
+            .all(|(regex_id, from, _, _)| states[from] == *regex_id));
+
+        assert_eq!(
+            Halo2MultiTable::from_json(&multi_table.to_json().unwrap()).unwrap(),
+            multi_table
+        );
+        assert_eq!(
+            Halo2MultiTable::from_bytes(&multi_table.to_bytes()).unwrap(),
+            multi_table
+        );
+    }
 }
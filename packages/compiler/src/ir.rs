@@ -0,0 +1,576 @@
+use crate::{errors::CompilerError, regex::get_max_state, structs::RegexAndDFA};
+use std::collections::{BTreeMap, BTreeSet};
+use zk_regex_apis::extract_substrs::MatchPolicy;
+
+/// A check on the input byte at a position, shared by all transitions that use it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BytePredicate {
+    /// The byte is equal to the value.
+    Eq(u8),
+    /// The byte is in the inclusive range `(min, max)`.
+    Range(u8, u8),
+}
+
+/// A state transition of a [`RegexCircuit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitTransition {
+    pub from: usize,
+    pub to: usize,
+    /// All bytes of the transition as inclusive ranges, including the byte `0`.
+    pub ranges: Vec<(u8, u8)>,
+    /// The indexes into [`RegexCircuit::predicates`] whose OR enables the transition, excluding the byte `0`.
+    pub predicates: Vec<usize>,
+}
+
+/// The reveal mask of a public part of a [`RegexCircuit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevealMask {
+    /// The (from, to) transitions whose bytes are revealed.
+    pub transitions: BTreeSet<(usize, usize)>,
+    /// The states where the substring starts and ends, if computed.
+    pub boundaries: Option<(BTreeSet<usize>, BTreeSet<usize>)>,
+    /// The length of the packed output of the first revealed substring, if any.
+    pub max_len: Option<usize>,
+    /// Whether the substring is committed instead of revealed.
+    pub committed: bool,
+    /// The number of decimals of the numeric value output of the first revealed substring, if any.
+    pub uint_decimals: Option<usize>,
+}
+
+impl RevealMask {
+    /// Returns the indexes into `transitions` of the transitions from a start state of the substring.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the indexes in the order of `transitions`, or a CompilerError if the boundaries are not
+    /// computed.
+    pub fn start_transitions(&self) -> Result<Vec<usize>, CompilerError> {
+        let (starts, _) = self.boundaries.as_ref().ok_or_else(|| {
+            CompilerError::GenericError("Substring boundaries are not computed".to_string())
+        })?;
+        Ok(self
+            .transitions
+            .iter()
+            .enumerate()
+            .filter(|(_, (from, _))| starts.contains(from))
+            .map(|(trans_idx, _)| trans_idx)
+            .collect())
+    }
+}
+
+/// The operations of a regex circuit independent of the proving system: byte predicates, state transitions,
+/// accept logic and reveal masks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexCircuit {
+    pub regex: String,
+    pub num_states: usize,
+    pub max_state: usize,
+    pub accept_states: BTreeSet<usize>,
+    pub has_end_anchor: bool,
+    /// The byte predicates in the order of their first use by `transitions`.
+    pub predicates: Vec<BytePredicate>,
+    /// The transitions ordered by (to, from).
+    pub transitions: Vec<CircuitTransition>,
+    pub reveals: Vec<RevealMask>,
+    /// The pairs of indexes into `reveals` whose substrings are constrained to be equal.
+    pub equalities: Vec<(usize, usize)>,
+    /// Which match the reveal masks reveal.
+    pub match_policy: MatchPolicy,
+}
+
+/// The partition of the bytes into equivalence classes, where two bytes are equivalent if every transition of
+/// a [`RegexCircuit`] either contains both or neither of them.
+///
+/// The byte `0` never transitions, so its class is always `0` and is used by no transition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteClasses {
+    /// The class of each byte.
+    pub class_of: Vec<usize>,
+    pub num_classes: usize,
+    /// The maximal inclusive ranges of consecutive bytes in the same class.
+    pub segments: Vec<(u8, u8)>,
+    /// The classes of the transitions of the circuit, in the same order.
+    pub transition_classes: Vec<Vec<usize>>,
+}
+
+/// A code generator from a [`RegexCircuit`] for a proving system.
+pub trait Backend {
+    type Output;
+
+    /// Generates the output of the backend for the circuit.
+    fn generate(&self, circuit: &RegexCircuit) -> Result<Self::Output, CompilerError>;
+}
+
+/// Groups sorted bytes into inclusive ranges of consecutive bytes.
+pub(crate) fn bytes_to_ranges<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> Vec<(u8, u8)> {
+    let mut ranges: Vec<(u8, u8)> = vec![];
+    for &byte in bytes {
+        match ranges.last_mut() {
+            Some((_, max)) if *max as u16 + 1 == byte as u16 => *max = byte,
+            _ => ranges.push((byte, byte)),
+        }
+    }
+    ranges
+}
+
+/// Optimizes character ranges by grouping consecutive characters and identifying individual characters.
+///
+/// This function takes a slice of u8 values (representing ASCII characters) and groups them into
+/// ranges where possible, while also identifying individual characters that don't fit into ranges.
+///
+/// # Arguments
+///
+/// * `k` - A slice of u8 values representing ASCII characters.
+///
+/// # Returns
+///
+/// A tuple containing:
+/// * A Vec of (u8, u8) tuples representing optimized character ranges (min, max).
+/// * A BTreeSet of u8 values representing individual characters not included in ranges.
+///
+/// # Note
+///
+/// Ranges are only created for sequences of 16 or more consecutive characters.
+fn optimize_char_ranges(k: &[u8]) -> (Vec<(u8, u8)>, BTreeSet<u8>) {
+    let mut min_maxes = vec![];
+    let mut vals = k.iter().cloned().collect::<BTreeSet<u8>>();
+
+    if k.is_empty() {
+        return (min_maxes, vals);
+    }
+
+    let mut cur_min = k[0];
+    let mut cur_max = k[0];
+
+    for &val in &k[1..] {
+        if cur_max == val {
+            continue;
+        } else if cur_max + 1 == val {
+            cur_max = val;
+        } else {
+            if cur_max - cur_min >= 16 {
+                min_maxes.push((cur_min, cur_max));
+            }
+            cur_min = val;
+            cur_max = val;
+        }
+    }
+
+    if cur_max - cur_min >= 16 {
+        min_maxes.push((cur_min, cur_max));
+    }
+
+    for (min, max) in &min_maxes {
+        for code in *min..=*max {
+            vals.remove(&code);
+        }
+    }
+
+    (min_maxes, vals)
+}
+
+impl RegexCircuit {
+    /// Lowers a RegexAndDFA structure into the circuit IR.
+    ///
+    /// The bytes of each transition are split into range and equality predicates, which are deduplicated
+    /// across all transitions.
+    ///
+    /// # Arguments
+    ///
+    /// * `regex_and_dfa` - A reference to the RegexAndDFA structure.
+    ///
+    /// # Returns
+    ///
+    /// The RegexCircuit of the DFA.
+    pub fn new(regex_and_dfa: &RegexAndDFA) -> Self {
+        let dfa = &regex_and_dfa.dfa;
+
+        let mut rev_graph = BTreeMap::<usize, BTreeMap<usize, &BTreeSet<u8>>>::new();
+        for (from, state) in dfa.states.iter().enumerate() {
+            for (&to, chars) in &state.transitions {
+                rev_graph.entry(to).or_default().insert(from, chars);
+            }
+        }
+
+        let mut predicates = vec![];
+        let mut predicate_idxes = BTreeMap::<BytePredicate, usize>::new();
+        let mut transitions = vec![];
+        for (&to, prevs) in &rev_graph {
+            for (&from, &chars) in prevs {
+                let k = chars
+                    .iter()
+                    .copied()
+                    .filter(|&char| char != 0)
+                    .collect::<Vec<_>>();
+                let (min_maxes, individual_chars) = optimize_char_ranges(&k);
+                let transition_predicates = min_maxes
+                    .into_iter()
+                    .map(|(min, max)| BytePredicate::Range(min, max))
+                    .chain(individual_chars.into_iter().map(BytePredicate::Eq))
+                    .map(|predicate| {
+                        *predicate_idxes.entry(predicate).or_insert_with(|| {
+                            predicates.push(predicate);
+                            predicates.len() - 1
+                        })
+                    })
+                    .collect();
+                transitions.push(CircuitTransition {
+                    from,
+                    to,
+                    ranges: bytes_to_ranges(chars),
+                    predicates: transition_predicates,
+                });
+            }
+        }
+
+        let reveals = regex_and_dfa
+            .substrings
+            .substring_ranges
+            .iter()
+            .enumerate()
+            .map(|(idx, ranges)| RevealMask {
+                transitions: ranges.clone(),
+                boundaries: regex_and_dfa
+                    .substrings
+                    .substring_boundaries
+                    .as_ref()
+                    .and_then(|boundaries| boundaries.get(idx).cloned()),
+                max_len: regex_and_dfa
+                    .substrings
+                    .max_lens
+                    .get(idx)
+                    .copied()
+                    .flatten(),
+                committed: regex_and_dfa
+                    .substrings
+                    .committed
+                    .get(idx)
+                    .copied()
+                    .unwrap_or(false),
+                uint_decimals: regex_and_dfa
+                    .substrings
+                    .uint_decimals
+                    .get(idx)
+                    .copied()
+                    .flatten(),
+            })
+            .collect();
+
+        Self {
+            regex: regex_and_dfa.regex_pattern.clone(),
+            num_states: dfa.states.len(),
+            max_state: get_max_state(dfa),
+            accept_states: dfa
+                .states
+                .iter()
+                .enumerate()
+                .filter(|(_, state)| state.state_type == "accept")
+                .map(|(i, _)| i)
+                .collect(),
+            has_end_anchor: regex_and_dfa.has_end_anchor,
+            predicates,
+            transitions,
+            reveals,
+            equalities: regex_and_dfa.substrings.equal.clone(),
+            match_policy: regex_and_dfa.match_policy,
+        }
+    }
+
+    /// Computes the byte equivalence classes of the transitions.
+    ///
+    /// # Returns
+    ///
+    /// The ByteClasses of the circuit.
+    pub fn byte_classes(&self) -> ByteClasses {
+        // The signature of a byte is the set of transitions containing it.
+        let mut signatures = vec![vec![]; 256];
+        for (transition_idx, transition) in self.transitions.iter().enumerate() {
+            for &(min, max) in &transition.ranges {
+                for byte in min.max(1)..=max {
+                    signatures[byte as usize].push(transition_idx);
+                }
+            }
+        }
+
+        let mut class_idxes = BTreeMap::<&Vec<usize>, usize>::new();
+        let class_of = signatures
+            .iter()
+            .map(|signature| {
+                let num_classes = class_idxes.len();
+                *class_idxes.entry(signature).or_insert(num_classes)
+            })
+            .collect::<Vec<_>>();
+
+        let mut segments: Vec<(u8, u8)> = vec![];
+        for byte in 0..=255u8 {
+            match segments.last_mut() {
+                Some((_, max)) if class_of[*max as usize] == class_of[byte as usize] => *max = byte,
+                _ => segments.push((byte, byte)),
+            }
+        }
+
+        let transition_classes = self
+            .transitions
+            .iter()
+            .map(|transition| {
+                transition
+                    .ranges
+                    .iter()
+                    .flat_map(|&(min, max)| min.max(1)..=max)
+                    .map(|byte| class_of[byte as usize])
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect()
+            })
+            .collect();
+
+        ByteClasses {
+            num_classes: class_idxes.len(),
+            class_of,
+            segments,
+            transition_classes,
+        }
+    }
+
+    /// Checks whether the predicates are mutually exclusive, i.e., no byte satisfies two of them, so that their
+    /// OR is their sum.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate_idxes` - The indexes into `predicates` to check.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the byte ranges of the predicates are pairwise disjoint.
+    pub fn predicates_are_exclusive(&self, predicate_idxes: &[usize]) -> bool {
+        let mut ranges = predicate_idxes
+            .iter()
+            .map(|&predicate_idx| match self.predicates[predicate_idx] {
+                BytePredicate::Eq(byte) => (byte, byte),
+                BytePredicate::Range(min, max) => (min, max),
+            })
+            .collect::<Vec<_>>();
+        ranges.sort();
+        ranges.windows(2).all(|pair| pair[0].1 < pair[1].0)
+    }
+
+    /// Returns the transitions into the state `to`, ordered by their source state.
+    pub fn transitions_to(&self, to: usize) -> impl Iterator<Item = &CircuitTransition> {
+        self.transitions
+            .iter()
+            .filter(move |transition| transition.to == to)
+    }
+
+    /// Returns the accept state, which must be unique for the backends that support a single accept state.
+    pub fn unique_accept_state(&self) -> Result<usize, CompilerError> {
+        match self.accept_states.len() {
+            0 => Err(CompilerError::AcceptNodesError(
+                "Accept node must exist".to_string(),
+            )),
+            1 => Ok(*self.accept_states.iter().next().unwrap()),
+            _ => Err(CompilerError::AcceptNodesError(
+                "The size of accept nodes must be one".to_string(),
+            )),
+        }
+    }
+
+    /// Returns the transitions of a single run of the DFA from a position of `msg`, as in the anchored search
+    /// modes.
+    ///
+    /// The run starts in the state reached from `0` by the `255` prepended to `msg` and, on the first byte only, in
+    /// the state `0`, whose transitions are restricted to the bytes without a transition from the former state. This
+    /// matches the default circuit, where the transitions from `0` are taken whenever no other transition is, so
+    /// that a regex such as `(\r\n|^)from:` also matches `\r\nfrom:` at the start.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the [`AnchoredRun`], or a CompilerError if the `255` leads to several states.
+    pub fn anchored_run(&self) -> Result<AnchoredRun, CompilerError> {
+        let init_states = self
+            .transitions
+            .iter()
+            .filter(|transition| {
+                transition.from == 0 && transition.ranges.iter().any(|&(_, max)| max == 255)
+            })
+            .map(|transition| transition.to)
+            .collect::<BTreeSet<_>>();
+        if init_states.len() > 1 {
+            return Err(CompilerError::GenericError(format!(
+                "The anchored search modes require a single state after the start of the input, but there are {:?}",
+                init_states
+            )));
+        }
+        let init_state = init_states.into_iter().next().unwrap_or(0);
+
+        let predicate_ranges = |transition: &CircuitTransition| {
+            transition
+                .predicates
+                .iter()
+                .map(|&predicate_idx| match self.predicates[predicate_idx] {
+                    BytePredicate::Eq(byte) => (byte, byte),
+                    BytePredicate::Range(min, max) => (min, max),
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut start_transitions = vec![];
+        if init_state != 0 {
+            let init_bytes = self
+                .transitions
+                .iter()
+                .filter(|transition| transition.from == init_state)
+                .flat_map(|transition| transition.ranges.iter().flat_map(|&(min, max)| min..=max))
+                .collect::<BTreeSet<u8>>();
+            for transition in self
+                .transitions
+                .iter()
+                .filter(|transition| transition.from == 0)
+            {
+                let bytes = transition
+                    .ranges
+                    .iter()
+                    .flat_map(|&(min, max)| min..=max)
+                    .filter(|byte| !matches!(byte, 0 | 255) && !init_bytes.contains(byte))
+                    .collect::<Vec<_>>();
+                if !bytes.is_empty() {
+                    start_transitions.push(AnchoredTransition {
+                        from: 0,
+                        to: transition.to,
+                        ranges: bytes_to_ranges(&bytes),
+                    });
+                }
+            }
+        }
+
+        let mut reachable = BTreeSet::from([init_state]);
+        reachable.extend(start_transitions.iter().map(|transition| transition.to));
+        while let Some(next) = self.transitions.iter().find_map(|transition| {
+            (reachable.contains(&transition.from)
+                && !reachable.contains(&transition.to)
+                && !transition.predicates.is_empty())
+            .then_some(transition.to)
+        }) {
+            reachable.insert(next);
+        }
+        let transitions = start_transitions
+            .into_iter()
+            .chain(
+                self.transitions
+                    .iter()
+                    .filter(|transition| {
+                        reachable.contains(&transition.from)
+                            && (transition.from != 0 || init_state == 0)
+                            && !transition.predicates.is_empty()
+                    })
+                    .map(|transition| AnchoredTransition {
+                        from: transition.from,
+                        to: transition.to,
+                        ranges: predicate_ranges(transition),
+                    }),
+            )
+            .collect();
+        Ok(AnchoredRun {
+            init_state,
+            transitions,
+        })
+    }
+}
+
+/// A single run of the DFA of a [`RegexCircuit`] from a position of `msg`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchoredRun {
+    /// The state reached from `0` by the `255` prepended to `msg`, or `0` if there is none.
+    pub init_state: usize,
+    /// The transitions of the run. The transitions from `0` are those of the first byte if `init_state` is not `0`.
+    pub transitions: Vec<AnchoredTransition>,
+}
+
+/// A state transition of an [`AnchoredRun`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchoredTransition {
+    pub from: usize,
+    pub to: usize,
+    /// The inclusive byte ranges enabling the transition.
+    pub ranges: Vec<(u8, u8)>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::regex::create_regex_and_dfa_from_str_and_defs;
+    use crate::structs::SubstringDefinitionsJson;
+
+    #[test]
+    fn test_predicates_are_shared() {
+        let regex_and_dfa = create_regex_and_dfa_from_str_and_defs(
+            "a[a-z]+b[a-z]+",
+            SubstringDefinitionsJson {
+                transitions: vec![vec![]],
+            },
+        )
+        .unwrap();
+        let circuit = RegexCircuit::new(&regex_and_dfa);
+
+        let unique_predicates = circuit.predicates.iter().collect::<BTreeSet<_>>();
+        assert_eq!(unique_predicates.len(), circuit.predicates.len());
+        // Both `[a-z]+` loops use the same range predicate.
+        let range_idx = circuit
+            .predicates
+            .iter()
+            .position(|&predicate| predicate == BytePredicate::Range(b'a', b'z'))
+            .unwrap();
+        assert!(
+            circuit
+                .transitions
+                .iter()
+                .filter(|transition| transition.predicates.contains(&range_idx))
+                .count()
+                >= 2
+        );
+        assert!(circuit.unique_accept_state().is_ok());
+        assert!(circuit
+            .transitions
+            .iter()
+            .all(|transition| circuit.predicates_are_exclusive(&transition.predicates)));
+    }
+
+    #[test]
+    fn test_byte_classes() {
+        let regex_and_dfa = create_regex_and_dfa_from_str_and_defs(
+            "[a-z]+@[a-z0-9]+",
+            SubstringDefinitionsJson {
+                transitions: vec![vec![]],
+            },
+        )
+        .unwrap();
+        let circuit = RegexCircuit::new(&regex_and_dfa);
+        let classes = circuit.byte_classes();
+
+        // The classes are the unused bytes, `[a-z]`, `[0-9]` and `@`.
+        assert_eq!(classes.num_classes, 4);
+        assert_eq!(classes.class_of[0], 0);
+        assert_eq!(
+            classes.class_of[b'a' as usize],
+            classes.class_of[b'z' as usize]
+        );
+        assert_ne!(
+            classes.class_of[b'a' as usize],
+            classes.class_of[b'0' as usize]
+        );
+        assert_eq!(
+            classes.segments,
+            vec![
+                (0, 47),
+                (48, 57),
+                (58, 63),
+                (64, 64),
+                (65, 96),
+                (97, 122),
+                (123, 255)
+            ]
+        );
+        assert!(classes
+            .transition_classes
+            .iter()
+            .all(|transition_classes| !transition_classes.contains(&0)));
+    }
+}
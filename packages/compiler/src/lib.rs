@@ -1,19 +1,45 @@
 mod circom;
 mod errors;
 mod halo2;
+mod ir;
+mod negate;
+mod product;
 mod regex;
 mod structs;
+mod trace;
 mod wasm;
 
-use circom::gen_circom_template;
-use errors::CompilerError;
+use circom::{gen_circom_product, gen_circom_shared, gen_circom_template};
+pub use errors::CompilerError;
 use halo2::gen_halo2_tables;
 use itertools::Itertools;
-use regex::{create_regex_and_dfa_from_str_and_defs, get_regex_and_dfa};
-use std::{fs::File, path::PathBuf};
+use regex::{constrained_substr_idx, create_regex_and_dfa_from_str_and_defs, get_regex_and_dfa};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
 use structs::{RegexAndDFA, SubstringDefinitionsJson};
+use trace::trace_circuit;
+use zk_regex_apis::{
+    extract_substrs::{PartConstraint, PartRef},
+    registry::{CircuitSize, PatternRegistry},
+};
 
-pub use structs::{DecomposedRegexConfig, RegexPartConfig};
+pub use circom::{
+    CircomBackend, CircomOptions, CircomStats, Occurrence, SearchMode, StateEncoding,
+};
+pub use halo2::{
+    write_halo2_binary, write_halo2_multi_binary, Halo2Backend, Halo2MultiRegex, Halo2MultiTable,
+    Halo2SubstrTable, Halo2Table, Halo2Transition, HALO2_TABLE_VERSION,
+};
+pub use ir::{Backend, ByteClasses, BytePredicate, CircuitTransition, RegexCircuit, RevealMask};
+pub use negate::{NegatedDfa, NegatedTransition, SearchDfa, MAX_SEARCH_STATES};
+pub use product::{CombineMode, MAX_PRODUCT_STATES};
+use product::{CrossEquality, ProductAutomaton};
+pub use regex::MAX_PART_STATES;
+pub use structs::{DecomposedRegexConfig, PartOutput, RegexPartConfig};
+pub use trace::{ByteTrace, CircuitTrace, PackedRevealTrace};
+pub use zk_regex_apis::extract_substrs::MatchPolicy;
 
 /// Loads substring definitions from a JSON file or creates a default one.
 ///
@@ -38,6 +64,13 @@ fn load_substring_definitions_json(
     }
 }
 
+/// Returns the default names of the public parts, i.e., `substr_{idx}`.
+fn default_public_part_names(num_public_parts: usize) -> Vec<String> {
+    (0..num_public_parts)
+        .map(|idx| format!("substr_{}", idx))
+        .collect()
+}
+
 /// Generates output files for Halo2 and Circom based on the provided regex and DFA.
 ///
 /// # Arguments
@@ -46,8 +79,9 @@ fn load_substring_definitions_json(
 /// * `halo2_dir_path` - An optional path to the directory for Halo2 output files.
 /// * `circom_file_path` - An optional path to the Circom output file.
 /// * `circom_template_name` - An optional name for the Circom template.
-/// * `num_public_parts` - The number of public parts in the regex.
+/// * `public_part_names` - The names of the public parts in the regex.
 /// * `gen_substrs` - A boolean indicating whether to generate substrings.
+/// * `circom_options` - The options of the Circom code generation.
 ///
 /// # Returns
 ///
@@ -57,20 +91,24 @@ fn generate_outputs(
     halo2_dir_path: Option<&str>,
     circom_file_path: Option<&str>,
     circom_template_name: Option<&str>,
-    num_public_parts: usize,
+    public_part_names: &[String],
     gen_substrs: bool,
+    circom_options: &CircomOptions,
 ) -> Result<(), CompilerError> {
     if let Some(halo2_dir_path) = halo2_dir_path {
         let halo2_dir_path = PathBuf::from(halo2_dir_path);
         let allstr_file_path = halo2_dir_path.join("allstr.txt");
-        let substr_file_paths = (0..num_public_parts)
+        let substr_file_paths = (0..public_part_names.len())
             .map(|idx| halo2_dir_path.join(format!("substr_{}.txt", idx)))
             .collect_vec();
+        let table_file_path = halo2_dir_path.join("table.json");
 
         gen_halo2_tables(
             regex_and_dfa,
             &allstr_file_path,
             &substr_file_paths,
+            &table_file_path,
+            public_part_names,
             gen_substrs,
         )?;
     }
@@ -85,6 +123,7 @@ fn generate_outputs(
             &circom_file_path,
             &circom_template_name,
             gen_substrs,
+            circom_options,
         )?;
     }
 
@@ -110,6 +149,39 @@ pub fn gen_from_decomposed(
     circom_file_path: Option<&str>,
     circom_template_name: Option<&str>,
     gen_substrs: Option<bool>,
+) -> Result<(), CompilerError> {
+    gen_from_decomposed_with_options(
+        decomposed_regex_path,
+        halo2_dir_path,
+        circom_file_path,
+        circom_template_name,
+        gen_substrs,
+        &CircomOptions::default(),
+    )
+}
+
+/// Generates outputs from a decomposed regex configuration file with the given options of the Circom code
+/// generation.
+///
+/// # Arguments
+///
+/// * `decomposed_regex_path` - The path to the decomposed regex configuration file.
+/// * `halo2_dir_path` - An optional path to the directory for Halo2 output files.
+/// * `circom_file_path` - An optional path to the Circom output file.
+/// * `circom_template_name` - An optional name for the Circom template.
+/// * `gen_substrs` - An optional boolean indicating whether to generate substrings.
+/// * `circom_options` - A reference to the options of the Circom code generation.
+///
+/// # Returns
+///
+/// A `Result` indicating success or a `CompilerError`.
+pub fn gen_from_decomposed_with_options(
+    decomposed_regex_path: &str,
+    halo2_dir_path: Option<&str>,
+    circom_file_path: Option<&str>,
+    circom_template_name: Option<&str>,
+    gen_substrs: Option<bool>,
+    circom_options: &CircomOptions,
 ) -> Result<(), CompilerError> {
     let mut decomposed_regex_config: DecomposedRegexConfig =
         serde_json::from_reader(File::open(decomposed_regex_path)?)?;
@@ -117,19 +189,22 @@ pub fn gen_from_decomposed(
 
     let regex_and_dfa = get_regex_and_dfa(&mut decomposed_regex_config)?;
 
-    let num_public_parts = decomposed_regex_config
-        .parts
-        .iter()
-        .filter(|part| part.is_public)
-        .count();
+    let public_part_names = default_public_part_names(
+        decomposed_regex_config
+            .parts
+            .iter()
+            .filter(|part| part.is_public)
+            .count(),
+    );
 
     generate_outputs(
         &regex_and_dfa,
         halo2_dir_path,
         circom_file_path,
         circom_template_name,
-        num_public_parts,
+        &public_part_names,
         gen_substrs,
+        circom_options,
     )?;
 
     Ok(())
@@ -156,9 +231,45 @@ pub fn gen_from_raw(
     circom_file_path: Option<&str>,
     template_name: Option<&str>,
     gen_substrs: Option<bool>,
+) -> Result<(), CompilerError> {
+    gen_from_raw_with_options(
+        raw_regex,
+        substrs_json_path,
+        halo2_dir_path,
+        circom_file_path,
+        template_name,
+        gen_substrs,
+        &CircomOptions::default(),
+    )
+}
+
+/// Generates outputs from a raw regex string and optional substring definitions with the given options of the
+/// Circom code generation.
+///
+/// # Arguments
+///
+/// * `raw_regex` - The raw regex string.
+/// * `substrs_json_path` - An optional path to the JSON file containing substring definitions.
+/// * `halo2_dir_path` - An optional path to the directory for Halo2 output files.
+/// * `circom_file_path` - An optional path to the Circom output file.
+/// * `template_name` - An optional name for the Circom template.
+/// * `gen_substrs` - An optional boolean indicating whether to generate substrings.
+/// * `circom_options` - A reference to the options of the Circom code generation.
+///
+/// # Returns
+///
+/// A `Result` indicating success or a `CompilerError`.
+pub fn gen_from_raw_with_options(
+    raw_regex: &str,
+    substrs_json_path: Option<&str>,
+    halo2_dir_path: Option<&str>,
+    circom_file_path: Option<&str>,
+    template_name: Option<&str>,
+    gen_substrs: Option<bool>,
+    circom_options: &CircomOptions,
 ) -> Result<(), CompilerError> {
     let substrs_defs_json = load_substring_definitions_json(substrs_json_path)?;
-    let num_public_parts = substrs_defs_json.transitions.len();
+    let public_part_names = default_public_part_names(substrs_defs_json.transitions.len());
 
     let regex_and_dfa = create_regex_and_dfa_from_str_and_defs(raw_regex, substrs_defs_json)?;
 
@@ -169,8 +280,9 @@ pub fn gen_from_raw(
         halo2_dir_path,
         circom_file_path,
         template_name,
-        num_public_parts,
+        &public_part_names,
         gen_substrs,
+        circom_options,
     )?;
 
     Ok(())
@@ -198,23 +310,788 @@ pub fn gen_circom_from_decomposed_regex(
 
     let regex_and_dfa = get_regex_and_dfa(decomposed_regex)?;
 
-    let num_public_parts = decomposed_regex
-        .parts
-        .iter()
-        .filter(|part| part.is_public)
-        .count();
+    let public_part_names = default_public_part_names(
+        decomposed_regex
+            .parts
+            .iter()
+            .filter(|part| part.is_public)
+            .count(),
+    );
 
     generate_outputs(
         &regex_and_dfa,
         None,
         circom_file_path,
         circom_template_name,
-        num_public_parts,
+        &public_part_names,
         gen_substrs,
+        &CircomOptions::default(),
     )?;
 
     Ok(())
 }
 
+/// Loads the pattern registry, i.e., the built-in patterns and, if specified, the patterns in a directory.
+///
+/// # Arguments
+///
+/// * `pattern_dir` - An optional path to a directory of pattern json files.
+///
+/// # Returns
+///
+/// A `Result` containing either the `PatternRegistry` or a `CompilerError`.
+pub fn load_pattern_registry(pattern_dir: Option<&str>) -> Result<PatternRegistry, CompilerError> {
+    let mut registry = PatternRegistry::builtin();
+    if let Some(pattern_dir) = pattern_dir {
+        registry.load_dir(pattern_dir)?;
+    }
+    Ok(registry)
+}
+
+/// Computes the size of the circuit generated from a decomposed regex.
+///
+/// # Arguments
+///
+/// * `decomposed_regex` - A mutable reference to the `DecomposedRegexConfig` containing the regex parts.
+///
+/// # Returns
+///
+/// A `Result` containing either the `CircuitSize` or a `CompilerError`.
+pub fn circuit_size(
+    decomposed_regex: &mut DecomposedRegexConfig,
+) -> Result<CircuitSize, CompilerError> {
+    let regex_and_dfa = get_regex_and_dfa(decomposed_regex)?;
+    Ok(CircuitSize {
+        dfa_states: regex_and_dfa.dfa.states.len(),
+        dfa_transitions: regex_and_dfa
+            .dfa
+            .states
+            .iter()
+            .map(|state| state.transitions.len())
+            .sum(),
+    })
+}
+
+/// Converts the config of a registry pattern into the compiler's `DecomposedRegexConfig`.
+fn registry_pattern_config(
+    registry: &PatternRegistry,
+    name: &str,
+) -> Result<DecomposedRegexConfig, CompilerError> {
+    let pattern = registry
+        .get(name)
+        .ok_or_else(|| CompilerError::PatternNotFound(name.to_string()))?;
+    Ok(DecomposedRegexConfig {
+        parts: pattern
+            .def
+            .config
+            .parts
+            .iter()
+            .enumerate()
+            .map(|(part_idx, part)| RegexPartConfig {
+                is_public: part.is_public,
+                regex_def: part.regex_def.clone(),
+                max_len: pattern.def.options.max_len(part_idx),
+                ..Default::default()
+            })
+            .collect(),
+        match_policy: pattern.def.options.match_policy,
+        constraints: pattern.def.options.constraints.clone(),
+    })
+}
+
+/// Converts a snake_case pattern name into the default Circom template name, e.g., `from_addr` into `FromAddrRegex`.
+fn default_template_name(name: &str) -> String {
+    let mut template_name = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().expect("words are not empty");
+            first.to_ascii_uppercase().to_string() + chars.as_str()
+        })
+        .collect::<String>();
+    template_name.push_str("Regex");
+    template_name
+}
+
+/// Generates outputs from a pattern of the pattern registry, so that the circuit and the extractor of the
+/// `zk-regex-apis` crate share the same definition.
+///
+/// # Arguments
+///
+/// * `name` - The name of the pattern.
+/// * `pattern_dir` - An optional path to a directory of additional pattern json files.
+/// * `halo2_dir_path` - An optional path to the directory for Halo2 output files.
+/// * `circom_file_path` - An optional path to the Circom output file.
+/// * `circom_template_name` - An optional name for the Circom template. Defaults to the CamelCase pattern name followed by `Regex`.
+/// * `gen_substrs` - An optional boolean indicating whether to generate substrings.
+///
+/// # Returns
+///
+/// A `Result` indicating success or a `CompilerError`.
+pub fn gen_from_registry(
+    name: &str,
+    pattern_dir: Option<&str>,
+    halo2_dir_path: Option<&str>,
+    circom_file_path: Option<&str>,
+    circom_template_name: Option<&str>,
+    gen_substrs: Option<bool>,
+) -> Result<(), CompilerError> {
+    gen_from_registry_with_options(
+        name,
+        pattern_dir,
+        halo2_dir_path,
+        circom_file_path,
+        circom_template_name,
+        gen_substrs,
+        &CircomOptions::default(),
+    )
+}
+
+/// Generates outputs from a pattern of the pattern registry with the given options of the Circom code generation.
+///
+/// # Arguments
+///
+/// * `name` - The name of the pattern.
+/// * `pattern_dir` - An optional path to a directory of additional pattern json files.
+/// * `halo2_dir_path` - An optional path to the directory for Halo2 output files.
+/// * `circom_file_path` - An optional path to the Circom output file.
+/// * `circom_template_name` - An optional name for the Circom template. Defaults to the CamelCase pattern name followed by `Regex`.
+/// * `gen_substrs` - An optional boolean indicating whether to generate substrings.
+/// * `circom_options` - A reference to the options of the Circom code generation.
+///
+/// # Returns
+///
+/// A `Result` indicating success or a `CompilerError`.
+pub fn gen_from_registry_with_options(
+    name: &str,
+    pattern_dir: Option<&str>,
+    halo2_dir_path: Option<&str>,
+    circom_file_path: Option<&str>,
+    circom_template_name: Option<&str>,
+    gen_substrs: Option<bool>,
+    circom_options: &CircomOptions,
+) -> Result<(), CompilerError> {
+    let registry = load_pattern_registry(pattern_dir)?;
+    let mut decomposed_regex_config = registry_pattern_config(&registry, name)?;
+    let gen_substrs = gen_substrs.unwrap_or(true);
+    let circom_template_name = circom_template_name
+        .map(str::to_string)
+        .unwrap_or_else(|| default_template_name(name));
+
+    let regex_and_dfa = get_regex_and_dfa(&mut decomposed_regex_config)?;
+
+    let public_part_names = registry
+        .get(name)
+        .map(|pattern| pattern.def.public_parts.clone())
+        .unwrap_or_default();
+
+    generate_outputs(
+        &regex_and_dfa,
+        halo2_dir_path,
+        circom_file_path,
+        Some(&circom_template_name),
+        &public_part_names,
+        gen_substrs,
+        circom_options,
+    )?;
+
+    Ok(())
+}
+
+/// Runs an input through the circuit generated from a decomposed regex, computing its signals without a witness generator.
+///
+/// # Arguments
+///
+/// * `decomposed_regex` - A mutable reference to the `DecomposedRegexConfig` containing the regex parts.
+/// * `input` - The input bytes.
+/// * `max_bytes` - An optional `msg_bytes` of the circuit. The input is padded with zeros up to it. Defaults to the input length.
+///
+/// # Returns
+///
+/// A `Result` containing either the `CircuitTrace` or a `CompilerError`.
+pub fn trace_from_decomposed(
+    decomposed_regex: &mut DecomposedRegexConfig,
+    input: &[u8],
+    max_bytes: Option<usize>,
+) -> Result<CircuitTrace, CompilerError> {
+    let max_bytes = max_bytes.unwrap_or(input.len());
+    if input.len() > max_bytes {
+        return Err(CompilerError::GenericError(format!(
+            "The input length {} exceeds the max bytes {}",
+            input.len(),
+            max_bytes
+        )));
+    }
+
+    let regex_and_dfa = get_regex_and_dfa(decomposed_regex)?;
+
+    let mut msg = input.to_vec();
+    msg.resize(max_bytes, 0);
+    trace_circuit(&regex_and_dfa, &msg)
+}
+
+/// Resolves a regex given either as a path to a decomposed regex json file or as the name of a registry pattern.
+///
+/// # Returns
+///
+/// A `Result` containing the name, the `DecomposedRegexConfig` and the public part names of the regex, or a
+/// `CompilerError`. The name of a file is its stem.
+fn resolve_pattern(
+    registry: &PatternRegistry,
+    pattern: &str,
+) -> Result<(String, DecomposedRegexConfig, Vec<String>), CompilerError> {
+    let path = Path::new(pattern);
+    if path.is_file() {
+        let decomposed_regex_config: DecomposedRegexConfig =
+            serde_json::from_reader(File::open(path)?)?;
+        let public_part_names = default_public_part_names(
+            decomposed_regex_config
+                .parts
+                .iter()
+                .filter(|part| part.is_public)
+                .count(),
+        );
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| pattern.to_string());
+        Ok((name, decomposed_regex_config, public_part_names))
+    } else {
+        let decomposed_regex_config = registry_pattern_config(registry, pattern)?;
+        let public_part_names = registry
+            .get(pattern)
+            .map(|pattern| pattern.def.public_parts.clone())
+            .unwrap_or_default();
+        Ok((
+            pattern.to_string(),
+            decomposed_regex_config,
+            public_part_names,
+        ))
+    }
+}
+
+/// Computes the number of components per input byte of the Circom circuit of a regex.
+///
+/// # Arguments
+///
+/// * `pattern` - The regex, either a path to a decomposed regex json file or the name of a registry pattern.
+/// * `pattern_dir` - An optional path to a directory of additional pattern json files.
+/// * `circom_options` - Optional options of the Circom code generation. Defaults to the original circuit.
+///
+/// # Returns
+///
+/// A `Result` containing either the `CircomStats` or a `CompilerError`.
+pub fn circom_stats(
+    pattern: &str,
+    pattern_dir: Option<&str>,
+    circom_options: Option<CircomOptions>,
+) -> Result<CircomStats, CompilerError> {
+    let registry = load_pattern_registry(pattern_dir)?;
+    let (_, mut decomposed_regex_config, _) = resolve_pattern(&registry, pattern)?;
+    let regex_and_dfa = get_regex_and_dfa(&mut decomposed_regex_config)?;
+    Ok(CircomBackend {
+        template_name: "",
+        gen_substrs: false,
+        options: circom_options.unwrap_or_default(),
+        shared_predicates: None,
+    }
+    .stats(&RegexCircuit::new(&regex_and_dfa)))
+}
+
+/// Builds the combined Halo2 lookup table of several regexes, so that one lookup argument serves all of them.
+///
+/// # Arguments
+///
+/// * `patterns` - The regexes, each either a path to a decomposed regex json file or the name of a registry pattern.
+///   The regex id of each regex is its index.
+/// * `pattern_dir` - An optional path to a directory of additional pattern json files.
+///
+/// # Returns
+///
+/// A `Result` containing either the `Halo2MultiTable` or a `CompilerError`.
+pub fn halo2_multi_table(
+    patterns: &[String],
+    pattern_dir: Option<&str>,
+) -> Result<Halo2MultiTable, CompilerError> {
+    let registry = load_pattern_registry(pattern_dir)?;
+    let tables = patterns
+        .iter()
+        .map(|pattern| {
+            let (name, mut decomposed_regex_config, public_part_names) =
+                resolve_pattern(&registry, pattern)?;
+            let regex_and_dfa = get_regex_and_dfa(&mut decomposed_regex_config)?;
+            Ok((name, Halo2Table::new(&regex_and_dfa, &public_part_names)?))
+        })
+        .collect::<Result<Vec<_>, CompilerError>>()?;
+    Ok(Halo2MultiTable::new(tables))
+}
+
+/// Writes the combined Halo2 lookup table of several regexes to `multi_table.json` in `halo2_dir_path`.
+///
+/// # Arguments
+///
+/// * `patterns` - The regexes, each either a path to a decomposed regex json file or the name of a registry pattern.
+/// * `pattern_dir` - An optional path to a directory of additional pattern json files.
+/// * `halo2_dir_path` - The path to the directory for Halo2 output files.
+///
+/// # Returns
+///
+/// A `Result` indicating success or a `CompilerError`.
+pub fn gen_halo2_multi_table(
+    patterns: &[String],
+    pattern_dir: Option<&str>,
+    halo2_dir_path: &str,
+) -> Result<(), CompilerError> {
+    let table = halo2_multi_table(patterns, pattern_dir)?;
+    std::fs::write(
+        Path::new(halo2_dir_path).join("multi_table.json"),
+        table.to_json()?,
+    )?;
+    Ok(())
+}
+
 #[cfg(target_arch = "wasm32")]
 pub use crate::wasm::*;
+
+/// Generates the Circom templates of several regexes over the same message, whose byte predicates are computed
+/// once per position by a shared template.
+///
+/// The shared template takes `msg` and outputs the range-checked `in` and the `pred` signals, which each regex
+/// template, named after its pattern as in `gen_from_registry`, takes as inputs instead of `msg`.
+///
+/// # Arguments
+///
+/// * `patterns` - The regexes, each either a path to a decomposed regex json file or the name of a registry pattern.
+/// * `pattern_dir` - An optional path to a directory of additional pattern json files.
+/// * `predicates_template_name` - The name of the shared predicate template.
+/// * `gen_substrs` - An optional boolean indicating whether to generate substrings.
+/// * `circom_options` - Optional options of the Circom code generation. Defaults to the original circuit.
+///
+/// # Returns
+///
+/// A `Result` containing either the Circom code or a `CompilerError`.
+pub fn gen_shared_circom_string(
+    patterns: &[String],
+    pattern_dir: Option<&str>,
+    predicates_template_name: &str,
+    gen_substrs: Option<bool>,
+    circom_options: Option<CircomOptions>,
+) -> Result<String, CompilerError> {
+    let registry = load_pattern_registry(pattern_dir)?;
+    let circuits = patterns
+        .iter()
+        .map(|pattern| {
+            let (name, mut decomposed_regex_config, _) = resolve_pattern(&registry, pattern)?;
+            let regex_and_dfa = get_regex_and_dfa(&mut decomposed_regex_config)?;
+            Ok((
+                default_template_name(&name),
+                RegexCircuit::new(&regex_and_dfa),
+            ))
+        })
+        .collect::<Result<Vec<_>, CompilerError>>()?;
+    gen_circom_shared(
+        &circuits,
+        predicates_template_name,
+        gen_substrs.unwrap_or(true),
+        &circom_options.unwrap_or_default(),
+    )
+}
+
+/// Writes the Circom templates generated by `gen_shared_circom_string` to a file.
+///
+/// # Arguments
+///
+/// * `patterns` - The regexes, each either a path to a decomposed regex json file or the name of a registry pattern.
+/// * `pattern_dir` - An optional path to a directory of additional pattern json files.
+/// * `circom_file_path` - The path of the Circom file to write.
+/// * `predicates_template_name` - The name of the shared predicate template.
+/// * `gen_substrs` - An optional boolean indicating whether to generate substrings.
+/// * `circom_options` - Optional options of the Circom code generation. Defaults to the original circuit.
+///
+/// # Returns
+///
+/// A `Result` indicating success or a `CompilerError`.
+pub fn gen_shared_circom(
+    patterns: &[String],
+    pattern_dir: Option<&str>,
+    circom_file_path: &str,
+    predicates_template_name: &str,
+    gen_substrs: Option<bool>,
+    circom_options: Option<CircomOptions>,
+) -> Result<(), CompilerError> {
+    let circom = gen_shared_circom_string(
+        patterns,
+        pattern_dir,
+        predicates_template_name,
+        gen_substrs,
+        circom_options,
+    )?;
+    std::fs::write(circom_file_path, circom)?;
+    Ok(())
+}
+
+/// Generates a single Circom template that matches several regexes over the same message with the circuit of their
+/// product automaton.
+///
+/// The template outputs `{name}_out` and the `{name}_reveal{idx}` signals of each regex, and `out`, which is `1`
+/// if all or any of the regexes match depending on `combine`.
+///
+/// # Arguments
+///
+/// * `regexes` - The names and the `DecomposedRegexConfig`s of the regexes. The names prefix their signals. The
+///   constraints referring to the parts of other regexes by their names are moved out of the configs into the
+///   constraints of the combined template.
+/// * `template_name` - The name of the Circom template.
+/// * `combine` - How `out` is computed from the outputs of the regexes.
+/// * `gen_substrs` - An optional boolean indicating whether to generate substrings.
+/// * `circom_options` - Optional options of the Circom code generation, which must use the vector state encoding.
+///
+/// # Returns
+///
+/// A `Result` containing either the Circom code or a `CompilerError`.
+pub fn gen_combined_circom_string(
+    regexes: &mut [(String, DecomposedRegexConfig)],
+    template_name: &str,
+    combine: CombineMode,
+    gen_substrs: Option<bool>,
+    circom_options: Option<CircomOptions>,
+) -> Result<String, CompilerError> {
+    let equalities = take_cross_regex_equalities(regexes)?;
+    let regex_and_dfas = regexes
+        .iter_mut()
+        .map(|(name, decomposed_regex_config)| {
+            Ok((name.clone(), get_regex_and_dfa(decomposed_regex_config)?))
+        })
+        .collect::<Result<Vec<_>, CompilerError>>()?;
+    gen_circom_product(
+        &ProductAutomaton::new(&regex_and_dfas)?,
+        template_name,
+        gen_substrs.unwrap_or(true),
+        combine,
+        &equalities,
+        &circom_options.unwrap_or_default(),
+    )
+}
+
+/// Removes the equality constraints referring to a part of a regex by its name from the configs of the regexes,
+/// and returns the pairs of substrings they constrain, each given by the index of its regex and its index in the
+/// regex.
+fn take_cross_regex_equalities(
+    regexes: &mut [(String, DecomposedRegexConfig)],
+) -> Result<Vec<CrossEquality>, CompilerError> {
+    let resolve = |regex_idx: usize, part: &PartRef| -> Result<(usize, usize), CompilerError> {
+        let (regex_idx, part_idx) = match part {
+            PartRef::Index(part_idx) => (regex_idx, *part_idx),
+            PartRef::Named {
+                regex,
+                part: part_idx,
+            } => (
+                regexes
+                    .iter()
+                    .position(|(name, _)| name == regex)
+                    .ok_or_else(|| {
+                        CompilerError::GenericError(format!(
+                            "The constrained part {} refers to an unknown regex",
+                            part
+                        ))
+                    })?,
+                *part_idx,
+            ),
+        };
+        Ok((
+            regex_idx,
+            constrained_substr_idx(&regexes[regex_idx].1, part_idx, part)?,
+        ))
+    };
+    let is_named = |part: &PartRef| matches!(part, PartRef::Named { .. });
+
+    let mut equalities = vec![];
+    for (regex_idx, (_, config)) in regexes.iter().enumerate() {
+        for PartConstraint::Equal([a, b]) in &config.constraints {
+            if is_named(a) || is_named(b) {
+                equalities.push((resolve(regex_idx, a)?, resolve(regex_idx, b)?));
+            }
+        }
+    }
+    for (_, config) in regexes.iter_mut() {
+        config
+            .constraints
+            .retain(|PartConstraint::Equal([a, b])| !is_named(a) && !is_named(b));
+    }
+    Ok(equalities)
+}
+
+/// Writes the combined Circom template of several regexes generated by `gen_combined_circom_string` to a file.
+///
+/// # Arguments
+///
+/// * `patterns` - The regexes, each either a path to a decomposed regex json file or the name of a registry pattern.
+/// * `pattern_dir` - An optional path to a directory of additional pattern json files.
+/// * `circom_file_path` - The path of the Circom file to write.
+/// * `template_name` - The name of the Circom template.
+/// * `combine` - How `out` is computed from the outputs of the regexes.
+/// * `gen_substrs` - An optional boolean indicating whether to generate substrings.
+/// * `circom_options` - Optional options of the Circom code generation, which must use the vector state encoding.
+///
+/// # Returns
+///
+/// A `Result` indicating success or a `CompilerError`.
+pub fn gen_combined_circom(
+    patterns: &[String],
+    pattern_dir: Option<&str>,
+    circom_file_path: &str,
+    template_name: &str,
+    combine: CombineMode,
+    gen_substrs: Option<bool>,
+    circom_options: Option<CircomOptions>,
+) -> Result<(), CompilerError> {
+    let registry = load_pattern_registry(pattern_dir)?;
+    let mut regexes = patterns
+        .iter()
+        .map(|pattern| {
+            let (name, decomposed_regex_config, _) = resolve_pattern(&registry, pattern)?;
+            Ok((name, decomposed_regex_config))
+        })
+        .collect::<Result<Vec<_>, CompilerError>>()?;
+    let circom = gen_combined_circom_string(
+        &mut regexes,
+        template_name,
+        combine,
+        gen_substrs,
+        circom_options,
+    )?;
+    std::fs::write(circom_file_path, circom)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builtin_patterns_circuit_size() {
+        let registry = PatternRegistry::builtin();
+        for pattern in registry.patterns() {
+            let mut config = registry_pattern_config(&registry, &pattern.name).unwrap();
+            assert_eq!(
+                Some(circuit_size(&mut config).unwrap()),
+                pattern.def.expected_circuit_size,
+                "{}",
+                pattern.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_gen_halo2_tables_from_raw() {
+        let dir = std::env::temp_dir().join(format!("zk-regex-raw-halo2-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let substrs_json_path = dir.join("substrs.json");
+        std::fs::write(
+            &substrs_json_path,
+            r#"{"transitions": [[[2, 3]], [[6, 7], [7, 7]], [[8, 9]]]}"#,
+        )
+        .unwrap();
+
+        gen_from_raw(
+            "1=(a|b) (2=(b|c)+ )+d",
+            substrs_json_path.to_str(),
+            dir.to_str(),
+            None,
+            None,
+            Some(true),
+        )
+        .unwrap();
+        let substrs = (0..3)
+            .map(|idx| std::fs::read_to_string(dir.join(format!("substr_{}.txt", idx))).unwrap())
+            .collect_vec();
+        let table = Halo2Table::load(dir.join("table.json")).unwrap();
+        write_halo2_binary(&dir).unwrap();
+        let binary_table = Halo2Table::load(dir.join("table.bin")).unwrap();
+
+        std::fs::write(&substrs_json_path, r#"{"transitions": [[[2, 3], [3, 5]]]}"#).unwrap();
+        let err = gen_from_raw(
+            "1=(a|b) (2=(b|c)+ )+d",
+            substrs_json_path.to_str(),
+            dir.to_str(),
+            None,
+            None,
+            Some(true),
+        )
+        .unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(substrs[0], "2\n3\n2 3\n");
+        assert_eq!(substrs[1], "6\n7\n6 7\n7 7\n");
+        assert_eq!(substrs[2], "8\n9\n8 9\n");
+        assert_eq!(table.substrs[1].name, "substr_1");
+        assert_eq!(table.substrs[1].transitions, vec![(6, 7), (7, 7)]);
+        assert_eq!(binary_table, table);
+        assert_eq!(
+            err.to_string(),
+            "Transition (3, 5) of substring 0 does not exist in the DFA"
+        );
+    }
+
+    #[test]
+    fn test_default_template_name() {
+        assert_eq!(default_template_name("from_addr"), "FromAddrRegex");
+        assert_eq!(default_template_name("message_id"), "MessageIdRegex");
+    }
+
+    #[test]
+    fn test_circom_stats_alphabet_classes() {
+        let alphabet_classes = Some(CircomOptions {
+            alphabet_classes: true,
+            ..Default::default()
+        });
+        let registry = PatternRegistry::builtin();
+        for name in registry.names() {
+            let before = circom_stats(name, None, None).unwrap();
+            let after = circom_stats(name, None, alphabet_classes).unwrap();
+            assert_eq!(before.byte_classes, 0);
+            assert_eq!(after.eq + after.lt, 0);
+            assert_eq!(after.and, before.and - before.lt / 2);
+            assert!(
+                after.estimated_constraints_per_byte() < before.estimated_constraints_per_byte(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_circom_stats_one_hot() {
+        let one_hot = Some(CircomOptions {
+            one_hot: true,
+            ..Default::default()
+        });
+        let registry = PatternRegistry::builtin();
+        for name in registry.names() {
+            let before = circom_stats(name, None, None).unwrap();
+            let after = circom_stats(name, None, one_hot).unwrap();
+            assert_eq!(
+                (after.eq, after.lt, after.and),
+                (before.eq, before.lt, before.and)
+            );
+            assert_eq!(after.one_hot_checks, 1);
+            // Shared MultiOR gates become one sum per use.
+            assert!(
+                after.multi_or + after.inline_multi_or < before.multi_or + before.inline_multi_or
+                    && after.multi_or + after.inline_multi_or + after.sums
+                        >= before.multi_or + before.inline_multi_or,
+                "{}",
+                name
+            );
+            assert!(
+                after.estimated_constraints_per_byte() < before.estimated_constraints_per_byte(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_circom_stats_index_encoding() {
+        let index = Some(CircomOptions {
+            state_encoding: StateEncoding::Index,
+            ..Default::default()
+        });
+        let registry = PatternRegistry::builtin();
+        for name in registry.names() {
+            let before = circom_stats(name, None, None).unwrap();
+            let after = circom_stats(name, None, index).unwrap();
+            assert_eq!(after.eq + after.lt + after.and + after.multi_or, 0);
+            assert_eq!(after.class_products, after.byte_classes);
+            assert!(after.state_signals < before.state_signals, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_gen_shared_circom() {
+        let patterns = ["from_addr", "to_addr", "subject_all"].map(str::to_string);
+        let circom =
+            gen_shared_circom_string(&patterns, None, "HeaderPredicates", None, None).unwrap();
+
+        assert_eq!(circom.matches("template ").count(), 4);
+        assert_eq!(circom.matches("pragma circom").count(), 1);
+        assert_eq!(circom.matches("in_range_checks[i] ===").count(), 1);
+        assert!(circom.contains("template FromAddrRegex(msg_bytes) {"));
+        // The regex templates read the shared predicates instead of checking the bytes themselves.
+        assert!(!circom.contains("IsEqual();"));
+        assert!(!circom.contains("LessEqThan(8);"));
+        assert!(!circom.contains("signal input msg[msg_bytes];\n\tsignal output out;"));
+        let num_predicates = circom.matches("\t\tpred[").count();
+        let unique_predicates = circom
+            .lines()
+            .filter(|line| line.starts_with("\t\tpred["))
+            .map(|line| line.split(" <== ").nth(1).unwrap())
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(unique_predicates.len(), num_predicates);
+        assert_eq!(circom.matches('{').count(), circom.matches('}').count());
+
+        assert!(gen_shared_circom_string(
+            &patterns,
+            None,
+            "HeaderPredicates",
+            None,
+            Some(CircomOptions {
+                alphabet_classes: true,
+                ..Default::default()
+            })
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_gen_combined_circom() {
+        let registry = PatternRegistry::builtin();
+        let mut regexes = ["from_addr", "subject_all"]
+            .map(|name| {
+                (
+                    name.to_string(),
+                    registry_pattern_config(&registry, name).unwrap(),
+                )
+            })
+            .to_vec();
+        let circom =
+            gen_combined_circom_string(&mut regexes, "HeaderRegex", CombineMode::Any, None, None)
+                .unwrap();
+
+        assert_eq!(circom.matches("template ").count(), 1);
+        assert!(circom.contains("\tsignal output from_addr_out;"));
+        assert!(circom.contains("\tsignal output from_addr_reveal0[msg_bytes];"));
+        assert!(circom.contains("\tsignal output subject_all_reveal0[msg_bytes];"));
+        assert!(circom.contains("\tout <== MultiOR(2)([from_addr_out, subject_all_out]);"));
+        assert_eq!(circom.matches('{').count(), circom.matches('}').count());
+    }
+
+    #[test]
+    fn test_registry_pattern_max_len() {
+        let mut registry = PatternRegistry::builtin();
+        registry
+            .register(
+                "invoice_id",
+                serde_json::from_str(
+                    r#"{
+                        "parts": [
+                            {"is_public": false, "regex_def": "Invoice #"},
+                            {"is_public": true, "regex_def": "[0-9]+", "max_len": 8}
+                        ]
+                    }"#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let config = registry_pattern_config(&registry, "invoice_id").unwrap();
+        assert_eq!(
+            config
+                .parts
+                .iter()
+                .map(|part| part.max_len)
+                .collect::<Vec<_>>(),
+            vec![None, Some(8)]
+        );
+    }
+}
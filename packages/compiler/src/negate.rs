@@ -0,0 +1,353 @@
+use crate::{
+    errors::CompilerError,
+    ir::{bytes_to_ranges, RegexCircuit},
+    regex::minimize_graph,
+    structs::DFAGraph,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The maximum number of states of the search automaton of a [`NegatedDfa`].
+pub const MAX_SEARCH_STATES: usize = 4096;
+
+/// A `(from, to, ranges)` transition of a [`NegatedDfa`], where `ranges` are the inclusive byte ranges.
+pub type NegatedTransition = (usize, usize, Vec<(u8, u8)>);
+
+/// The complete DFA of the inputs `in` (i.e., `255` followed by `msg`) that contain no substring matched by a regex.
+///
+/// As in the matching circuit, the transitions of the regex on the padding byte `0` are ignored, so a substring
+/// containing `0` never matches. The DFA is built by the subset construction of the search automaton, whose states
+/// are the states of the regex reachable by some suffix of the input, and is minimized. Every live state accepts,
+/// and every missing transition leads to the explicit dead state, which is entered once a substring matches and is
+/// never left.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NegatedDfa {
+    /// The number of states, including the dead state.
+    pub num_states: usize,
+    /// The transitions between live states.
+    pub transitions: Vec<NegatedTransition>,
+}
+
+/// The transitions on every byte and the accept flags of the search automaton of a regex, before minimization.
+type SearchAutomaton = (Vec<Vec<usize>>, Vec<bool>);
+
+/// Builds the search automaton of the circuit of a regex by the subset construction, where a state is the set of
+/// the states of the regex reachable by some suffix of the input.
+///
+/// # Arguments
+///
+/// * `circuit` - A reference to the RegexCircuit of the regex.
+/// * `absorb_matches` - Whether the states where a substring has matched are merged into one absorbing state,
+///   which is the only non-accepting state, as in a [`NegatedDfa`]. Otherwise, the states containing an accept state
+///   of the regex are the accept states, as in a [`SearchDfa`].
+///
+/// # Returns
+///
+/// A `Result` containing the transitions and the accept flags, or a `CompilerError` if the regex has an end anchor,
+/// matches the empty string, or its search automaton has more than [`MAX_SEARCH_STATES`] states.
+fn build_search_automaton(
+    circuit: &RegexCircuit,
+    absorb_matches: bool,
+) -> Result<SearchAutomaton, CompilerError> {
+    if circuit.has_end_anchor {
+        return Err(CompilerError::GenericError(
+            "The search automaton does not support the end anchor $".to_string(),
+        ));
+    }
+    let mut delta = vec![[None; 256]; circuit.num_states];
+    for transition in &circuit.transitions {
+        for &(min, max) in &transition.ranges {
+            for byte in min.max(1)..=max {
+                delta[transition.from][byte as usize] = Some(transition.to);
+            }
+        }
+    }
+
+    // With `absorb_matches`, `None` is the matched state.
+    let start = BTreeSet::from([0]);
+    if circuit.accept_states.contains(&0) {
+        return Err(CompilerError::GenericError(
+            "The regex matches the empty string, so every input matches it".to_string(),
+        ));
+    }
+    let mut states = vec![Some(start.clone())];
+    let mut state_ids = BTreeMap::from([(Some(start), 0)]);
+    if absorb_matches {
+        states.push(None);
+        state_ids.insert(None, 1);
+    }
+    let mut transitions = vec![];
+    while let Some(state) = states.get(transitions.len()).cloned() {
+        let Some(state) = state else {
+            transitions.push(vec![1; 256]);
+            continue;
+        };
+        let mut next_states = vec![0; 256];
+        for (byte, next_state) in next_states.iter_mut().enumerate().skip(1) {
+            let next = state
+                .iter()
+                .filter_map(|&from| delta[from][byte])
+                .chain([0])
+                .collect::<BTreeSet<_>>();
+            let next = (!absorb_matches
+                || !next
+                    .iter()
+                    .any(|state| circuit.accept_states.contains(state)))
+            .then_some(next);
+            let num_states = state_ids.len();
+            *next_state = *state_ids.entry(next.clone()).or_insert_with(|| {
+                states.push(next);
+                num_states
+            });
+            if states.len() > MAX_SEARCH_STATES {
+                return Err(CompilerError::GraphError(format!(
+                    "The search automaton of the regex has more than {} states",
+                    MAX_SEARCH_STATES
+                )));
+            }
+        }
+        transitions.push(next_states);
+    }
+    let accepts = states
+        .iter()
+        .map(|state| match state {
+            Some(state) if !absorb_matches => state
+                .iter()
+                .any(|state| circuit.accept_states.contains(state)),
+            state => state.is_some(),
+        })
+        .collect::<Vec<_>>();
+    Ok((transitions, accepts))
+}
+
+/// Returns the transitions of a minimized DFA graph as [`NegatedTransition`]s.
+fn collect_transitions(graph: &DFAGraph) -> Vec<NegatedTransition> {
+    graph
+        .states
+        .iter()
+        .flat_map(|state| {
+            state
+                .transitions
+                .iter()
+                .map(move |(&to, bytes)| (state.state_id, to, bytes_to_ranges(bytes)))
+        })
+        .collect()
+}
+
+impl NegatedDfa {
+    /// Builds the NegatedDfa of the circuit of a regex.
+    ///
+    /// # Arguments
+    ///
+    /// * `circuit` - A reference to the RegexCircuit of the regex.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the NegatedDfa or a `CompilerError` if the regex has an end anchor, matches the empty
+    /// string, or its search automaton has more than [`MAX_SEARCH_STATES`] states.
+    pub fn new(circuit: &RegexCircuit) -> Result<Self, CompilerError> {
+        let (transitions, accepts) = build_search_automaton(circuit, true)?;
+        let graph = minimize_graph(&transitions, &accepts)
+            .expect("the start state of the search automaton accepts");
+        Ok(Self {
+            num_states: graph.states.len() + 1,
+            transitions: collect_transitions(&graph),
+        })
+    }
+
+    /// Returns the dead state.
+    pub fn dead_state(&self) -> usize {
+        self.num_states - 1
+    }
+
+    /// Returns the state the DFA moves to from `state` on `byte`.
+    pub fn next_state(&self, state: usize, byte: u8) -> usize {
+        self.transitions
+            .iter()
+            .find(|(from, _, ranges)| {
+                *from == state && ranges.iter().any(|&(min, max)| min <= byte && byte <= max)
+            })
+            .map_or(self.dead_state(), |&(_, to, _)| to)
+    }
+
+    /// Returns whether no substring of `in`, i.e., `255` followed by `msg`, matches the regex.
+    pub fn accepts(&self, msg: &[u8]) -> bool {
+        std::iter::once(255)
+            .chain(msg.iter().copied())
+            .fold(0, |state, byte| self.next_state(state, byte))
+            != self.dead_state()
+    }
+}
+
+/// The complete DFA of the search automaton of a regex on the inputs `in` (i.e., `255` followed by `msg`), whose
+/// accept states are the positions where a substring matched by the regex ends.
+///
+/// It is built by the same subset construction as a [`NegatedDfa`], but keeps running after a match, so it finds
+/// every occurrence of the regex, including those that start inside another run of the matching circuit, which
+/// restarts from the state `0` only when the run fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchDfa {
+    /// The number of states.
+    pub num_states: usize,
+    /// The transitions, which cover every byte from every state.
+    pub transitions: Vec<NegatedTransition>,
+    /// The accept states.
+    pub accept_states: BTreeSet<usize>,
+}
+
+impl SearchDfa {
+    /// Builds the SearchDfa of the circuit of a regex.
+    ///
+    /// # Arguments
+    ///
+    /// * `circuit` - A reference to the RegexCircuit of the regex.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the SearchDfa or a `CompilerError` if the regex has an end anchor, matches the empty
+    /// string or no string, or its search automaton has more than [`MAX_SEARCH_STATES`] states.
+    pub fn new(circuit: &RegexCircuit) -> Result<Self, CompilerError> {
+        let (transitions, accepts) = build_search_automaton(circuit, false)?;
+        let graph = minimize_graph(&transitions, &accepts).ok_or_else(|| {
+            CompilerError::GenericError("The regex matches no string".to_string())
+        })?;
+        Ok(Self {
+            num_states: graph.states.len(),
+            transitions: collect_transitions(&graph),
+            accept_states: graph
+                .states
+                .iter()
+                .filter(|state| state.state_type == "accept")
+                .map(|state| state.state_id)
+                .collect(),
+        })
+    }
+
+    /// Returns the state the DFA moves to from `state` on `byte`.
+    pub fn next_state(&self, state: usize, byte: u8) -> usize {
+        self.transitions
+            .iter()
+            .find(|(from, _, ranges)| {
+                *from == state && ranges.iter().any(|&(min, max)| min <= byte && byte <= max)
+            })
+            .map_or(0, |&(_, to, _)| to)
+    }
+
+    /// Returns the indexes of `msg` after which a substring of `in`, i.e., `255` followed by `msg`, matches the
+    /// regex.
+    pub fn match_ends(&self, msg: &[u8]) -> Vec<usize> {
+        let mut state = self.next_state(0, 255);
+        let mut ends = vec![];
+        for (idx, &byte) in msg.iter().enumerate() {
+            state = self.next_state(state, byte);
+            if self.accept_states.contains(&state) {
+                ends.push(idx);
+            }
+        }
+        ends
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        regex::{create_regex_and_dfa_from_str_and_defs, get_regex_and_dfa},
+        registry_pattern_config,
+        structs::{RegexAndDFA, SubstringDefinitionsJson},
+    };
+    use zk_regex_apis::registry::PatternRegistry;
+
+    /// Returns whether some substring of `in` without the byte `0` is matched by the DFA, by running it from
+    /// every position.
+    fn has_match(regex_and_dfa: &RegexAndDFA, msg: &[u8]) -> bool {
+        let input = std::iter::once(255)
+            .chain(msg.iter().copied())
+            .collect::<Vec<_>>();
+        let dfa = &regex_and_dfa.dfa;
+        (0..input.len()).any(|start| {
+            let mut state = 0;
+            for &byte in input[start..].iter().take_while(|&&byte| byte != 0) {
+                match dfa.states[state]
+                    .transitions
+                    .iter()
+                    .find(|(_, bytes)| bytes.contains(&byte))
+                {
+                    Some((&next, _)) => state = next,
+                    None => return false,
+                }
+                if dfa.states[state].state_type == "accept" {
+                    return true;
+                }
+            }
+            false
+        })
+    }
+
+    #[test]
+    fn test_negated_dfa() {
+        let raw = |regex: &str| {
+            create_regex_and_dfa_from_str_and_defs(
+                regex,
+                SubstringDefinitionsJson {
+                    transitions: vec![],
+                },
+            )
+            .unwrap()
+        };
+        let registry = PatternRegistry::builtin();
+        let mut regexes = vec![
+            raw("viagra|free money|lottery"),
+            raw("abac"),
+            raw("a[a-z]*b"),
+        ];
+        for name in ["from_addr", "subject_all", "email_domain"] {
+            let mut config = registry_pattern_config(&registry, name).unwrap();
+            regexes.push(get_regex_and_dfa(&mut config).unwrap());
+        }
+
+        let inputs: [&[u8]; 9] = [
+            b"hello world",
+            b"claim your free money now",
+            b"free mone\0y",
+            b"ababac",
+            b"aaab",
+            b"from:alice@zkemail.com\r\nsubject:hi\r\n",
+            b"subject:lottery\r\n\0\0\0",
+            b"\0\0\0",
+            b"viagr lotter",
+        ];
+        for regex_and_dfa in &regexes {
+            let negated = NegatedDfa::new(&RegexCircuit::new(regex_and_dfa)).unwrap();
+            for input in inputs {
+                assert_eq!(
+                    negated.accepts(input),
+                    !has_match(regex_and_dfa, input),
+                    "{} {:?}",
+                    regex_and_dfa.regex_pattern,
+                    String::from_utf8_lossy(input)
+                );
+            }
+            let search = SearchDfa::new(&RegexCircuit::new(regex_and_dfa)).unwrap();
+            for input in inputs {
+                assert_eq!(
+                    search.match_ends(input).is_empty(),
+                    !has_match(regex_and_dfa, input)
+                );
+            }
+            // The dead state is absorbing and is the only non-accepting state.
+            assert!(negated
+                .transitions
+                .iter()
+                .all(|&(from, to, _)| from != negated.dead_state() && to != negated.dead_state()));
+        }
+
+        let negated = NegatedDfa::new(&RegexCircuit::new(&raw("abac"))).unwrap();
+        // The greedy matching circuit misses the match starting inside a failed one.
+        let trace = crate::trace::trace_circuit(&raw("abac"), b"ababac").unwrap();
+        assert!(!trace.out);
+        assert!(!negated.accepts(b"ababac"));
+
+        assert!(NegatedDfa::new(&RegexCircuit::new(&raw("a*"))).is_err());
+        assert!(NegatedDfa::new(&RegexCircuit::new(&raw("abc$"))).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{
+    errors::CompilerError,
+    ir::RegexCircuit,
+    structs::{DFAGraph, DFAStateNode, RegexAndDFA, SubstringDefinitions},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use zk_regex_apis::extract_substrs::MatchPolicy;
+
+/// The maximum number of states of a product automaton.
+pub const MAX_PRODUCT_STATES: usize = 4096;
+
+/// A pair of substrings of different regexes of a [`ProductAutomaton`] constrained to be equal, each given by the
+/// index of its regex and its index in the regex.
+pub(crate) type CrossEquality = ((usize, usize), (usize, usize));
+
+/// How the `out` signal of a combined template is computed from the `{name}_out` signals of its regexes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CombineMode {
+    /// `out` is `1` if every regex matches.
+    #[default]
+    All,
+    /// `out` is `1` if some regex matches.
+    Any,
+}
+
+/// A state of a product automaton.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct ProductState {
+    /// The state of each regex.
+    states: Vec<usize>,
+    /// Whether the state of each regex was reached by a transition from a non-zero state, i.e., the
+    /// `from_zero_enabled` signal of the regex is `0`.
+    continued: Vec<bool>,
+}
+
+/// The product of the circuits of several regexes, which tracks every regex with one state vector.
+///
+/// The circuit of a regex effectively transitions from a state `s` on a byte `b` to `δ(s, b)` if it is a non-zero
+/// state and to `δ(0, b)` otherwise. The product automaton runs these effective transitions of all regexes at once,
+/// so that its circuit computes the states and the `from_zero_enabled` signals of each regex as sums of its
+/// states. The accept states and the reveal transitions computed by `process_public_regex` stay in the circuit of
+/// each regex and are checked on these projections.
+#[derive(Debug, Clone)]
+pub struct ProductAutomaton {
+    /// The names of the regexes.
+    pub names: Vec<String>,
+    /// The circuits of the regexes.
+    pub circuits: Vec<RegexCircuit>,
+    /// The product DFA, which has no accept states and no reveal transitions.
+    pub regex_and_dfa: RegexAndDFA,
+    /// The product states, where state `0` is the zero state of every regex.
+    states: Vec<ProductState>,
+}
+
+/// Returns the transitions `δ(state, byte)` to non-zero states of the circuit of a regex, or `0` if there is none.
+///
+/// The byte `0` is excluded since the circuit never transitions on it.
+fn collect_transitions(circuit: &RegexCircuit) -> Vec<[usize; 256]> {
+    let mut delta = vec![[0; 256]; circuit.num_states];
+    for transition in circuit.transitions.iter().filter(|t| t.to != 0) {
+        for &(min, max) in &transition.ranges {
+            for byte in min.max(1)..=max {
+                delta[transition.from][byte as usize] = transition.to;
+            }
+        }
+    }
+    delta
+}
+
+impl ProductAutomaton {
+    /// Builds the product automaton of several regexes by a breadth-first search from the zero state.
+    ///
+    /// # Arguments
+    ///
+    /// * `regexes` - The names and the RegexAndDFA structures of the regexes.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the ProductAutomaton or a `CompilerError` if it has more than
+    /// [`MAX_PRODUCT_STATES`] states.
+    pub fn new(regexes: &[(String, RegexAndDFA)]) -> Result<Self, CompilerError> {
+        let circuits = regexes
+            .iter()
+            .map(|(_, regex_and_dfa)| RegexCircuit::new(regex_and_dfa))
+            .collect::<Vec<_>>();
+        let deltas = circuits.iter().map(collect_transitions).collect::<Vec<_>>();
+
+        let zero = ProductState {
+            states: vec![0; regexes.len()],
+            continued: vec![false; regexes.len()],
+        };
+        let mut states = vec![zero.clone()];
+        let mut state_ids = BTreeMap::from([(zero, 0)]);
+        let mut transitions = vec![];
+        let mut queue = VecDeque::from([0]);
+        while let Some(id) = queue.pop_front() {
+            let mut next_bytes = BTreeMap::<usize, BTreeSet<u8>>::new();
+            for byte in 1..=255u8 {
+                let (next_states, continued) = states[id]
+                    .states
+                    .iter()
+                    .zip(&deltas)
+                    .map(|(&state, delta)| {
+                        let next = delta[state][byte as usize];
+                        if state != 0 && next != 0 {
+                            (next, true)
+                        } else {
+                            (delta[0][byte as usize], false)
+                        }
+                    })
+                    .unzip();
+                let next = ProductState {
+                    states: next_states,
+                    continued,
+                };
+                let next_id = match state_ids.get(&next) {
+                    Some(&next_id) => next_id,
+                    None => {
+                        if states.len() == MAX_PRODUCT_STATES {
+                            return Err(CompilerError::ProductStateLimit(MAX_PRODUCT_STATES));
+                        }
+                        states.push(next.clone());
+                        state_ids.insert(next, states.len() - 1);
+                        queue.push_back(states.len() - 1);
+                        states.len() - 1
+                    }
+                };
+                if next_id != 0 {
+                    next_bytes.entry(next_id).or_default().insert(byte);
+                }
+            }
+            // The states are dequeued in the order of their ids.
+            transitions.push((id, next_bytes));
+        }
+
+        let regex_and_dfa = RegexAndDFA {
+            regex_pattern: regexes
+                .iter()
+                .map(|(name, regex_and_dfa)| format!("{}: {}", name, regex_and_dfa.regex_pattern))
+                .collect::<Vec<_>>()
+                .join(", "),
+            dfa: DFAGraph {
+                states: transitions
+                    .into_iter()
+                    .map(|(id, transitions)| DFAStateNode {
+                        state_type: String::new(),
+                        state_id: id,
+                        transitions,
+                    })
+                    .collect(),
+            },
+            has_end_anchor: regexes
+                .iter()
+                .any(|(_, regex_and_dfa)| regex_and_dfa.has_end_anchor),
+            substrings: SubstringDefinitions {
+                substring_ranges: vec![],
+                substring_boundaries: None,
+                max_lens: vec![],
+                committed: vec![],
+                equal: vec![],
+                uint_decimals: vec![],
+            },
+            match_policy: MatchPolicy::Greedy,
+        };
+
+        Ok(Self {
+            names: regexes.iter().map(|(name, _)| name.clone()).collect(),
+            circuits,
+            regex_and_dfa,
+            states,
+        })
+    }
+
+    /// Returns the state of the regex `regex_idx` in the product state `state`.
+    pub fn component(&self, state: usize, regex_idx: usize) -> usize {
+        self.states[state].states[regex_idx]
+    }
+
+    /// Returns whether the state of the regex `regex_idx` in the product state `state` was reached by a transition
+    /// from a non-zero state.
+    pub fn is_continued(&self, state: usize, regex_idx: usize) -> bool {
+        self.states[state].continued[regex_idx]
+    }
+
+    /// Returns the non-zero product states whose state of the regex `regex_idx` satisfies `filter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `regex_idx` - The index of the regex.
+    /// * `filter` - A predicate on the state of the regex and whether it was reached from a non-zero state.
+    ///
+    /// # Returns
+    ///
+    /// The product states in increasing order.
+    pub fn states_where(
+        &self,
+        regex_idx: usize,
+        filter: impl Fn(usize, bool) -> bool,
+    ) -> Vec<usize> {
+        (1..self.states.len())
+            .filter(|&state| {
+                filter(
+                    self.component(state, regex_idx),
+                    self.is_continued(state, regex_idx),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{regex::get_regex_and_dfa, registry_pattern_config, trace::trace_circuit};
+    use zk_regex_apis::registry::PatternRegistry;
+
+    #[test]
+    fn test_product_projections() {
+        let registry = PatternRegistry::builtin();
+        let regexes = ["email_domain", "from_addr", "subject_all"]
+            .iter()
+            .map(|name| {
+                let mut config = registry_pattern_config(&registry, name).unwrap();
+                (name.to_string(), get_regex_and_dfa(&mut config).unwrap())
+            })
+            .collect::<Vec<_>>();
+        let product = ProductAutomaton::new(&regexes).unwrap();
+        assert!(product.regex_and_dfa.dfa.states.len() > 1);
+
+        let msg = b"from:Alice <alice@zkemail.com>\r\nsubject:hello\r\nto:bob@example.org\r\n\0\0";
+        let dfa = &product.regex_and_dfa.dfa;
+        let next = |from: usize, byte: u8| {
+            dfa.states[from]
+                .transitions
+                .iter()
+                .find(|(_, bytes)| bytes.contains(&byte))
+                .map(|(&next, _)| next)
+        };
+        // in[0] = 255 is read from the zero state, which the product automaton falls back to on every byte.
+        let mut state = next(0, 255).unwrap_or(0);
+        let initial_state = state;
+        let mut product_states = vec![];
+        for &byte in msg {
+            state = next(state, byte).or_else(|| next(0, byte)).unwrap_or(0);
+            product_states.push(state);
+        }
+
+        for (regex_idx, (_, regex_and_dfa)) in regexes.iter().enumerate() {
+            let trace = trace_circuit(regex_and_dfa, msg).unwrap();
+            let initial = product.component(initial_state, regex_idx);
+            assert_eq!(
+                trace.initial_states,
+                (initial != 0)
+                    .then_some(initial)
+                    .into_iter()
+                    .collect::<Vec<_>>()
+            );
+            for (byte_trace, &state) in trace.bytes.iter().zip(&product_states) {
+                let component = product.component(state, regex_idx);
+                let active = (component != 0)
+                    .then_some(component)
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                assert_eq!(byte_trace.active_states, active);
+                assert_eq!(byte_trace.state_changed, component != 0);
+                assert_eq!(
+                    byte_trace.from_zero_enabled,
+                    !product.is_continued(state, regex_idx)
+                );
+            }
+        }
+    }
+}
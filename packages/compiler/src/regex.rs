@@ -1,8 +1,8 @@
 use crate::{
     errors::CompilerError,
     structs::{
-        DFAGraph, DFAGraphInfo, DFAStateInfo, DFAStateNode, RegexAndDFA, RegexPartConfig,
-        SubstringDefinitions, SubstringDefinitionsJson,
+        DFAGraph, DFAGraphInfo, DFAStateInfo, DFAStateNode, PartOutput, RegexAndDFA,
+        RegexPartConfig, SubstringDefinitions, SubstringDefinitionsJson,
     },
     DecomposedRegexConfig,
 };
@@ -15,6 +15,18 @@ use std::{
     collections::{BTreeMap, BTreeSet, VecDeque},
     num::ParseIntError,
 };
+use zk_regex_apis::{
+    commitment::MAX_COMMITTED_BYTES,
+    extract_substrs::{MatchPolicy, PartConstraint, PartRef},
+    leftmost::find_caret_index,
+    numeric::MAX_UINT_DIGITS,
+};
+
+/// The maximum number of states of the product automaton of the operators of a regex part.
+pub const MAX_PART_STATES: usize = 4096;
+
+/// The (start states, end states) of a substring.
+type SubstringBoundaries = (BTreeSet<usize>, BTreeSet<usize>);
 
 /// Creates a DFA configuration with specific settings.
 ///
@@ -29,61 +41,6 @@ fn create_dfa_config() -> Config {
         .accelerate(true)
 }
 
-/// Finds the index of the first caret (^) in a regex string that is not inside parentheses.
-///
-/// # Arguments
-///
-/// * `regex` - A string slice containing the regex pattern.
-///
-/// # Returns
-///
-/// An `Option<usize>` containing the index of the caret if found, or `None` if not found.
-fn find_caret_index(regex: &str) -> Option<usize> {
-    let regex_bytes = regex.as_bytes();
-    let mut is_in_parenthesis = false;
-    let mut caret_found = false;
-    let mut idx = 0;
-
-    while idx < regex_bytes.len() {
-        match regex_bytes[idx] {
-            b'\\' => {
-                idx += 2;
-            }
-            b'(' => {
-                is_in_parenthesis = true;
-                idx += 1;
-            }
-            b'[' => {
-                idx += 2;
-            }
-            b')' => {
-                debug_assert!(is_in_parenthesis, "Unmatched parenthesis");
-                is_in_parenthesis = false;
-                idx += 1;
-                if caret_found {
-                    break;
-                }
-            }
-            b'^' => {
-                caret_found = true;
-                idx += 1;
-                if !is_in_parenthesis {
-                    break;
-                }
-            }
-            _ => {
-                idx += 1;
-            }
-        }
-    }
-
-    if caret_found {
-        Some(idx)
-    } else {
-        None
-    }
-}
-
 /// Processes the caret (^) in a regex, splitting it into two parts if necessary.
 ///
 /// # Arguments
@@ -99,10 +56,16 @@ fn process_caret_in_regex(
     let caret_position = find_caret_index(&decomposed_regex.parts[0].regex_def);
 
     if let Some(index) = caret_position {
+        if has_part_operators(&decomposed_regex.parts[0]) {
+            return Err(CompilerError::GenericError(
+                "Invalid regex, the operators of a part cannot be combined with ^".to_string(),
+            ));
+        }
         let caret_regex = decomposed_regex.parts[0].regex_def[0..index].to_string();
         decomposed_regex.parts.push_front(RegexPartConfig {
             is_public: false,
             regex_def: caret_regex,
+            ..Default::default()
         });
         decomposed_regex.parts[1].regex_def =
             decomposed_regex.parts[1].regex_def[index..].to_string();
@@ -825,12 +788,16 @@ fn process_public_regex(
     net_dfa: &DFAGraph,
     graph: &DFAGraph,
     previous_max_state_id: usize,
-) -> (BTreeSet<(usize, usize)>, (BTreeSet<usize>, BTreeSet<usize>)) {
+) -> (BTreeSet<(usize, usize)>, SubstringBoundaries) {
     if !regex.is_public {
         return (BTreeSet::new(), (BTreeSet::new(), BTreeSet::new()));
     }
 
-    let (accepting_states, substring_starts) = collect_accepting_states(net_dfa);
+    let (accepting_states, mut substring_starts) = collect_accepting_states(net_dfa);
+    // The first part starts from its own start state, as no previous part accepts.
+    if net_dfa.states.is_empty() {
+        substring_starts.extend(graph.states.first().map(|state| state.state_id));
+    }
     let mut public_edges = collect_public_edges(graph);
     let substring_ends = collect_substr_ends(graph);
 
@@ -919,6 +886,262 @@ fn add_dfa(net_dfa: &DFAGraph, graph: &DFAGraph) -> DFAGraph {
     new_dfa
 }
 
+/// Returns whether a regex part uses the `and`, `difference` or `not` operators.
+fn has_part_operators(regex: &RegexPartConfig) -> bool {
+    !regex.and.is_empty() || !regex.difference.is_empty() || regex.not
+}
+
+/// Describes a regex part including its operators, e.g. `([a-z]+)&(.{1,8})-(abc)` or `!(abc)`.
+fn describe_part(regex: &RegexPartConfig) -> String {
+    if !has_part_operators(regex) {
+        return regex.regex_def.clone();
+    }
+    let mut description = format!("({})", regex.regex_def);
+    for and in &regex.and {
+        description += &format!("&({})", and);
+    }
+    for difference in &regex.difference {
+        description += &format!("-({})", difference);
+    }
+    if regex.not {
+        description = format!("!({})", description);
+    }
+    description
+}
+
+/// Builds the DFA graph of a regex that matches whole strings.
+///
+/// # Arguments
+///
+/// * `regex_def` - The regex string.
+/// * `config` - The DFA configuration.
+///
+/// # Returns
+///
+/// A `Result` containing the `DFAGraph` or a `CompilerError`.
+fn create_part_dfa_graph(regex_def: &str, config: &Config) -> Result<DFAGraph, CompilerError> {
+    let dfa = DFA::builder()
+        .configure(config.clone())
+        .build(&format!(r"^({})$", regex_def))
+        .map_err(|err| CompilerError::BuildError {
+            regex: regex_def.to_string(),
+            source: Box::new(err),
+        })?;
+    convert_dfa_to_graph(dfa)
+}
+
+/// Returns the state a DFA graph moves to from `state` on `byte`, or `None` if it rejects every continuation.
+fn next_graph_state(graph: &DFAGraph, state: usize, byte: u8) -> Option<usize> {
+    graph.states[state]
+        .transitions
+        .iter()
+        .find(|(_, bytes)| bytes.contains(&byte))
+        .map(|(&next, _)| next)
+}
+
+/// Minimizes a DFA given by its transitions and accept flags, dropping the states that reach no accept state.
+///
+/// # Arguments
+///
+/// * `transitions` - The next state of each state on each byte.
+/// * `accepts` - Whether each state is an accept state.
+///
+/// # Returns
+///
+/// The minimal `DFAGraph`, whose states are numbered in BFS order from the start state `0`, or `None` if it matches
+/// no string.
+pub(crate) fn minimize_graph(transitions: &[Vec<usize>], accepts: &[bool]) -> Option<DFAGraph> {
+    // The live states are those from which an accept state is reachable.
+    let mut live = accepts.to_vec();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for state in 0..transitions.len() {
+            if !live[state] && transitions[state].iter().any(|&next| live[next]) {
+                live[state] = true;
+                changed = true;
+            }
+        }
+    }
+    if !live[0] {
+        return None;
+    }
+    let next_live =
+        |state: usize, byte: usize| Some(transitions[state][byte]).filter(|&next| live[next]);
+
+    // Moore's partition refinement, where the dead states are the class `None`.
+    let mut classes = accepts
+        .iter()
+        .map(|&accept| accept as usize)
+        .collect::<Vec<_>>();
+    let mut num_classes = 0;
+    loop {
+        let mut signatures = BTreeMap::new();
+        let next_classes = (0..transitions.len())
+            .map(|state| {
+                let signature = (
+                    classes[state],
+                    (0..transitions[state].len())
+                        .map(|byte| next_live(state, byte).map(|next| classes[next]))
+                        .collect::<Vec<_>>(),
+                );
+                let num_signatures = signatures.len();
+                *signatures.entry(signature).or_insert(num_signatures)
+            })
+            .collect::<Vec<_>>();
+        classes = next_classes;
+        if signatures.len() == num_classes {
+            break;
+        }
+        num_classes = signatures.len();
+    }
+
+    // Number the classes of the live states in BFS order from the start state.
+    let mut class_ids = BTreeMap::from([(classes[0], 0)]);
+    let mut representatives = vec![0];
+    let mut states = vec![];
+    while let Some(&state) = representatives.get(states.len()) {
+        let mut next_bytes = BTreeMap::<usize, BTreeSet<u8>>::new();
+        for byte in 0..transitions[state].len() {
+            if let Some(next) = next_live(state, byte) {
+                let num_ids = class_ids.len();
+                let next_id = *class_ids.entry(classes[next]).or_insert_with(|| {
+                    representatives.push(next);
+                    num_ids
+                });
+                next_bytes.entry(next_id).or_default().insert(byte as u8);
+            }
+        }
+        states.push(DFAStateNode {
+            state_type: if accepts[state] {
+                "accept".to_string()
+            } else {
+                String::new()
+            },
+            state_id: states.len(),
+            transitions: next_bytes,
+        });
+    }
+
+    Some(DFAGraph { states })
+}
+
+/// Evaluates the `and`, `difference` and `not` operators of a regex part on the DFA graph of its `regex_def`.
+///
+/// The graphs of the operands are run in parallel over every byte except `255`, which the circuit reserves for
+/// the start of the input, and the product state accepts if the `regex_def` and every `and` graph accept and no
+/// `difference` graph accepts, or the opposite if `not` is set. The product is then minimized.
+///
+/// # Arguments
+///
+/// * `regex` - The regex part.
+/// * `graph` - The DFA graph of `regex.regex_def`.
+/// * `config` - The DFA configuration of the operands.
+///
+/// # Returns
+///
+/// A `Result` containing the `DFAGraph` of the part or a `CompilerError` if the part matches no string or the
+/// product has more than [`MAX_PART_STATES`] states.
+fn apply_part_operators(
+    regex: &RegexPartConfig,
+    graph: DFAGraph,
+    config: &Config,
+) -> Result<DFAGraph, CompilerError> {
+    if !has_part_operators(regex) {
+        return Ok(graph);
+    }
+    let num_ands = regex.and.len();
+    let graphs = std::iter::once(Ok(graph))
+        .chain(
+            regex
+                .and
+                .iter()
+                .chain(&regex.difference)
+                .map(|regex_def| create_part_dfa_graph(regex_def, config)),
+        )
+        .collect::<Result<Vec<_>, CompilerError>>()?;
+    let is_accept = |state: &[Option<usize>]| {
+        let accepted = |idx: usize| {
+            state[idx].is_some_and(|state| graphs[idx].states[state].state_type == "accept")
+        };
+        let matched = (0..=num_ands).all(accepted) && !(num_ands + 1..graphs.len()).any(accepted);
+        matched != regex.not
+    };
+
+    let start = vec![Some(0); graphs.len()];
+    let mut state_ids = BTreeMap::from([(start.clone(), 0)]);
+    let mut states = vec![start];
+    let mut transitions = vec![];
+    while let Some(state) = states.get(transitions.len()).cloned() {
+        let next_states = (0..255u8)
+            .map(|byte| {
+                let next = graphs
+                    .iter()
+                    .zip(&state)
+                    .map(|(graph, state)| {
+                        state.and_then(|state| next_graph_state(graph, state, byte))
+                    })
+                    .collect::<Vec<_>>();
+                let num_states = state_ids.len();
+                *state_ids.entry(next.clone()).or_insert_with(|| {
+                    states.push(next);
+                    num_states
+                })
+            })
+            .collect();
+        if states.len() > MAX_PART_STATES {
+            return Err(CompilerError::GraphError(format!(
+                "The product of the operators of the regex part {} has more than {} states",
+                describe_part(regex),
+                MAX_PART_STATES
+            )));
+        }
+        transitions.push(next_states);
+    }
+    let accepts = states
+        .iter()
+        .map(|state| is_accept(state))
+        .collect::<Vec<_>>();
+
+    minimize_graph(&transitions, &accepts).ok_or_else(|| {
+        CompilerError::GraphError(format!(
+            "The regex part {} matches no string",
+            describe_part(regex)
+        ))
+    })
+}
+
+/// Returns the index of the substring of the part `part_idx`, which an equality constraint refers to as `part`.
+///
+/// # Arguments
+///
+/// * `decomposed_regex` - A reference to the DecomposedRegexConfig containing the part, before its caret is
+///   processed.
+/// * `part_idx` - The index of the part in `parts`.
+/// * `part` - The reference to the part in the constraint.
+///
+/// # Returns
+///
+/// A Result containing the index of the substring, or a CompilerError if the part is not public with a max_len.
+pub(crate) fn constrained_substr_idx(
+    decomposed_regex: &DecomposedRegexConfig,
+    part_idx: usize,
+    part: &PartRef,
+) -> Result<usize, CompilerError> {
+    match decomposed_regex.parts.get(part_idx) {
+        Some(regex) if regex.is_public && regex.max_len.is_some() => Ok(decomposed_regex
+            .parts
+            .iter()
+            .take(part_idx)
+            .filter(|regex| regex.is_public)
+            .count()),
+        _ => Err(CompilerError::GenericError(format!(
+            "The constrained part {} must be a public part with a max_len",
+            part
+        ))),
+    }
+}
+
 /// Constructs a RegexAndDFA structure from a decomposed regex configuration.
 ///
 /// This function processes each part of the decomposed regex, builds individual DFAs,
@@ -938,25 +1161,87 @@ pub(crate) fn get_regex_and_dfa(
     let mut net_dfa_graph = DFAGraph { states: Vec::new() };
     let mut substring_ranges_array = Vec::new();
     let mut substring_boundaries_array = Vec::new();
+    let mut max_lens = Vec::new();
+    let mut committed = Vec::new();
+    let mut uint_decimals = Vec::new();
 
     let config = create_dfa_config();
 
+    let resolve = |part: &PartRef| match part {
+        PartRef::Index(part_idx) => constrained_substr_idx(decomposed_regex, *part_idx, part),
+        PartRef::Named { .. } => Err(CompilerError::GenericError(format!(
+            "The constrained part {} of another regex requires a combined template",
+            part
+        ))),
+    };
+    let equal = decomposed_regex
+        .constraints
+        .iter()
+        .map(|PartConstraint::Equal([a, b])| Ok((resolve(a)?, resolve(b)?)))
+        .collect::<Result<Vec<_>, CompilerError>>()?;
+
     let caret_position = process_caret_in_regex(decomposed_regex)?;
 
     let mut end_anchor = false;
 
     for (i, regex) in decomposed_regex.parts.iter().enumerate() {
         end_anchor = validate_end_anchor(decomposed_regex, i, regex)?;
+        match regex.max_len {
+            Some(_) if !regex.is_public => {
+                return Err(CompilerError::GenericError(format!(
+                    "The regex part {} has a max_len but is not public",
+                    describe_part(regex)
+                )));
+            }
+            Some(0) => {
+                return Err(CompilerError::GenericError(format!(
+                    "The max_len of the regex part {} must be positive",
+                    describe_part(regex)
+                )));
+            }
+            _ => {}
+        }
+        if regex.committed
+            && !(regex.is_public
+                && regex
+                    .max_len
+                    .is_some_and(|max_len| max_len <= MAX_COMMITTED_BYTES))
+        {
+            return Err(CompilerError::GenericError(format!(
+                "The committed regex part {} must be public with a max_len of at most {}",
+                describe_part(regex),
+                MAX_COMMITTED_BYTES
+            )));
+        }
+        match regex.output {
+            PartOutput::Uint
+                if !(regex.is_public
+                    && !regex.committed
+                    && regex.max_len.is_some_and(|max_len| {
+                        max_len + regex.decimals.unwrap_or(0) <= MAX_UINT_DIGITS
+                    })) =>
+            {
+                return Err(CompilerError::GenericError(format!(
+                    "The regex part {} with the uint output must be public and not committed, with a max_len of \
+                     at most {} digits including its decimals",
+                    describe_part(regex),
+                    MAX_UINT_DIGITS
+                )));
+            }
+            PartOutput::Bytes if regex.decimals.is_some() => {
+                return Err(CompilerError::GenericError(format!(
+                    "The regex part {} has decimals but not the uint output",
+                    describe_part(regex)
+                )));
+            }
+            _ => {}
+        }
 
-        let dfa = DFA::builder()
-            .configure(config.clone())
-            .build(&format!(r"^({})$", regex.regex_def.as_str()))
-            .map_err(|err| CompilerError::BuildError {
-                regex: regex.regex_def.clone(),
-                source: err,
-            })?;
-
-        let mut dfa_graph = convert_dfa_to_graph(dfa)?;
+        let mut dfa_graph = apply_part_operators(
+            regex,
+            create_part_dfa_graph(&regex.regex_def, &config)?,
+            &config,
+        )?;
 
         handle_caret_regex(i, caret_position, regex, &mut dfa_graph)?;
 
@@ -974,15 +1259,37 @@ pub(crate) fn get_regex_and_dfa(
                 process_public_regex(regex, &net_dfa_graph, &dfa_graph, max_state_index);
             substring_ranges_array.push(public_edges);
             substring_boundaries_array.push((substr_starts, substr_ends));
+            max_lens.push(regex.max_len);
+            committed.push(regex.committed);
+            uint_decimals
+                .push((regex.output == PartOutput::Uint).then(|| regex.decimals.unwrap_or(0)));
         }
 
         net_dfa_graph = add_dfa(&net_dfa_graph, &dfa_graph);
     }
 
+    if let Some(reentrant_state) =
+        split_initial_state(&mut net_dfa_graph, &mut substring_ranges_array)
+    {
+        for (starts, ends) in &mut substring_boundaries_array {
+            for states in [starts, ends] {
+                if states.contains(&0) {
+                    states.insert(reentrant_state);
+                }
+            }
+        }
+    }
+
+    if end_anchor && !decomposed_regex.match_policy.is_greedy() {
+        return Err(CompilerError::GenericError(
+            "The leftmost match policies do not support the end anchor $".to_string(),
+        ));
+    }
+
     let regex_str = decomposed_regex
         .parts
         .iter()
-        .map(|regex| regex.regex_def.as_str())
+        .map(describe_part)
         .collect::<String>();
 
     Ok(RegexAndDFA {
@@ -992,10 +1299,80 @@ pub(crate) fn get_regex_and_dfa(
         substrings: SubstringDefinitions {
             substring_ranges: substring_ranges_array,
             substring_boundaries: Some(substring_boundaries_array),
+            max_lens: if max_lens.iter().any(Option::is_some) {
+                max_lens
+            } else {
+                vec![]
+            },
+            committed: if committed.contains(&true) {
+                committed
+            } else {
+                vec![]
+            },
+            equal,
+            uint_decimals: if uint_decimals.iter().any(Option::is_some) {
+                uint_decimals
+            } else {
+                vec![]
+            },
         },
+        match_policy: decomposed_regex.match_policy,
     })
 }
 
+/// Splits the initial state of a DFA into the search-start state `0` and a re-entrant copy, if some transition
+/// enters the initial state.
+///
+/// The circuit treats state `0` as the state the search restarts from and never activates it by a transition, so
+/// a transition into it (e.g., the loop of a `.*` prefix or of a Kleene-star group at the start) would end the
+/// match. The copy has the transitions and the state type of the initial state and receives every transition
+/// that entered it, so the DFA accepts the same strings and its state `0` is only the search start.
+///
+/// # Arguments
+///
+/// * `dfa` - A mutable reference to the DFAGraph.
+/// * `substring_ranges` - The (from, to) state transitions of each substring, which are redirected in the same way.
+///
+/// # Returns
+///
+/// The state ID of the re-entrant copy, or `None` if no transition enters the initial state.
+fn split_initial_state(
+    dfa: &mut DFAGraph,
+    substring_ranges: &mut [BTreeSet<(usize, usize)>],
+) -> Option<usize> {
+    if !dfa
+        .states
+        .iter()
+        .any(|state| state.transitions.contains_key(&0))
+    {
+        return None;
+    }
+
+    let reentrant_state = get_max_state(dfa) + 1;
+    let mut copy = DFAStateNode {
+        state_id: reentrant_state,
+        ..dfa.states[0].clone()
+    };
+    for state in dfa.states.iter_mut().chain([&mut copy]) {
+        if let Some(chars) = state.transitions.remove(&0) {
+            state.transitions.insert(reentrant_state, chars);
+        }
+    }
+    dfa.states.push(copy);
+
+    for ranges in substring_ranges.iter_mut() {
+        *ranges = ranges
+            .iter()
+            .flat_map(|&(from, to)| {
+                let to = if to == 0 { reentrant_state } else { to };
+                let copied = (from == 0).then_some((reentrant_state, to));
+                std::iter::once((from, to)).chain(copied)
+            })
+            .collect();
+    }
+    Some(reentrant_state)
+}
+
 /// Creates a DFA graph from a regex string.
 ///
 /// # Arguments
@@ -1017,7 +1394,7 @@ fn create_dfa_graph_from_regex(regex: &str) -> Result<DFAGraph, CompilerError> {
         .build(&format!(r"^{}$", regex))
         .map_err(|e| CompilerError::BuildError {
             regex: regex.to_string(),
-            source: e,
+            source: Box::new(e),
         })?;
 
     convert_dfa_to_graph(dfa)
@@ -1061,6 +1438,65 @@ fn match_string_with_dfa_graph(graph: &DFAGraph, input: &str) -> bool {
     graph.states[current_state].state_type == "accept"
 }
 
+/// Validates the substring transitions of a raw regex and computes the start and end states of each substring.
+///
+/// A start state is the source of a substring transition that is the initial state or is entered by a transition
+/// outside the substring. An end state is the destination of a substring transition that is an accept state
+/// or is left by a transition outside the substring.
+///
+/// # Arguments
+///
+/// * `dfa` - A reference to the `DFAGraph` of the raw regex.
+/// * `substring_ranges` - The (from, to) state transitions of each substring.
+///
+/// # Returns
+///
+/// A `Result` containing the (start states, end states) of each substring, or a `CompilerError` naming the first
+/// transition that does not exist in the DFA.
+fn get_substring_boundaries(
+    dfa: &DFAGraph,
+    substring_ranges: &[BTreeSet<(usize, usize)>],
+) -> Result<Vec<SubstringBoundaries>, CompilerError> {
+    let edges = collect_public_edges(dfa);
+    let accept_states = collect_substr_ends(dfa);
+
+    substring_ranges
+        .iter()
+        .enumerate()
+        .map(|(substr_idx, ranges)| {
+            if let Some(&(from, to)) = ranges.iter().find(|edge| !edges.contains(edge)) {
+                return Err(CompilerError::MissingSubstringTransition {
+                    substr_idx,
+                    from,
+                    to,
+                });
+            }
+
+            let starts = ranges
+                .iter()
+                .map(|&(from, _)| from)
+                .filter(|&from| {
+                    from == 0
+                        || edges
+                            .iter()
+                            .any(|edge| edge.1 == from && !ranges.contains(edge))
+                })
+                .collect();
+            let ends = ranges
+                .iter()
+                .map(|&(_, to)| to)
+                .filter(|&to| {
+                    accept_states.contains(&to)
+                        || edges
+                            .iter()
+                            .any(|edge| edge.0 == to && !ranges.contains(edge))
+                })
+                .collect();
+            Ok((starts, ends))
+        })
+        .collect()
+}
+
 /// Creates a `RegexAndDFA` from a regex string and substring definitions.
 ///
 /// # Arguments
@@ -1075,9 +1511,9 @@ pub(crate) fn create_regex_and_dfa_from_str_and_defs(
     regex_str: &str,
     substrs_defs_json: SubstringDefinitionsJson,
 ) -> Result<RegexAndDFA, CompilerError> {
-    let dfa = create_dfa_graph_from_regex(regex_str)?;
+    let mut dfa = create_dfa_graph_from_regex(regex_str)?;
 
-    let substring_ranges = substrs_defs_json
+    let mut substring_ranges = substrs_defs_json
         .transitions
         .into_iter()
         .map(|transitions| {
@@ -1085,11 +1521,20 @@ pub(crate) fn create_regex_and_dfa_from_str_and_defs(
                 .into_iter()
                 .collect::<BTreeSet<(usize, usize)>>()
         })
-        .collect();
+        .collect::<Vec<_>>();
+
+    // The substring transitions are validated against the DFA before its initial state is split.
+    get_substring_boundaries(&dfa, &substring_ranges)?;
+    split_initial_state(&mut dfa, &mut substring_ranges);
+    let substring_boundaries = get_substring_boundaries(&dfa, &substring_ranges)?;
 
     let substrings = SubstringDefinitions {
         substring_ranges,
-        substring_boundaries: None,
+        substring_boundaries: Some(substring_boundaries),
+        max_lens: vec![],
+        committed: vec![],
+        equal: vec![],
+        uint_decimals: vec![],
     };
 
     Ok(RegexAndDFA {
@@ -1097,24 +1542,10 @@ pub(crate) fn create_regex_and_dfa_from_str_and_defs(
         dfa,
         has_end_anchor: regex_str.ends_with('$'),
         substrings,
+        match_policy: MatchPolicy::Greedy,
     })
 }
 
-/// Gets the index of the accepted state in a DFA graph.
-///
-/// # Arguments
-///
-/// * `dfa` - A reference to a `DFAGraph`.
-///
-/// # Returns
-///
-/// An `Option<usize>` containing the index of the accepted state, if found.
-pub(crate) fn get_accepted_state(dfa: &DFAGraph) -> Option<usize> {
-    dfa.states
-        .iter()
-        .position(|state| state.state_type == "accept")
-}
-
 /// Gets the maximum state ID in a DFA graph.
 ///
 /// # Arguments
@@ -1132,10 +1563,16 @@ pub(crate) fn get_max_state(dfa: &DFAGraph) -> usize {
         .unwrap_or_default()
 }
 
+#[cfg(test)]
 mod dfa_test {
-    use crate::regex::{create_dfa_graph_from_regex, match_string_with_dfa_graph};
+    use crate::regex::{
+        apply_part_operators, create_dfa_config, create_dfa_graph_from_regex,
+        create_part_dfa_graph, get_regex_and_dfa, match_string_with_dfa_graph, MAX_PART_STATES,
+    };
+    use crate::structs::{DecomposedRegexConfig, RegexPartConfig};
+    use crate::trace::trace_circuit;
     use serde::{Deserialize, Serialize};
-    use std::{env, fs::File, io::BufReader, path::PathBuf};
+    use std::{collections::VecDeque, env, fs::File, io::BufReader, path::PathBuf};
 
     #[derive(Debug, Deserialize, Serialize)]
     struct RegexTestCase {
@@ -1183,4 +1620,171 @@ mod dfa_test {
             }
         }
     }
+    #[test]
+    fn test_part_operators() {
+        let config = create_dfa_config();
+        let part_graph = |part: RegexPartConfig| {
+            apply_part_operators(
+                &part,
+                create_part_dfa_graph(&part.regex_def, &config).unwrap(),
+                &config,
+            )
+            .unwrap()
+        };
+
+        let token = part_graph(RegexPartConfig {
+            regex_def: "[A-Za-z0-9]+".to_string(),
+            difference: vec!["unsubscribe".to_string()],
+            ..Default::default()
+        });
+        for input in ["hello", "unsub", "unsubscribes", "Unsubscribe"] {
+            assert!(match_string_with_dfa_graph(&token, input), "{}", input);
+        }
+        for input in ["", "unsubscribe", "hello world"] {
+            assert!(!match_string_with_dfa_graph(&token, input), "{}", input);
+        }
+
+        let short_addr = part_graph(RegexPartConfig {
+            regex_def: "[a-z]+@[a-z.]+".to_string(),
+            and: vec![".{0,8}".to_string()],
+            ..Default::default()
+        });
+        assert!(match_string_with_dfa_graph(&short_addr, "ab@cd.io"));
+        assert!(!match_string_with_dfa_graph(&short_addr, "abc@cd.io"));
+
+        let not_abc = part_graph(RegexPartConfig {
+            regex_def: "abc".to_string(),
+            not: true,
+            ..Default::default()
+        });
+        for input in ["", "ab", "abcd", "xyz"] {
+            assert!(match_string_with_dfa_graph(&not_abc, input), "{}", input);
+        }
+        assert!(!match_string_with_dfa_graph(&not_abc, "abc"));
+
+        // The minimized product of a regex with itself is the DFA of the regex.
+        let email = "[a-z0-9.]+@[a-z0-9]+\\.com";
+        let product = part_graph(RegexPartConfig {
+            regex_def: email.to_string(),
+            and: vec![email.to_string()],
+            ..Default::default()
+        });
+        assert_eq!(
+            product.states.len(),
+            create_part_dfa_graph(email, &config).unwrap().states.len()
+        );
+
+        assert!(apply_part_operators(
+            &RegexPartConfig {
+                regex_def: "abc".to_string(),
+                difference: vec!["[a-c]+".to_string()],
+                ..Default::default()
+            },
+            create_part_dfa_graph("abc", &config).unwrap(),
+            &config,
+        )
+        .is_err());
+
+        // The product of cycles of coprime lengths has their product number of states.
+        let err = apply_part_operators(
+            &RegexPartConfig {
+                regex_def: "(a{64})*b".to_string(),
+                and: vec!["(a{65})*b".to_string()],
+                ..Default::default()
+            },
+            create_part_dfa_graph("(a{64})*b", &config).unwrap(),
+            &config,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with(&format!("has more than {} states", MAX_PART_STATES)));
+    }
+
+    #[test]
+    fn test_public_part_with_operators() {
+        let mut decomposed = DecomposedRegexConfig {
+            parts: VecDeque::from([
+                RegexPartConfig {
+                    regex_def: "subject:".to_string(),
+                    ..Default::default()
+                },
+                RegexPartConfig {
+                    is_public: true,
+                    regex_def: "[a-z]+".to_string(),
+                    difference: vec!["unsubscribe".to_string()],
+                    ..Default::default()
+                },
+                RegexPartConfig {
+                    regex_def: "\r\n".to_string(),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+        let regex_and_dfa = get_regex_and_dfa(&mut decomposed).unwrap();
+        assert_eq!(
+            regex_and_dfa.regex_pattern,
+            "subject:([a-z]+)-(unsubscribe)\r\n"
+        );
+
+        let trace = trace_circuit(&regex_and_dfa, b"subject:hello\r\n\0").unwrap();
+        assert!(trace.out);
+        assert_eq!(trace.reveals, vec![vec![(8, 13)]]);
+        let trace = trace_circuit(&regex_and_dfa, b"subject:unsubscribe\r\n\0").unwrap();
+        assert!(!trace.out);
+    }
+
+    #[test]
+    fn test_split_initial_state() {
+        let cases: [(&str, &[u8], (usize, usize)); 4] = [
+            ("(a|b)*c", b"xxabbac\0", (2, 7)),
+            ("(ab)*c", b"aababc\0\0", (1, 6)),
+            (".*abc", b"hello abc\0", (0, 9)),
+            ("[a-z]*=", b"key=value\0", (0, 4)),
+        ];
+        for (regex, msg, reveal) in cases {
+            let mut decomposed = DecomposedRegexConfig {
+                parts: VecDeque::from([RegexPartConfig {
+                    is_public: true,
+                    regex_def: regex.to_string(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            };
+            let regex_and_dfa = get_regex_and_dfa(&mut decomposed).unwrap();
+            let dfa = &regex_and_dfa.dfa;
+            assert!(
+                dfa.states
+                    .iter()
+                    .all(|state| !state.transitions.contains_key(&0)),
+                "{}",
+                regex
+            );
+            let full_match = regex::Regex::new(&format!("^(?:{})$", regex)).unwrap();
+            for input in [
+                "",
+                "c",
+                "abc",
+                "ababc",
+                "aabc",
+                "abac",
+                "x=",
+                "=",
+                "hello abc",
+            ] {
+                assert_eq!(
+                    match_string_with_dfa_graph(dfa, input),
+                    full_match.is_match(input),
+                    "{} {:?}",
+                    regex,
+                    input
+                );
+            }
+
+            let trace = trace_circuit(&regex_and_dfa, msg).unwrap();
+            assert!(trace.out, "{}", regex);
+            assert_eq!(trace.reveals, vec![vec![reveal]], "{}", regex);
+        }
+    }
 }
@@ -1,15 +1,105 @@
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use zk_regex_apis::extract_substrs::{MatchPolicy, PartConstraint};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RegexPartConfig {
     pub is_public: bool,
     pub regex_def: String,
+    /// Regexes that the part must also match, i.e., the part matches the intersection of `regex_def` and them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub and: Vec<String>,
+    /// Regexes that the part must not match, i.e., the part matches `regex_def` minus them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub difference: Vec<String>,
+    /// Whether the part matches the complement of the above, i.e., every byte string except `255` it does not match.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub not: bool,
+    /// The maximal length of the substring of a public part, which adds the `reveal{idx}_start`,
+    /// `reveal{idx}_len` and `reveal{idx}_packed[max_len]` outputs to the Circom template.
+    #[serde(default, alias = "maxLen", skip_serializing_if = "Option::is_none")]
+    pub max_len: Option<usize>,
+    /// Whether the substring of a public part is committed instead of revealed, i.e., the Circom template outputs
+    /// `reveal{idx}_commitment`, the Poseidon hash of its packed bytes and the `reveal{idx}_salt` input, instead of
+    /// the other `reveal{idx}` outputs. It requires a `max_len`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub committed: bool,
+    /// The additional output of a public part with a `max_len`, see [`PartOutput`].
+    #[serde(default, skip_serializing_if = "PartOutput::is_bytes")]
+    pub output: PartOutput,
+    /// The number of decimals of a part with the [`PartOutput::Uint`] output, which may then contain a decimal
+    /// point followed by at most this many digits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decimals: Option<usize>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The additional output of a public part with a `max_len`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartOutput {
+    /// Only the bytes of the substring.
+    #[default]
+    Bytes,
+    /// Also the numeric value of the substring as `reveal{idx}_value`, where the circuit constrains the substring
+    /// to ASCII digits and the value is scaled by `10 ** decimals`, e.g., `12.5` with 2 decimals is `1250`.
+    Uint,
+}
+
+impl PartOutput {
+    /// Returns whether this is [`PartOutput::Bytes`].
+    pub fn is_bytes(&self) -> bool {
+        *self == PartOutput::Bytes
+    }
+}
+
+/// A configuration of decomposed regexes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DecomposedRegexConfig {
     pub parts: VecDeque<RegexPartConfig>,
+    /// The match revealed by the circuit, see [`MatchPolicy`].
+    #[serde(
+        default,
+        alias = "matchPolicy",
+        skip_serializing_if = "MatchPolicy::is_greedy"
+    )]
+    pub match_policy: MatchPolicy,
+    /// The constraints between the substrings of the parts, which the circuit enforces.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<PartConstraint>,
+}
+
+impl DecomposedRegexConfig {
+    /// Creates a configuration of the given parts with the default options.
+    pub fn new(parts: impl IntoIterator<Item = RegexPartConfig>) -> Self {
+        Self {
+            parts: parts.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the match policy.
+    pub fn with_match_policy(mut self, match_policy: MatchPolicy) -> Self {
+        self.match_policy = match_policy;
+        self
+    }
+
+    /// Sets the constraints between the substrings of the parts.
+    pub fn with_constraints(mut self, constraints: Vec<PartConstraint>) -> Self {
+        self.constraints = constraints;
+        self
+    }
+}
+
+#[cfg(test)]
+impl DecomposedRegexConfig {
+    /// Creates a configuration of the parts given by their regex and whether they are public, as the tests use.
+    pub(crate) fn from_parts(parts: &[(&str, bool)]) -> Self {
+        Self::new(parts.iter().map(|&(regex_def, is_public)| RegexPartConfig {
+            is_public,
+            regex_def: regex_def.to_string(),
+            ..Default::default()
+        }))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +130,19 @@ pub struct DFAGraph {
 pub struct SubstringDefinitions {
     pub substring_ranges: Vec<BTreeSet<(usize, usize)>>,
     pub substring_boundaries: Option<Vec<(BTreeSet<usize>, BTreeSet<usize>)>>,
+    /// The `max_len` of each substring, or empty if no public part has one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub max_lens: Vec<Option<usize>>,
+    /// Whether each substring is committed, or empty if no public part is.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub committed: Vec<bool>,
+    /// The pairs of substrings constrained to be equal, by their indexes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub equal: Vec<(usize, usize)>,
+    /// The number of decimals of each substring with the [`PartOutput::Uint`] output and `None` for the others, or
+    /// empty if no public part has it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uint_decimals: Vec<Option<usize>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +151,8 @@ pub struct RegexAndDFA {
     pub dfa: DFAGraph,
     pub has_end_anchor: bool,
     pub substrings: SubstringDefinitions,
+    #[serde(default, skip_serializing_if = "MatchPolicy::is_greedy")]
+    pub match_policy: MatchPolicy,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
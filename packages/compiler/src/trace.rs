@@ -0,0 +1,1038 @@
+use crate::{
+    errors::CompilerError,
+    ir::{RegexCircuit, RevealMask},
+    structs::{DFAGraph, RegexAndDFA},
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zk_regex_apis::extract_substrs::MatchPolicy;
+
+/// The values of the signals of the generated circuit for one byte of `msg`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByteTrace {
+    /// The byte `msg[index]`, i.e., `in[index+1]`.
+    pub byte: u8,
+    /// The active non-zero DFA states after reading the byte, i.e., `j` such that `states[index+2][j] == 1`.
+    pub active_states: Vec<usize>,
+    /// `from_zero_enabled[index+1]`: no non-zero state is reached by a transition from a non-zero state.
+    pub from_zero_enabled: bool,
+    /// `state_changed[index+1].out`: some non-zero state is active after reading the byte.
+    pub state_changed: bool,
+    /// `is_consecutive[index][2]`: the byte belongs to the last run of states reaching the accept state.
+    pub is_consecutive: bool,
+}
+
+/// The values of the signals of the generated circuit for an input, computed without a witness generator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitTrace {
+    /// The active non-zero DFA states after reading the `255` prepended to `msg`, i.e., `states[1]`.
+    pub initial_states: Vec<usize>,
+    /// The per-byte signals of `msg`, including its zero padding.
+    pub bytes: Vec<ByteTrace>,
+    /// The `out` signal.
+    pub out: bool,
+    /// Whether at most one non-zero state is active and reached by at most one transition at every position, so
+    /// that the circuit generated with the one-hot sums computes the same signals.
+    pub one_hot: bool,
+    /// The `(start, end)` byte ranges of `msg` revealed in each `reveal{idx}` signal.
+    pub reveals: Vec<Vec<(usize, usize)>>,
+    /// The outputs of each public part with a `max_len`, or `None` for the parts without one.
+    pub packed_reveals: Vec<Option<PackedRevealTrace>>,
+    /// Whether the assertions on the outputs of the parts hold, i.e., every substring is at most as long as its
+    /// `max_len`, every substring with the uint output is a number and the substrings constrained to be equal are
+    /// equal.
+    pub constraints_hold: bool,
+}
+
+/// The values of the outputs of a public part with a `max_len`, which are computed from the first run of its
+/// revealed bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackedRevealTrace {
+    /// `reveal{idx}_found`.
+    pub found: bool,
+    /// `reveal{idx}_start`.
+    pub start: usize,
+    /// `reveal{idx}_len`.
+    pub len: usize,
+    /// `reveal{idx}_packed`: the bytes of the substring padded with zeros, or its first `max_len` bytes if it is
+    /// longer.
+    pub packed: Vec<u8>,
+    /// The decimal string of `reveal{idx}_value` if the part has the uint output and the substring is a number.
+    pub value: Option<String>,
+}
+
+/// Returns the transitions of the DFA as `next_states[state]` = `(next_state, bytes)` pairs.
+///
+/// The byte `0` is excluded since the circuit never transitions on it.
+fn collect_transitions(dfa: &DFAGraph) -> Vec<Vec<(usize, Vec<bool>)>> {
+    dfa.states
+        .iter()
+        .map(|state| {
+            state
+                .transitions
+                .iter()
+                .map(|(&next, chars)| {
+                    let mut bytes = vec![false; 256];
+                    for &char in chars.iter().filter(|&&char| char != 0) {
+                        bytes[char as usize] = true;
+                    }
+                    (next, bytes)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Converts the indexes of the set values into `(start, end)` ranges.
+pub(crate) fn collect_spans(values: impl Iterator<Item = bool>) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = vec![];
+    for (idx, value) in values.enumerate() {
+        if !value {
+            continue;
+        }
+        match spans.last_mut() {
+            Some((_, end)) if *end == idx => *end += 1,
+            _ => spans.push((idx, idx + 1)),
+        }
+    }
+    spans
+}
+
+/// Computes the signals of the circuit generated from `regex_and_dfa` for the input `msg`.
+///
+/// This follows the constraints emitted by `generate_state_transition_logic`, `generate_accept_logic`,
+/// `write_consecutive_logic` and `write_substr_logic` in `circom.rs`, so that a circuit returning `out = 0`
+/// can be debugged without generating a witness.
+///
+/// # Arguments
+///
+/// * `regex_and_dfa` - A reference to the `RegexAndDFA` the circuit is generated from.
+/// * `msg` - The `msg` input of the circuit, including its zero padding.
+///
+/// # Returns
+///
+/// A `Result` containing the `CircuitTrace` or a `CompilerError` if the circuit cannot be generated or `msg`
+/// contains the byte `255`, which the circuit rejects.
+pub(crate) fn trace_circuit(
+    regex_and_dfa: &RegexAndDFA,
+    msg: &[u8],
+) -> Result<CircuitTrace, CompilerError> {
+    check_msg(msg)?;
+
+    let dfa = &regex_and_dfa.dfa;
+    let state_len = dfa.states.len();
+    let circuit = RegexCircuit::new(regex_and_dfa);
+    let accept_state = circuit.unique_accept_state()?;
+    let transitions = collect_transitions(dfa);
+
+    let msg_bytes = msg.len();
+    let num_bytes = msg_bytes + 1;
+    let input = std::iter::once(255)
+        .chain(msg.iter().copied())
+        .collect::<Vec<u8>>();
+
+    let mut states = vec![vec![false; state_len]; num_bytes + 1];
+    let mut from_zero_enabled = vec![false; num_bytes + 1];
+    let mut state_changed = vec![false; num_bytes];
+    let mut one_hot = true;
+    for i in 0..num_bytes {
+        states[i][0] = true;
+        let byte = input[i] as usize;
+
+        // states_tmp: the transitions from the non-zero states.
+        let mut next_states = vec![false; state_len];
+        for (prev, prev_transitions) in transitions.iter().enumerate().skip(1) {
+            if !states[i][prev] {
+                continue;
+            }
+            for (next, bytes) in prev_transitions {
+                if *next != 0 && bytes[byte] {
+                    one_hot &= !next_states[*next];
+                    next_states[*next] = true;
+                }
+            }
+        }
+
+        // The transitions from the zero state are enabled only if no other transition is taken.
+        from_zero_enabled[i] = !next_states.iter().skip(1).any(|&state| state);
+        if from_zero_enabled[i] {
+            for (next, bytes) in &transitions[0] {
+                if *next != 0 && bytes[byte] {
+                    next_states[*next] = true;
+                }
+            }
+        }
+
+        state_changed[i] = next_states.iter().skip(1).any(|&state| state);
+        one_hot &= next_states.iter().skip(1).filter(|&&state| state).count() <= 1;
+        states[i + 1] = next_states;
+    }
+
+    finish_trace(
+        &circuit,
+        msg,
+        accept_state,
+        &states,
+        &from_zero_enabled,
+        &state_changed,
+        one_hot,
+    )
+}
+
+/// Computes the signals of the circuit generated from `regex_and_dfa` with an anchored search mode for the input
+/// `msg`, whose single run of the DFA starts at `msg[start_index]`.
+///
+/// This follows the constraints emitted by `gen_circom_anchored` in `circom.rs`, where `start_index` is `0` for
+/// `SearchMode::Anchored` and the `start_index` input for `SearchMode::AtIndex`.
+///
+/// # Arguments
+///
+/// * `regex_and_dfa` - A reference to the `RegexAndDFA` the circuit is generated from.
+/// * `msg` - The `msg` input of the circuit, including its zero padding.
+/// * `start_index` - The index of `msg` where the run starts.
+///
+/// # Returns
+///
+/// A `Result` containing the `CircuitTrace` or a `CompilerError` if the circuit cannot be generated or `msg`
+/// contains the byte `255`, which the circuit rejects.
+#[cfg(test)]
+pub(crate) fn trace_anchored_circuit(
+    regex_and_dfa: &RegexAndDFA,
+    msg: &[u8],
+    start_index: usize,
+) -> Result<CircuitTrace, CompilerError> {
+    use crate::ir::AnchoredRun;
+
+    check_msg(msg)?;
+
+    let circuit = RegexCircuit::new(regex_and_dfa);
+    let accept_state = circuit.unique_accept_state()?;
+    let AnchoredRun {
+        init_state,
+        transitions,
+    } = circuit.anchored_run()?;
+    let state_len = circuit.num_states;
+
+    let num_bytes = msg.len() + 1;
+    let input = std::iter::once(255)
+        .chain(msg.iter().copied())
+        .collect::<Vec<u8>>();
+
+    let mut states = vec![vec![false; state_len]; num_bytes + 1];
+    let mut from_zero_enabled = vec![false; num_bytes + 1];
+    let mut state_changed = vec![false; num_bytes];
+    let mut one_hot = true;
+    for i in 0..num_bytes {
+        let byte = input[i];
+        let mut next_states = vec![false; state_len];
+        for transition in &transitions {
+            if states[i][transition.from]
+                && transition
+                    .ranges
+                    .iter()
+                    .any(|&(min, max)| (min..=max).contains(&byte))
+            {
+                one_hot &= !next_states[transition.to];
+                next_states[transition.to] = true;
+                from_zero_enabled[i] |= transition.from == 0;
+            }
+        }
+        if i == start_index {
+            next_states[init_state] = true;
+            next_states[0] |= transitions.iter().any(|transition| transition.from == 0);
+        }
+
+        state_changed[i] = next_states.iter().skip(1).any(|&state| state);
+        one_hot &= next_states.iter().skip(1).filter(|&&state| state).count() <= 1;
+        states[i + 1] = next_states;
+    }
+
+    finish_trace(
+        &circuit,
+        msg,
+        accept_state,
+        &states,
+        &from_zero_enabled,
+        &state_changed,
+        one_hot,
+    )
+}
+
+/// Returns whether the constraints of `occurrence` hold in the circuit generated from `regex_and_dfa` for the input
+/// `msg`, following `write_occurrence_logic` in `circom.rs`, where the greedy match policy is replaced by
+/// [`MatchPolicy::LeftmostLongest`].
+///
+/// # Arguments
+///
+/// * `regex_and_dfa` - A reference to the `RegexAndDFA` the circuit is generated from.
+/// * `msg` - The `msg` input of the circuit, including its zero padding.
+/// * `occurrence` - The `Occurrence` of the circuit, which is not `Occurrence::Any`.
+///
+/// # Returns
+///
+/// A `Result` containing whether the constraints hold or a `CompilerError` if the circuit cannot be generated.
+#[cfg(test)]
+pub(crate) fn trace_occurrence(
+    regex_and_dfa: &RegexAndDFA,
+    msg: &[u8],
+    occurrence: crate::circom::Occurrence,
+) -> Result<bool, CompilerError> {
+    use crate::{circom::Occurrence, negate::SearchDfa};
+
+    let trace = trace_circuit(regex_and_dfa, msg)?;
+    let circuit = RegexCircuit::new(regex_and_dfa);
+    let accept_state = circuit.unique_accept_state()?;
+    let search = SearchDfa::new(&circuit)?;
+
+    // accepted[i] and from_zero_enabled[i] are the signals of `states[i]`, where `states[0]` is the state `0`.
+    let accepted = vec![false, trace.initial_states.contains(&accept_state)]
+        .into_iter()
+        .chain(
+            trace
+                .bytes
+                .iter()
+                .map(|byte| byte.active_states.contains(&accept_state)),
+        )
+        .collect::<Vec<_>>();
+    let from_zero_enabled = std::iter::once(false)
+        .chain(trace.bytes.iter().map(|byte| byte.from_zero_enabled))
+        .collect::<Vec<_>>();
+    let policy = match circuit.match_policy {
+        MatchPolicy::Greedy => MatchPolicy::LeftmostLongest,
+        policy => policy,
+    };
+    let in_match = compute_in_match(policy, |i| accepted[i], &from_zero_enabled, msg.len());
+
+    // search_accepted[i+1] is whether the SearchDfa accepts after `in[i]`.
+    let mut state = search.next_state(0, 255);
+    let mut search_accepted = vec![false, search.accept_states.contains(&state)];
+    for &byte in msg {
+        state = search.next_state(state, byte);
+        search_accepted.push(search.accept_states.contains(&state));
+    }
+
+    let mut holds = !search_accepted[1] || accepted[1];
+    let mut accepted_before = false;
+    for i in 0..msg.len() {
+        accepted_before |= accepted[i + 1];
+        let search_first = search_accepted[i + 2] && !accepted_before;
+        holds &= !search_first || accepted[i + 2];
+        if occurrence == Occurrence::Unique {
+            holds &= !search_accepted[i + 2] || in_match[i];
+        }
+    }
+    Ok(holds)
+}
+
+/// Returns an error if `msg` contains the byte `255`, which the circuit rejects.
+fn check_msg(msg: &[u8]) -> Result<(), CompilerError> {
+    match msg.iter().position(|&byte| byte == 255) {
+        Some(idx) => Err(CompilerError::GenericError(format!(
+            "The byte at index {} is 255, which is not allowed in the circuit input",
+            idx
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Computes the `in_match` signals of a leftmost match policy from the `accepted` signals of the accept state and
+/// the `from_zero_enabled` signals, following `write_leftmost_logic` in `circom.rs`.
+///
+/// Every signal is `1` with the greedy match policy, which has no `in_match` signals.
+fn compute_in_match(
+    policy: MatchPolicy,
+    accepted: impl Fn(usize) -> bool,
+    from_zero_enabled: &[bool],
+    msg_bytes: usize,
+) -> Vec<bool> {
+    let mut in_match = vec![true; msg_bytes];
+    if !policy.is_greedy() {
+        let mut accepted_before = false;
+        for i in 0..msg_bytes {
+            accepted_before |= accepted(i + 1);
+            in_match[i] = !accepted_before
+                || (policy == MatchPolicy::LeftmostLongest
+                    && (i == 0 || in_match[i - 1])
+                    && !from_zero_enabled[i + 1]);
+        }
+    }
+    in_match
+}
+
+/// Computes the outputs of a public part with a `max_len` from its `is_reveal` signals and whether a transition
+/// from a start state of the substring is taken at each byte, following `write_packed_reveal_logic` in
+/// `circom.rs`: the first revealed run starts at the first byte read by such a transition and ends before the
+/// circuit restarts from the state `0`.
+fn trace_packed_reveal(
+    reveal: &RevealMask,
+    max_len: usize,
+    msg: &[u8],
+    is_reveal: &[bool],
+    from_start: &[bool],
+    from_zero_enabled: &[bool],
+) -> PackedRevealTrace {
+    let mut start = None;
+    let mut in_first = vec![false; msg.len()];
+    for i in 0..msg.len() {
+        let starts_here = is_reveal[i] && from_start[i] && start.is_none();
+        let continues = i > 0 && in_first[i - 1] && is_reveal[i];
+        in_first[i] = starts_here || (continues && !from_zero_enabled[i + 1]);
+        if starts_here {
+            start = Some(i);
+        }
+    }
+    let packed = (0..max_len)
+        .map(|j| match start {
+            Some(start) if start + j < msg.len() && in_first[start + j] => msg[start + j],
+            _ => 0,
+        })
+        .collect::<Vec<_>>();
+    let len = in_first.iter().filter(|&&in_first| in_first).count();
+    let value = reveal
+        .uint_decimals
+        .and_then(|decimals| trace_uint_value(&packed, len, decimals));
+    PackedRevealTrace {
+        found: start.is_some(),
+        start: start.unwrap_or(0),
+        len,
+        packed,
+        value,
+    }
+}
+
+/// Computes the `reveal{idx}_value` output of a substring with the uint output from its packed bytes and length,
+/// following `write_uint_logic` in `circom.rs`.
+///
+/// # Returns
+///
+/// The decimal string of the value, or `None` if the constraints on the bytes of the substring do not hold.
+fn trace_uint_value(packed: &[u8], len: usize, decimals: usize) -> Option<String> {
+    // acc concatenates the digits, and after_dot counts the decimal points before each byte.
+    let mut acc = String::new();
+    let mut after_dot = 0;
+    let mut num_decimals = 0;
+    for (j, &byte) in packed.iter().enumerate() {
+        let in_part = j < len;
+        let is_digit = byte.is_ascii_digit();
+        let is_dot = decimals > 0 && byte == b'.';
+        if in_part && !is_digit && !is_dot {
+            return None;
+        }
+        if in_part && is_digit {
+            acc.push(byte as char);
+            num_decimals += after_dot;
+        }
+        if in_part && is_dot {
+            after_dot += 1;
+        }
+    }
+    if after_dot > 1 || num_decimals > decimals {
+        return None;
+    }
+    acc += &"0".repeat(decimals - num_decimals);
+    let value = acc.trim_start_matches('0');
+    Some(if value.is_empty() { "0" } else { value }.to_string())
+}
+
+/// Computes the accept, consecutive, reveal and packed reveal signals of a trace from its `states`,
+/// `from_zero_enabled` and `state_changed` signals.
+fn finish_trace(
+    circuit: &RegexCircuit,
+    msg: &[u8],
+    accept_state: usize,
+    states: &[Vec<bool>],
+    from_zero_enabled: &[bool],
+    state_changed: &[bool],
+    one_hot: bool,
+) -> Result<CircuitTrace, CompilerError> {
+    let msg_bytes = msg.len();
+    let num_bytes = msg_bytes + 1;
+    let state_len = states[0].len();
+    let is_accepted = states.iter().any(|state| state[accept_state]);
+    let out = if circuit.has_end_anchor {
+        let padding_start = 1 + msg.iter().filter(|&&byte| byte != 0).count();
+        is_accepted && padding_start < num_bytes && states[padding_start][accept_state]
+    } else {
+        is_accepted
+    };
+
+    // is_consecutive[i][2] is computed backward from the end of msg.
+    let mut is_consecutive = vec![false; msg_bytes + 1];
+    for i in (0..msg_bytes).rev() {
+        let next = is_consecutive[i + 1];
+        let is_accept_or_next = states[i + 2][accept_state] || next;
+        let is_changed_and_consecutive = state_changed[i + 1] && is_accept_or_next;
+        is_consecutive[i] = (!from_zero_enabled[i + 2] || states[i + 2][accept_state])
+            && is_changed_and_consecutive;
+    }
+
+    // in_match[i] restricts the reveals to the first run reaching the accept state under a leftmost policy.
+    let in_match = compute_in_match(
+        circuit.match_policy,
+        |i| states[i][accept_state],
+        from_zero_enabled,
+        msg_bytes,
+    );
+
+    // Whether the transition `(cur, next)` is taken at msg[i].
+    let is_taken = |(cur, next): (usize, usize), i: usize| {
+        let prev_state = if cur == 0 {
+            from_zero_enabled[i + 1] && states[i + 1][cur]
+        } else {
+            !from_zero_enabled[i + 1] && states[i + 1][cur]
+        };
+        prev_state && states[i + 2][next]
+    };
+    let mut reveals = vec![];
+    let mut packed_reveals = vec![];
+    for reveal in &circuit.reveals {
+        let is_reveal = (0..msg_bytes)
+            .map(|i| {
+                let is_substr = reveal
+                    .transitions
+                    .iter()
+                    .any(|&transition| is_taken(transition, i));
+                out && is_substr && is_consecutive[i] && in_match[i]
+            })
+            .collect::<Vec<_>>();
+        reveals.push(collect_spans(is_reveal.iter().copied()));
+        packed_reveals.push(match reveal.max_len {
+            Some(max_len) => {
+                let transitions = reveal.transitions.iter().collect::<Vec<_>>();
+                let start_transitions = reveal
+                    .start_transitions()?
+                    .into_iter()
+                    .map(|trans_idx| *transitions[trans_idx])
+                    .collect::<Vec<_>>();
+                let from_start = (0..msg_bytes)
+                    .map(|i| {
+                        start_transitions
+                            .iter()
+                            .any(|&transition| is_taken(transition, i))
+                    })
+                    .collect::<Vec<_>>();
+                Some(trace_packed_reveal(
+                    reveal,
+                    max_len,
+                    msg,
+                    &is_reveal,
+                    &from_start,
+                    from_zero_enabled,
+                ))
+            }
+            None => None,
+        });
+    }
+
+    let mut constraints_hold =
+        circuit
+            .reveals
+            .iter()
+            .zip(&packed_reveals)
+            .all(|(reveal, packed)| match (reveal.max_len, packed) {
+                (Some(max_len), Some(packed)) => {
+                    packed.len <= max_len
+                        && (reveal.uint_decimals.is_none() || packed.value.is_some())
+                }
+                _ => true,
+            });
+    for &(a, b) in &circuit.equalities {
+        if let (Some(a), Some(b)) = (&packed_reveals[a], &packed_reveals[b]) {
+            let max_len = a.packed.len().min(b.packed.len());
+            constraints_hold &= a.len == b.len && a.packed[..max_len] == b.packed[..max_len];
+        }
+    }
+
+    let active_states =
+        |state: &[bool]| (1..state_len).filter(|&idx| state[idx]).collect::<Vec<_>>();
+
+    Ok(CircuitTrace {
+        initial_states: active_states(&states[1]),
+        bytes: (0..msg_bytes)
+            .map(|i| ByteTrace {
+                byte: msg[i],
+                active_states: active_states(&states[i + 2]),
+                from_zero_enabled: from_zero_enabled[i + 1],
+                state_changed: state_changed[i + 1],
+                is_consecutive: is_consecutive[i],
+            })
+            .collect(),
+        out,
+        one_hot,
+        reveals,
+        packed_reveals,
+        constraints_hold,
+    })
+}
+
+/// Formats a byte as a printable character or an escape sequence.
+fn format_byte(byte: u8) -> String {
+    match byte {
+        b'\r' => "\\r".to_string(),
+        b'\n' => "\\n".to_string(),
+        b'\t' => "\\t".to_string(),
+        0x20..=0x7e => (byte as char).to_string(),
+        _ => format!("\\x{:02x}", byte),
+    }
+}
+
+impl fmt::Display for CircuitTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "initial states: {:?}", self.initial_states)?;
+        writeln!(
+            f,
+            "{:>6}  {:<6}  {:<24}  {:>17}  {:>13}  {:>14}",
+            "index", "byte", "states", "from_zero_enabled", "state_changed", "is_consecutive"
+        )?;
+        for (idx, byte) in self.bytes.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>6}  {:<6}  {:<24}  {:>17}  {:>13}  {:>14}",
+                idx,
+                format_byte(byte.byte),
+                format!("{:?}", byte.active_states),
+                byte.from_zero_enabled as u8,
+                byte.state_changed as u8,
+                byte.is_consecutive as u8
+            )?;
+        }
+        writeln!(f, "out: {}", self.out as u8)?;
+        writeln!(f, "one-hot: {}", self.one_hot as u8)?;
+        for (idx, spans) in self.reveals.iter().enumerate() {
+            let spans = spans
+                .iter()
+                .map(|&(start, end)| {
+                    let substr = self.bytes[start..end]
+                        .iter()
+                        .map(|byte| format_byte(byte.byte))
+                        .collect::<String>();
+                    format!("{}..{} \"{}\"", start, end, substr)
+                })
+                .collect::<Vec<_>>();
+            writeln!(f, "reveal{}: [{}]", idx, spans.join(", "))?;
+        }
+        for (idx, packed) in self.packed_reveals.iter().enumerate() {
+            if let Some(packed) = packed {
+                let substr = packed
+                    .packed
+                    .iter()
+                    .take(packed.len)
+                    .map(|&byte| format_byte(byte))
+                    .collect::<String>();
+                write!(
+                    f,
+                    "reveal{}: found {}, start {}, len {}, packed \"{}\"",
+                    idx, packed.found as u8, packed.start, packed.len, substr
+                )?;
+                match &packed.value {
+                    Some(value) => writeln!(f, ", value {}", value)?,
+                    None => writeln!(f)?,
+                }
+            }
+        }
+        if self.packed_reveals.iter().any(Option::is_some) {
+            writeln!(f, "constraints hold: {}", self.constraints_hold as u8)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{circom::Occurrence, regex::get_regex_and_dfa, registry_pattern_config};
+    use zk_regex_apis::{
+        commitment::commit_bytes,
+        extract_substrs::{
+            check_part_constraints, extract_substr_idxes_from_bytes_with_options,
+            extract_substr_idxes_with_options, parse_decomposed_regex,
+        },
+        numeric::parse_uint,
+        packing::{pack_bytes, unpack_bytes},
+    };
+
+    fn trace_builtin(name: &str, input: &str, msg_bytes: usize) -> CircuitTrace {
+        let registry = zk_regex_apis::registry::PatternRegistry::builtin();
+        let mut config = registry_pattern_config(&registry, name).unwrap();
+        let regex_and_dfa = get_regex_and_dfa(&mut config).unwrap();
+        let mut msg = input.as_bytes().to_vec();
+        msg.resize(msg_bytes, 0);
+        trace_circuit(&regex_and_dfa, &msg).unwrap()
+    }
+
+    #[test]
+    fn test_trace_matches_extractor() {
+        for (name, input) in [
+            ("from_addr", "from:suegamisora@gmail.com\r\n"),
+            ("subject_all", "to:a@b.com\r\nsubject:hello zk\r\n"),
+            (
+                "timestamp",
+                "dkim-signature:v=1; a=rsa-sha256; t=1694989812; x=1695594612;",
+            ),
+        ] {
+            let trace = trace_builtin(name, input, 128);
+            assert!(trace.out, "{}", name);
+            assert!(trace.one_hot, "{}", name);
+            assert_eq!(trace.bytes.len(), 128);
+            assert_eq!(
+                trace.reveals,
+                vec![zk_regex_apis::registry::extract_by_name(name, input).unwrap()],
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_trace_without_match() {
+        let trace = trace_builtin("subject_all", "subject:no line break", 32);
+        assert!(!trace.out);
+        assert!(trace.reveals.iter().all(|spans| spans.is_empty()));
+        assert!(trace.bytes[7].active_states.len() == 1);
+        assert!(!trace.bytes[7].from_zero_enabled);
+        assert!(trace.bytes[30].from_zero_enabled);
+    }
+
+    #[test]
+    fn test_trace_match_policies() {
+        let msg = b"x=abc;x=de;\0\0";
+        let input = "x=abc;x=de;";
+        for (policy, reveals) in [
+            (MatchPolicy::Greedy, vec![(2, 5), (8, 10)]),
+            (MatchPolicy::LeftmostLongest, vec![(2, 5)]),
+            (MatchPolicy::LeftmostShortest, vec![(2, 3)]),
+        ] {
+            let mut config =
+                crate::DecomposedRegexConfig::from_parts(&[("x=", false), ("[a-z]+", true)])
+                    .with_match_policy(policy);
+            let trace = trace_circuit(&get_regex_and_dfa(&mut config).unwrap(), msg).unwrap();
+            assert!(trace.out);
+            assert_eq!(trace.reveals, vec![reveals.clone()], "{:?}", policy);
+
+            if !policy.is_greedy() {
+                let (apis_config, apis_options) =
+                    parse_decomposed_regex(&serde_json::to_value(&config).unwrap()).unwrap();
+                assert_eq!(
+                    extract_substr_idxes_with_options(input, &apis_config, &apis_options, false)
+                        .unwrap(),
+                    reveals,
+                    "{:?}",
+                    policy
+                );
+            }
+        }
+
+        let mut config = crate::DecomposedRegexConfig::from_parts(&[("[a-z]+$", true)])
+            .with_match_policy(MatchPolicy::LeftmostLongest);
+        assert!(get_regex_and_dfa(&mut config).is_err());
+    }
+
+    #[test]
+    fn test_trace_matches_leftmost_extraction() {
+        for (parts, input) in [
+            (vec![("x=", true), ("[a-z]+", true)], "x=abc;x=de;"),
+            (vec![("x", true), ("(ab)*c", true)], "zxabababxababcz"),
+            (
+                vec![
+                    ("(\r\n|^)subject:", true),
+                    ("[^\r\n]+", true),
+                    ("\r\n", true),
+                ],
+                "to:a@b.com\r\nsubject:hello zk\r\nsubject:again\r\n",
+            ),
+            (
+                vec![("[a-z]+", true), ("@", true), ("[a-z.]+", true)],
+                "mail me@foo.com or bob@bar.org",
+            ),
+            (vec![("=", true), ("[0-9]*", true), (";", true)], "a=;b=12;"),
+            (vec![("a+", true), ("(b|ab)", true)], "caaab"),
+        ] {
+            for policy in [MatchPolicy::LeftmostLongest, MatchPolicy::LeftmostShortest] {
+                let mut config =
+                    crate::DecomposedRegexConfig::from_parts(&parts).with_match_policy(policy);
+                let trace =
+                    trace_circuit(&get_regex_and_dfa(&mut config).unwrap(), input.as_bytes())
+                        .unwrap();
+                let (apis_config, apis_options) =
+                    parse_decomposed_regex(&serde_json::to_value(&config).unwrap()).unwrap();
+                let spans = extract_substr_idxes_from_bytes_with_options(
+                    input.as_bytes(),
+                    &apis_config,
+                    &apis_options,
+                    false,
+                )
+                .unwrap();
+                assert!(trace.out, "{:?} {:?}", parts, policy);
+                assert_eq!(
+                    trace.reveals.concat(),
+                    spans
+                        .into_iter()
+                        .filter(|(start, end)| start < end)
+                        .collect::<Vec<_>>(),
+                    "{:?} {:?}",
+                    parts,
+                    policy
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_trace_anchored_start() {
+        let mut config =
+            crate::DecomposedRegexConfig::from_parts(&[("(\r\n|^)from:", false), ("[a-z]+", true)]);
+        let regex_and_dfa = get_regex_and_dfa(&mut config).unwrap();
+        for (input, start_index, reveals) in [
+            ("from:abc\r\n", 0, vec![(5, 8)]),
+            ("\r\nfrom:abc\r\n", 0, vec![(7, 10)]),
+            ("x\r\nfrom:ab", 1, vec![(8, 10)]),
+            ("x\r\nfrom:ab", 3, vec![(8, 10)]),
+            ("x\r\nfrom:ab", 0, vec![]),
+            ("x\r\nfrom:ab", 2, vec![]),
+            ("\nfrom:abc", 0, vec![]),
+        ] {
+            let mut msg = input.as_bytes().to_vec();
+            msg.resize(16, 0);
+            let trace = trace_anchored_circuit(&regex_and_dfa, &msg, start_index).unwrap();
+            assert_eq!(
+                trace.out,
+                !reveals.is_empty(),
+                "{:?} {}",
+                input,
+                start_index
+            );
+            assert_eq!(trace.reveals, vec![reveals], "{:?} {}", input, start_index);
+            assert!(trace.one_hot);
+        }
+    }
+
+    #[test]
+    fn test_trace_packed_reveals() {
+        let config = |parts: &[(&str, bool)], max_len| {
+            let mut config = crate::DecomposedRegexConfig::from_parts(parts);
+            for part in config.parts.iter_mut().filter(|part| part.is_public) {
+                part.max_len = Some(max_len);
+            }
+            config
+        };
+        let subject = [
+            ("(\r\n|^)subject:", false),
+            ("[^\r\n]+", true),
+            ("\r\n", false),
+        ];
+        for (parts, input, max_len) in [
+            (
+                &subject[..],
+                "to:a@b.com\r\nsubject:hello zk\r\nsubject:again\r\n",
+                16,
+            ),
+            (&subject[..], "subject:hi\r\n", 4),
+            (&[("x=", false), ("[a-z]+", true)][..], "x=abc;x=de;", 4),
+            // The second run is revealed right after the first one, so the first run ends where the circuit
+            // restarts from the state `0`.
+            (&[("[a-z]+;", true)][..], "ab;cd;", 8),
+        ] {
+            let mut config = config(parts, max_len);
+            let trace =
+                trace_circuit(&get_regex_and_dfa(&mut config).unwrap(), input.as_bytes()).unwrap();
+            let (apis_config, mut apis_options) =
+                parse_decomposed_regex(&serde_json::to_value(&config).unwrap()).unwrap();
+            apis_options.match_policy = MatchPolicy::LeftmostLongest;
+            let (start, end) =
+                extract_substr_idxes_with_options(input, &apis_config, &apis_options, false)
+                    .unwrap()[0];
+            let mut substr = input.as_bytes()[start..end].to_vec();
+            substr.resize(max_len, 0);
+
+            let packed = trace.packed_reveals[0].as_ref().unwrap();
+            assert!(packed.found && trace.constraints_hold, "{:?}", input);
+            assert_eq!(
+                (packed.start, packed.len),
+                (start, end - start),
+                "{:?}",
+                input
+            );
+            assert_eq!(packed.packed, substr, "{:?}", input);
+            // The `reveal{idx}_fields` outputs unpack to the packed bytes.
+            assert_eq!(
+                unpack_bytes(&pack_bytes(&packed.packed), max_len).unwrap(),
+                substr
+            );
+        }
+
+        let regex_and_dfa = get_regex_and_dfa(&mut config(&subject, 4)).unwrap();
+        let trace = trace_circuit(&regex_and_dfa, b"subject:hello\r\n\0").unwrap();
+        let packed = trace.packed_reveals[0].as_ref().unwrap();
+        assert_eq!((packed.found, packed.start, packed.len), (true, 8, 5));
+        assert_eq!(packed.packed, b"hell");
+        assert!(!trace.constraints_hold);
+        let trace = trace_circuit(&regex_and_dfa, b"subject:hello\0\0").unwrap();
+        let packed = trace.packed_reveals[0].as_ref().unwrap();
+        assert_eq!((packed.found, packed.start, packed.len), (false, 0, 0));
+        assert_eq!(packed.packed, [0; 4]);
+        assert!(trace.constraints_hold);
+    }
+
+    #[test]
+    fn test_trace_committed_reveals() {
+        let packed_reveals = [false, true].map(|committed| {
+            let mut config = crate::DecomposedRegexConfig::from_parts(&[
+                ("subject:", false),
+                ("[a-z]+", true),
+                ("\r\n", false),
+            ]);
+            config.parts[1].max_len = Some(8);
+            config.parts[1].committed = committed;
+            trace_circuit(
+                &get_regex_and_dfa(&mut config).unwrap(),
+                b"subject:hello\r\n\0",
+            )
+            .unwrap()
+            .packed_reveals
+        });
+        // The commitment hashes the packed bytes output by the uncommitted part.
+        assert_eq!(packed_reveals[0], packed_reveals[1]);
+        let packed = packed_reveals[1][0].as_ref().unwrap();
+        assert_eq!(
+            commit_bytes(&packed.packed, 8, "12345").unwrap(),
+            commit_bytes(b"hello", 8, "12345").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_trace_equal_reveals() {
+        let mut config = crate::DecomposedRegexConfig::from_parts(&[
+            ("from:[a-z]+@", false),
+            ("[a-z]+", true),
+            ("\\.com;id:[a-z]+@", false),
+            ("[a-z]+", true),
+            ("\\.com", false),
+        ])
+        .with_constraints(serde_json::from_str(r#"[{"equal": [1, 3]}]"#).unwrap());
+        config.parts[1].max_len = Some(6);
+        config.parts[3].max_len = Some(4);
+        let regex_and_dfa = get_regex_and_dfa(&mut config).unwrap();
+        let (apis_config, apis_options) =
+            parse_decomposed_regex(&serde_json::to_value(&config).unwrap()).unwrap();
+        for (input, holds) in [
+            ("from:a@abc.com;id:b@abc.com", true),
+            ("from:a@abc.com;id:b@abd.com", false),
+            ("from:a@ab.com;id:b@abc.com", false),
+            // Longer than the max_len of the part 3.
+            ("from:a@abcde.com;id:b@abcde.com", false),
+        ] {
+            let trace = trace_circuit(&regex_and_dfa, input.as_bytes()).unwrap();
+            assert!(trace.out, "{}", input);
+            assert_eq!(trace.constraints_hold, holds, "{}", input);
+            assert_eq!(
+                check_part_constraints(
+                    input.as_bytes(),
+                    &[(
+                        "from".to_string(),
+                        apis_config.clone(),
+                        apis_options.clone()
+                    )]
+                )
+                .is_ok(),
+                holds,
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_trace_uint_reveals() {
+        for decimals in [0, 2] {
+            let mut config = crate::DecomposedRegexConfig::from_parts(&[
+                ("(\r\n|^)amount:", false),
+                ("[0-9a-z.]+", true),
+                ("\r\n", false),
+            ]);
+            config.parts[1].max_len = Some(6);
+            config.parts[1].output = crate::PartOutput::Uint;
+            config.parts[1].decimals = Some(decimals).filter(|&decimals| decimals > 0);
+            let regex_and_dfa = get_regex_and_dfa(&mut config).unwrap();
+            for substr in [
+                "1250", "007", "12.5", ".05", "12.", "1.2.3", "1.234", "12a", "0x10",
+            ] {
+                let input = format!("amount:{}\r\n", substr);
+                let trace = trace_circuit(&regex_and_dfa, input.as_bytes()).unwrap();
+                let value = parse_uint(substr.as_bytes(), decimals).ok();
+                assert_eq!(
+                    trace.packed_reveals[0].as_ref().unwrap().value,
+                    value,
+                    "{} {}",
+                    substr,
+                    decimals
+                );
+                assert_eq!(
+                    trace.constraints_hold,
+                    value.is_some(),
+                    "{} {}",
+                    substr,
+                    decimals
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_trace_occurrence() {
+        for (parts, input, first, unique) in [
+            (vec![("abac", true)], "xabac;", true, true),
+            (vec![("abac", true)], "abac;abac", true, false),
+            // The run starting at the first `a` fails at the second `b`, missing the occurrence starting inside it.
+            (vec![("abac", true)], "ababac;abac", false, false),
+            // The occurrences ending inside the first match are allowed.
+            (vec![("x=", false), ("[a-z]+", true)], "x=abc;", true, true),
+            (
+                vec![("x=", false), ("[a-z]+", true)],
+                "x=ab;x=c",
+                true,
+                false,
+            ),
+            (
+                vec![("subject:", false), ("[a-z]+", true), ("\r\n", false)],
+                "subject:a\r\nsubject:b\r\n",
+                true,
+                false,
+            ),
+        ] {
+            let regex_and_dfa =
+                get_regex_and_dfa(&mut crate::DecomposedRegexConfig::from_parts(&parts)).unwrap();
+            let msg = input.as_bytes();
+            assert_eq!(
+                trace_occurrence(&regex_and_dfa, msg, Occurrence::First).unwrap(),
+                first,
+                "{:?}",
+                input
+            );
+            assert_eq!(
+                trace_occurrence(&regex_and_dfa, msg, Occurrence::Unique).unwrap(),
+                unique,
+                "{:?}",
+                input
+            );
+
+            // The first occurrence ends where the first substring matching the regex ends.
+            let full_match =
+                ::regex::Regex::new(&format!("^(?:{})$", regex_and_dfa.regex_pattern)).unwrap();
+            let first_end = (1..=input.len())
+                .find(|&end| (0..end).any(|start| full_match.is_match(&input[start..end])))
+                .unwrap();
+            let accept_state = RegexCircuit::new(&regex_and_dfa)
+                .unique_accept_state()
+                .unwrap();
+            let trace = trace_circuit(&regex_and_dfa, msg).unwrap();
+            assert_eq!(
+                trace.bytes[first_end - 1]
+                    .active_states
+                    .contains(&accept_state),
+                first,
+                "{:?}",
+                input
+            );
+        }
+    }
+}
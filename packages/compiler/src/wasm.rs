@@ -4,7 +4,7 @@ use serde_wasm_bindgen::from_value;
 use std::panic;
 use wasm_bindgen::prelude::*;
 
-use self::circom::gen_circom_string;
+use self::circom::{gen_circom_string, gen_circom_string_with_options};
 
 #[wasm_bindgen]
 #[allow(non_snake_case)]
@@ -28,6 +28,56 @@ pub fn genFromDecomposed(
         .map_err(|e| JsValue::from_str(&format!("Failed to generate Circom string: {}", e)))
 }
 
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn genFromDecomposedWithOptions(
+    decomposedRegexJson: &str,
+    circomTemplateName: &str,
+    circomOptionsJson: &str,
+) -> Result<String, JsValue> {
+    let mut decomposed_regex_config: DecomposedRegexConfig =
+        serde_json::from_str(decomposedRegexJson).map_err(|e| {
+            JsValue::from_str(&format!("failed to parse decomposed_regex json: {}", e))
+        })?;
+    let circom_options: CircomOptions = serde_json::from_str(circomOptionsJson)
+        .map_err(|e| JsValue::from_str(&format!("failed to parse circom options json: {}", e)))?;
+
+    let regex_and_dfa = get_regex_and_dfa(&mut decomposed_regex_config).map_err(|e| {
+        JsValue::from_str(&format!(
+            "failed to convert the decomposed regex to dfa: {}",
+            e
+        ))
+    })?;
+
+    gen_circom_string_with_options(&regex_and_dfa, circomTemplateName, &circom_options)
+        .map_err(|e| JsValue::from_str(&format!("Failed to generate Circom string: {}", e)))
+}
+
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn genCombinedFromDecomposed(
+    regexesJson: &str,
+    circomTemplateName: &str,
+    combine: &str,
+    circomOptionsJson: &str,
+) -> Result<String, JsValue> {
+    let mut regexes: Vec<(String, DecomposedRegexConfig)> = serde_json::from_str(regexesJson)
+        .map_err(|e| JsValue::from_str(&format!("failed to parse regexes json: {}", e)))?;
+    let combine: CombineMode = serde_json::from_value(combine.into())
+        .map_err(|e| JsValue::from_str(&format!("failed to parse combine mode: {}", e)))?;
+    let circom_options: CircomOptions = serde_json::from_str(circomOptionsJson)
+        .map_err(|e| JsValue::from_str(&format!("failed to parse circom options json: {}", e)))?;
+
+    gen_combined_circom_string(
+        &mut regexes,
+        circomTemplateName,
+        combine,
+        None,
+        Some(circom_options),
+    )
+    .map_err(|e| JsValue::from_str(&format!("Failed to generate Circom string: {}", e)))
+}
+
 #[wasm_bindgen]
 #[allow(non_snake_case)]
 pub fn genFromRaw(rawRegex: &str, substrsJson: &str, circomTemplateName: &str) -> String {
@@ -59,3 +109,19 @@ pub fn genCircom(decomposedRegex: JsValue, circomTemplateName: &str) -> String {
         .expect("failed to convert the decomposed regex to dfa");
     gen_circom_string(&regex_and_dfa, circomTemplateName).expect("failed to generate circom")
 }
+
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn halo2TableToBinary(tableJson: &str) -> Result<Vec<u8>, JsValue> {
+    Halo2Table::from_json(tableJson)
+        .map(|table| table.to_bytes())
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse the Halo2 table: {}", e)))
+}
+
+#[wasm_bindgen]
+#[allow(non_snake_case)]
+pub fn halo2MultiTableToBinary(tableJson: &str) -> Result<Vec<u8>, JsValue> {
+    Halo2MultiTable::from_json(tableJson)
+        .map(|table| table.to_bytes())
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse the Halo2 multi table: {}", e)))
+}